@@ -1,12 +1,15 @@
 //! Codex Switcher - 账号管理模块
 //! 
 //! 处理多个 Codex 账号的存储、切换和管理
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use crate::endpoints::EndpointConfig;
+use crate::http_client::NetworkSettings;
+use crate::scheduler::JobSettings;
 
 /// 应用全局设置
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,9 +34,33 @@ pub struct AppSettings {
     #[serde(default = "default_refresh_interval")]
     pub refresh_interval_minutes: u32,
 
+    /// 后台任务配置，按任务名索引 (未列出的任务使用默认值)
+    #[serde(default)]
+    pub jobs: BTreeMap<String, JobSettings>,
+
     /// 主题设置: "light" | "dark"
     #[serde(default = "default_theme")]
     pub theme: String,
+
+    /// API 端点配置 (可指向本地替身服务)
+    #[serde(default)]
+    pub endpoints: EndpointConfig,
+
+    /// 网络设置 (代理、根证书、超时)
+    #[serde(default)]
+    pub network: NetworkSettings,
+
+    /// 删除账号时默认撤销其令牌
+    #[serde(default)]
+    pub revoke_on_delete: bool,
+
+    /// 浏览器登录等待回调的超时（秒）
+    #[serde(default = "default_oauth_timeout")]
+    pub oauth_timeout_secs: u64,
+
+    /// 漂移检查发现存储中的令牌较新时重写 auth.json (关闭时只报告)
+    #[serde(default)]
+    pub rewrite_auth_json_on_drift: bool,
 }
 
 fn default_primary_ide() -> String {
@@ -44,6 +71,10 @@ fn default_refresh_interval() -> u32 {
     30
 }
 
+fn default_oauth_timeout() -> u64 {
+    300
+}
+
 fn default_theme() -> String {
     "light".to_string()
 }
@@ -56,7 +87,13 @@ impl Default for AppSettings {
             use_pkill_restart: false,
             background_refresh: true,
             refresh_interval_minutes: default_refresh_interval(),
+            jobs: BTreeMap::new(),
             theme: default_theme(),
+            endpoints: EndpointConfig::default(),
+            network: NetworkSettings::default(),
+            revoke_on_delete: false,
+            oauth_timeout_secs: default_oauth_timeout(),
+            rewrite_auth_json_on_drift: false,
         }
     }
 }
@@ -73,6 +110,9 @@ pub struct Account {
     pub auth_json: serde_json::Value,
     /// OpenAI refresh_token (用于生成新的 auth_json)
     pub refresh_token: Option<String>,
+    /// refresh_token 最近一次获得 (登录或轮换) 的时间
+    #[serde(default)]
+    pub refresh_token_updated_at: Option<DateTime<Utc>>,
     /// 创建时间
     pub created_at: DateTime<Utc>,
     /// 上次使用时间
@@ -82,6 +122,22 @@ pub struct Account {
     /// 缓存的配额信息
     #[serde(default)]
     pub cached_quota: Option<CachedQuota>,
+    /// 刷新健康状态 (刷新正常时为空)
+    #[serde(default)]
+    pub refresh_health: Option<crate::refresh_health::RefreshHealth>,
+    /// 导入时未能校验身份的原因 (缺少 id_token 或无法获取签名公钥)，重新登录后清除
+    #[serde(default)]
+    pub unverified: Option<String>,
+}
+
+impl Account {
+    /// 更新 refresh_token，令牌发生变化时记录获得时间
+    pub fn set_refresh_token(&mut self, refresh_token: String) {
+        if self.refresh_token.as_deref() != Some(refresh_token.as_str()) {
+            self.refresh_token_updated_at = Some(Utc::now());
+        }
+        self.refresh_token = Some(refresh_token);
+    }
 }
 
 /// 缓存的配额信息
@@ -96,11 +152,60 @@ pub struct CachedQuota {
     pub plan_type: String,
     #[serde(default = "default_true")]
     pub is_valid_for_cli: bool,
+    /// 所有限流窗口
+    #[serde(default)]
+    pub windows: Vec<crate::usage::RateWindow>,
     pub updated_at: DateTime<Utc>,
 }
 
 fn default_true() -> bool { true }
 
+/// 单个令牌的撤销结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "status", content = "error")]
+pub enum RevokeOutcome {
+    /// 已撤销
+    Revoked,
+    /// 账号中没有该令牌
+    NoToken,
+    /// 令牌仍被其他账号 (如同一登录下的其他工作区条目) 或 Codex CLI 的 auth.json 使用，未撤销
+    Shared,
+    /// 离线，未能撤销
+    Offline,
+    /// 服务端拒绝或其他错误
+    Failed(String),
+}
+
+/// 单个账号的导入结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "status", content = "reason")]
+pub enum ImportStatus {
+    /// 身份已校验
+    Verified,
+    /// 无法校验，已导入并标记为未校验
+    Unverified(String),
+    /// 校验不通过，未导入
+    Rejected(String),
+}
+
+/// 导入账号配置时的逐个结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportRecord {
+    pub account_id: String,
+    pub account_name: String,
+    pub status: ImportStatus,
+}
+
+/// 删除账号时的撤销记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevocationRecord {
+    pub account_id: String,
+    pub account_name: String,
+    pub refresh_token: RevokeOutcome,
+    pub access_token: RevokeOutcome,
+    pub deleted_at: DateTime<Utc>,
+}
+
 /// 账号存储结构
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct AccountStore {
@@ -113,22 +218,39 @@ pub struct AccountStore {
     /// 全局设置
     #[serde(default)]
     pub settings: AppSettings,
+    /// 删除账号时的令牌撤销记录 (最近的在后)
+    #[serde(default)]
+    pub revocations: Vec<RevocationRecord>,
+    /// 定时切换规则 (按顺序匹配)
+    #[serde(default)]
+    pub switch_schedules: Vec<crate::schedule::SwitchRule>,
+    /// 最近一次已处理的定时切换时段
+    #[serde(default)]
+    pub schedule_last_applied: Option<String>,
 }
 
 impl AccountStore {
-    /// 配置文件路径
+    /// 配置文件路径 (可通过 CODEX_SWITCHER_HOME 覆盖目录)
     pub fn config_path() -> PathBuf {
-        dirs::home_dir()
-            .expect("无法获取用户目录")
-            .join(".codex-switcher")
+        std::env::var_os("CODEX_SWITCHER_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| {
+                dirs::home_dir()
+                    .expect("无法获取用户目录")
+                    .join(".codex-switcher")
+            })
             .join("accounts.json")
     }
 
-    /// Codex auth.json 路径
+    /// Codex auth.json 路径 (与 Codex CLI 一致，支持 CODEX_HOME)
     pub fn codex_auth_path() -> PathBuf {
-        dirs::home_dir()
-            .expect("无法获取用户目录")
-            .join(".codex")
+        std::env::var_os("CODEX_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| {
+                dirs::home_dir()
+                    .expect("无法获取用户目录")
+                    .join(".codex")
+            })
             .join("auth.json")
     }
 
@@ -143,7 +265,7 @@ impl AccountStore {
         }
     }
 
-    /// 保存账号存储
+    /// 保存账号存储 (进程内串行、原子写入，完成后广播变更)
     pub fn save(&self) -> Result<(), String> {
         let path = Self::config_path();
         
@@ -156,9 +278,16 @@ impl AccountStore {
         let content = serde_json::to_string_pretty(self)
             .map_err(|e| format!("序列化失败: {}", e))?;
         
-        fs::write(&path, content)
-            .map_err(|e| format!("写入文件失败: {}", e))?;
+        {
+            let _guard = crate::store::write_lock();
+            let tmp_path = path.with_extension("json.tmp");
+            fs::write(&tmp_path, content)
+                .map_err(|e| format!("写入临时文件失败: {}", e))?;
+            fs::rename(&tmp_path, &path)
+                .map_err(|e| format!("写入文件失败: {}", e))?;
+        }
         
+        crate::store::notify_saved();
         Ok(())
     }
 
@@ -209,10 +338,13 @@ impl AccountStore {
             name,
             auth_json,
             refresh_token: None, // 默认 None，由调用者后续按需设置
+            refresh_token_updated_at: None,
             created_at: Utc::now(),
             last_used: None,
             notes,
             cached_quota: None,
+            refresh_health: None,
+            unverified: None,
         };
 
         
@@ -257,10 +389,19 @@ impl AccountStore {
         if self.current.as_deref() == Some(id) {
             self.current = self.accounts.keys().next().cloned();
         }
+        self.prune_schedules();
         
         Ok(())
     }
 
+    /// 移除指向已不存在账号的定时切换规则，返回移除的条数
+    pub fn prune_schedules(&mut self) -> usize {
+        let before = self.switch_schedules.len();
+        let accounts = &self.accounts;
+        self.switch_schedules.retain(|rule| accounts.contains_key(&rule.account_id));
+        before - self.switch_schedules.len()
+    }
+
     /// 更新账号信息
     pub fn update_account(&mut self, id: &str, name: Option<String>, notes: Option<String>) -> Result<(), String> {
         let account = self.accounts.get_mut(id)
@@ -0,0 +1,222 @@
+//! Codex Switcher - 令牌声明查看
+//!
+//! 解析账号 id_token / access_token 中 `https://api.openai.com/auth` 命名空间的声明，
+//! 汇总为套餐、订阅有效期、组织、令牌签发与过期时间等结构化信息 (不校验签名，仅用于展示)
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::account::Account;
+use crate::{jwt, AppState};
+
+/// OpenAI 自定义声明的命名空间
+pub(crate) const AUTH_NAMESPACE: &str = "https://api.openai.com/auth";
+/// access_token 中邮箱所在的命名空间
+pub(crate) const PROFILE_NAMESPACE: &str = "https://api.openai.com/profile";
+
+/// 账号所属组织
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Organization {
+    pub id: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub is_default: bool,
+}
+
+/// 单个令牌的签发与过期时间
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenTimes {
+    pub issued_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub expired: bool,
+}
+
+/// 账号令牌声明汇总
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountClaims {
+    /// 本地账号 ID
+    pub id: String,
+    pub email: Option<String>,
+    /// OpenAI 用户 ID
+    pub user_id: Option<String>,
+    /// ChatGPT 账号 (工作区) ID
+    pub chatgpt_account_id: Option<String>,
+    /// 套餐类型: free / plus / pro / team ...
+    pub plan_type: Option<String>,
+    pub subscription_active_start: Option<DateTime<Utc>>,
+    pub subscription_active_until: Option<DateTime<Utc>>,
+    pub subscription_last_checked: Option<DateTime<Utc>>,
+    pub organizations: Vec<Organization>,
+    pub id_token: Option<TokenTimes>,
+    pub access_token: Option<TokenTimes>,
+    /// refresh_token 最近一次获得的时间
+    pub refresh_token_updated_at: Option<DateTime<Utc>>,
+    /// refresh_token 已使用的时长（秒）
+    pub refresh_token_age_secs: Option<i64>,
+}
+
+/// 解析秒级时间戳或 RFC 3339 字符串
+fn parse_time(value: Option<&serde_json::Value>) -> Option<DateTime<Utc>> {
+    match value? {
+        serde_json::Value::Number(n) => DateTime::from_timestamp(n.as_i64()?, 0),
+        serde_json::Value::String(s) => DateTime::parse_from_rfc3339(s)
+            .ok()
+            .map(|t| t.with_timezone(&Utc)),
+        _ => None,
+    }
+}
+
+fn token_times(claims: &serde_json::Value, now: DateTime<Utc>) -> TokenTimes {
+    let expires_at = parse_time(claims.get("exp"));
+    TokenTimes {
+        issued_at: parse_time(claims.get("iat")),
+        expires_at,
+        expired: expires_at.map(|exp| exp <= now).unwrap_or(true),
+    }
+}
+
+/// 读取声明中的组织列表
+pub fn organizations(claims: &serde_json::Value) -> Vec<Organization> {
+    claims.get(AUTH_NAMESPACE)
+        .and_then(|a| a.get("organizations"))
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default()
+}
+
+/// 汇总账号的令牌声明，id_token 中缺失的字段从 access_token 补充
+pub fn inspect(account: &Account, now: DateTime<Utc>) -> AccountClaims {
+    let tokens = account.auth_json.get("tokens");
+    let decode = |key: &str| {
+        tokens
+            .and_then(|t| t.get(key))
+            .and_then(|v| v.as_str())
+            .and_then(jwt::decode_unverified)
+    };
+    let id_claims = decode("id_token");
+    let access_claims = decode("access_token");
+
+    let sources: Vec<&serde_json::Value> = id_claims.iter().chain(access_claims.iter()).collect();
+    let auth_claim = |key: &str| {
+        sources.iter()
+            .filter_map(|c| c.get(AUTH_NAMESPACE).and_then(|a| a.get(key)))
+            .find(|v| !v.is_null())
+    };
+    let auth_str = |key: &str| auth_claim(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+
+    let email = sources.iter()
+        .find_map(|c| {
+            c.get("email")
+                .or_else(|| c.get(PROFILE_NAMESPACE).and_then(|p| p.get("email")))
+                .and_then(|v| v.as_str())
+        })
+        .map(|s| s.to_string());
+
+    let organizations = sources.iter()
+        .map(|c| organizations(c))
+        .find(|orgs| !orgs.is_empty())
+        .unwrap_or_default();
+
+    AccountClaims {
+        id: account.id.clone(),
+        email,
+        user_id: auth_str("chatgpt_user_id").or_else(|| auth_str("user_id")),
+        chatgpt_account_id: auth_str("chatgpt_account_id").or_else(|| {
+            tokens
+                .and_then(|t| t.get("account_id"))
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+        }),
+        plan_type: auth_str("chatgpt_plan_type"),
+        subscription_active_start: parse_time(auth_claim("chatgpt_subscription_active_start")),
+        subscription_active_until: parse_time(auth_claim("chatgpt_subscription_active_until")),
+        subscription_last_checked: parse_time(auth_claim("chatgpt_subscription_last_checked")),
+        organizations,
+        id_token: id_claims.as_ref().map(|c| token_times(c, now)),
+        access_token: access_claims.as_ref().map(|c| token_times(c, now)),
+        refresh_token_updated_at: account.refresh_token_updated_at,
+        refresh_token_age_secs: account.refresh_token_updated_at
+            .map(|t| (now - t).num_seconds().max(0)),
+    }
+}
+
+/// 获取指定账号的令牌声明
+#[tauri::command]
+pub fn get_account_claims(state: State<'_, AppState>, id: String) -> Result<AccountClaims, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    let account = store.accounts.get(&id)
+        .ok_or_else(|| format!("账号 {} 不存在", id))?;
+    Ok(inspect(account, Utc::now()))
+}
+
+/// 获取所有账号的令牌声明
+#[tauri::command]
+pub fn list_account_claims(state: State<'_, AppState>) -> Result<Vec<AccountClaims>, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    let now = Utc::now();
+    Ok(store.list_accounts().into_iter().map(|a| inspect(a, now)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::AccountStore;
+    use base64::{engine::general_purpose, Engine as _};
+
+    fn jwt(claims: serde_json::Value) -> String {
+        format!(
+            "{}.{}.sig",
+            general_purpose::URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#),
+            general_purpose::URL_SAFE_NO_PAD.encode(claims.to_string())
+        )
+    }
+
+    #[test]
+    fn test_inspect_merges_id_and_access_token_claims() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let id_token = jwt(serde_json::json!({
+            "email": "a@example.com",
+            "iat": 1_699_999_000,
+            "exp": 1_699_999_900,
+            AUTH_NAMESPACE: {
+                "chatgpt_account_id": "acct-1",
+                "chatgpt_plan_type": "team",
+                "chatgpt_subscription_active_until": "2024-01-31T00:00:00+00:00",
+                "organizations": [
+                    { "id": "org-1", "title": "Personal", "role": "owner", "is_default": true },
+                    { "id": "org-2", "title": "Team", "role": "reader", "is_default": false }
+                ]
+            }
+        }));
+        let access_token = jwt(serde_json::json!({
+            "iat": 1_699_999_000,
+            "exp": 1_700_003_600,
+            AUTH_NAMESPACE: { "chatgpt_user_id": "user-1", "chatgpt_plan_type": "plus" },
+            PROFILE_NAMESPACE: { "email": "ignored@example.com" }
+        }));
+
+        let mut store = AccountStore::default();
+        let mut account = store.add_account(
+            "a".to_string(),
+            serde_json::json!({ "tokens": { "id_token": id_token, "access_token": access_token } }),
+            None,
+        );
+        account.refresh_token_updated_at = DateTime::from_timestamp(1_699_996_400, 0);
+
+        let claims = inspect(&account, now);
+        assert_eq!(claims.email.as_deref(), Some("a@example.com"));
+        assert_eq!(claims.user_id.as_deref(), Some("user-1"));
+        assert_eq!(claims.chatgpt_account_id.as_deref(), Some("acct-1"));
+        // id_token 优先
+        assert_eq!(claims.plan_type.as_deref(), Some("team"));
+        assert_eq!(claims.subscription_active_until.unwrap().to_rfc3339(), "2024-01-31T00:00:00+00:00");
+        assert_eq!(claims.organizations.len(), 2);
+        assert!(claims.organizations[0].is_default);
+        assert!(claims.id_token.as_ref().unwrap().expired);
+        assert!(!claims.access_token.as_ref().unwrap().expired);
+        assert_eq!(claims.refresh_token_age_secs, Some(3600));
+    }
+}
@@ -0,0 +1,143 @@
+//! Codex Switcher - 系统睡眠与时钟跳变检测
+//!
+//! 单调时钟在系统睡眠期间不前进，墙上时间则包含整个睡眠时长；两者走时的差值超过阈值时
+//! 视为睡眠唤醒或系统时间被校正，立即唤醒后台任务补做错过的工作。
+//! 另外根据服务端响应的 Date 头估算本机时钟偏差。服务端给出的时间 (JWT exp、配额重置时间)
+//! 与校正后的时间比较；本机写入的时间 (expires_at、配额缓存时间) 仍与本机时间比较
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+/// 检测间隔
+const WATCH_INTERVAL: Duration = Duration::from_secs(30);
+/// 墙上时间与单调时钟走时相差超过该值视为跳变
+const JUMP_THRESHOLD_SECS: i64 = 60;
+/// 与服务端时间相差不超过该值时视为准确 (Date 头只精确到秒，且包含网络延迟)
+const SKEW_TOLERANCE_SECS: i64 = 30;
+
+/// 服务端时间 - 本机时间 (秒)
+static SKEW_SECS: AtomicI64 = AtomicI64::new(0);
+
+/// 跳变方向
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JumpKind {
+    /// 睡眠唤醒或时间被向后调整
+    Forward,
+    /// 时间被向前调整
+    Backward,
+}
+
+/// 时钟跳变事件
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockJump {
+    pub kind: JumpKind,
+    /// 墙上时间多走 (正) 或少走 (负) 的秒数
+    pub drift_secs: i64,
+    pub detected_at: DateTime<Utc>,
+}
+
+/// 同一段时间内墙上时间比单调时钟多走的秒数
+pub fn drift_secs(wall_elapsed: chrono::Duration, monotonic_elapsed: Duration) -> i64 {
+    let monotonic = chrono::Duration::from_std(monotonic_elapsed).unwrap_or(chrono::Duration::MAX);
+    (wall_elapsed - monotonic).num_seconds()
+}
+
+/// 偏移超过阈值时判定为跳变
+pub fn classify(drift_secs: i64) -> Option<JumpKind> {
+    if drift_secs > JUMP_THRESHOLD_SECS {
+        Some(JumpKind::Forward)
+    } else if drift_secs < -JUMP_THRESHOLD_SECS {
+        Some(JumpKind::Backward)
+    } else {
+        None
+    }
+}
+
+/// 校正后的当前时间 (与服务端给出的时间比较)
+pub fn now() -> DateTime<Utc> {
+    Utc::now() + chrono::Duration::seconds(SKEW_SECS.load(Ordering::Relaxed))
+}
+
+/// 由服务端 Date 头计算偏差，误差范围内视为无偏差
+pub fn skew_from_date(date: &str, local: DateTime<Utc>) -> Option<i64> {
+    let server = DateTime::parse_from_rfc2822(date.trim()).ok()?;
+    let skew = (server.with_timezone(&Utc) - local).num_seconds();
+    Some(if skew.abs() <= SKEW_TOLERANCE_SECS { 0 } else { skew })
+}
+
+/// 请求层上报: 记录响应的 Date 头
+pub fn observe_response(headers: &reqwest::header::HeaderMap) {
+    let Some(skew) = headers
+        .get(reqwest::header::DATE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|date| skew_from_date(date, Utc::now()))
+    else {
+        return;
+    };
+    if SKEW_SECS.swap(skew, Ordering::Relaxed) != skew && skew != 0 {
+        println!("[Clock] 本机时间与服务端相差 {} 秒，判断令牌有效期时将予以校正", skew);
+    }
+}
+
+/// 启动时钟监测
+pub fn start(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_wall = Utc::now();
+        let mut last_monotonic = Instant::now();
+        loop {
+            tokio::time::sleep(WATCH_INTERVAL).await;
+
+            let wall = Utc::now();
+            let monotonic = Instant::now();
+            let drift_secs = drift_secs(wall - last_wall, monotonic - last_monotonic);
+            last_wall = wall;
+            last_monotonic = monotonic;
+
+            let Some(kind) = classify(drift_secs) else { continue };
+            println!("[Clock] 检测到{} (偏移 {} 秒)，立即补做后台任务",
+                if kind == JumpKind::Forward { "系统唤醒或时间前跳" } else { "系统时间回拨" },
+                drift_secs);
+
+            // 本机时间已变化，旧的偏差估算作废，等待下一次响应重新测量
+            SKEW_SECS.store(0, Ordering::Relaxed);
+            let _ = app_handle.emit("clock-jumped", ClockJump { kind, drift_secs, detected_at: wall });
+
+            // 唤醒后网络可能尚未恢复，先探测再补做
+            crate::connectivity::probe().await;
+            crate::scheduler::catch_up();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_sleep_and_clock_corrections() {
+        let detect = |wall: i64, monotonic: u64| {
+            classify(drift_secs(chrono::Duration::seconds(wall), Duration::from_secs(monotonic)))
+        };
+
+        assert_eq!(detect(30, 30), None);
+        assert_eq!(detect(75, 30), None);
+        // 睡眠 2 小时: 单调时钟只走了 30 秒
+        assert_eq!(detect(7230, 30), Some(JumpKind::Forward));
+        // 时间回拨 1 小时
+        assert_eq!(detect(-3570, 30), Some(JumpKind::Backward));
+    }
+
+    #[test]
+    fn test_skew_from_date_header() {
+        let local = DateTime::parse_from_rfc3339("2026-03-01T12:00:00Z").unwrap().with_timezone(&Utc);
+
+        assert_eq!(skew_from_date("Sun, 01 Mar 2026 12:00:10 GMT", local), Some(0));
+        assert_eq!(skew_from_date("Sun, 01 Mar 2026 13:00:00 GMT", local), Some(3600));
+        assert_eq!(skew_from_date("Sun, 01 Mar 2026 11:50:00 GMT", local), Some(-600));
+        assert_eq!(skew_from_date("not a date", local), None);
+    }
+}
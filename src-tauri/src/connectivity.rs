@@ -0,0 +1,180 @@
+//! Codex Switcher - 网络连通性检测与离线模式
+//!
+//! 请求层遇到连接失败/超时时自动进入离线模式，后台定期探测恢复。
+//! 离线期间的 Token 刷新进入队列，恢复联网后统一补刷
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// 在线时的探测间隔
+const ONLINE_PROBE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// 离线时的探测间隔
+const OFFLINE_PROBE_INTERVAL: Duration = Duration::from_secs(30);
+/// 单次探测超时
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+static ONLINE: AtomicBool = AtomicBool::new(true);
+
+#[derive(Default)]
+struct Tracker {
+    last_checked_at: Option<chrono::DateTime<chrono::Utc>>,
+    offline_since: Option<chrono::DateTime<chrono::Utc>>,
+    /// 等待联网后刷新的账号 ID
+    pending_refresh: BTreeSet<String>,
+}
+
+static TRACKER: OnceLock<Mutex<Tracker>> = OnceLock::new();
+
+fn tracker() -> std::sync::MutexGuard<'static, Tracker> {
+    TRACKER
+        .get_or_init(|| Mutex::new(Tracker::default()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// 连通性状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectivityStatus {
+    pub online: bool,
+    pub last_checked_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub offline_since: Option<chrono::DateTime<chrono::Utc>>,
+    /// 排队等待刷新的账号
+    pub pending_refresh: Vec<String>,
+}
+
+/// 当前是否在线
+pub fn is_online() -> bool {
+    ONLINE.load(Ordering::SeqCst)
+}
+
+/// 更新在线状态，返回状态是否发生变化
+fn set_online(online: bool) -> bool {
+    let changed = ONLINE.swap(online, Ordering::SeqCst) != online;
+    let mut t = tracker();
+    t.last_checked_at = Some(chrono::Utc::now());
+    if changed {
+        t.offline_since = if online { None } else { Some(chrono::Utc::now()) };
+        println!("[Network] {}", if online { "网络已恢复" } else { "网络不可用，进入离线模式" });
+    }
+    changed
+}
+
+/// 请求层上报: 收到了 HTTP 响应
+pub fn report_reachable() {
+    set_online(true);
+}
+
+/// 请求层上报: 连接失败或超时
+pub fn report_unreachable() {
+    set_online(false);
+}
+
+/// 是否属于网络不可达类错误
+pub fn is_network_error(e: &reqwest::Error) -> bool {
+    e.is_connect() || e.is_timeout()
+}
+
+/// 将账号加入待刷新队列
+pub fn queue_refresh(account_id: &str) {
+    if tracker().pending_refresh.insert(account_id.to_string()) {
+        println!("[Network] 离线中，账号 {} 的 Token 刷新已加入队列", account_id);
+    }
+}
+
+/// 取出全部待刷新账号
+fn take_pending() -> Vec<String> {
+    std::mem::take(&mut tracker().pending_refresh).into_iter().collect()
+}
+
+pub fn status() -> ConnectivityStatus {
+    let t = tracker();
+    ConnectivityStatus {
+        online: is_online(),
+        last_checked_at: t.last_checked_at,
+        offline_since: t.offline_since,
+        pending_refresh: t.pending_refresh.iter().cloned().collect(),
+    }
+}
+
+/// 主动探测 OpenAI 授权服务是否可达 (收到任何 HTTP 响应即视为在线)
+pub async fn probe() -> bool {
+    let url = crate::endpoints::current().token_url;
+    let online = match crate::http_client::client()
+        .head(&url)
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+    {
+        Ok(_) => true,
+        Err(e) => !is_network_error(&e),
+    };
+    set_online(online);
+    online
+}
+
+/// 启动连通性监测，恢复联网后补刷队列中的 Token
+pub fn start(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut was_online = is_online();
+        loop {
+            let wait = if is_online() { ONLINE_PROBE_INTERVAL } else { OFFLINE_PROBE_INTERVAL };
+            tokio::time::sleep(wait).await;
+
+            let online = probe().await;
+            if online != was_online {
+                let _ = app_handle.emit("connectivity-changed", status());
+            }
+            // 请求层也可能已先行恢复在线状态，只要在线就处理队列
+            if online {
+                flush_pending(&app_handle).await;
+            }
+            was_online = online;
+        }
+    });
+}
+
+/// 刷新离线期间排队的账号
+async fn flush_pending(app_handle: &AppHandle) {
+    let pending = take_pending();
+    if pending.is_empty() {
+        return;
+    }
+
+    let state = app_handle.state::<crate::AppState>();
+    let mut refreshed = 0;
+    for id in pending {
+        match crate::refresh_account_tokens(&state.store, &id).await {
+            Ok(()) => refreshed += 1,
+            Err(e) => {
+                println!("[Network] 账号 {} 补刷 Token 失败: {}", id, e);
+                if !is_online() {
+                    queue_refresh(&id);
+                }
+            }
+        }
+    }
+
+    if refreshed > 0 {
+        println!("[Network] 已补刷 {} 个账号的 Token", refreshed);
+    }
+}
+
+/// 获取连通性状态 (recheck 为 true 时先主动探测)
+#[tauri::command]
+pub async fn get_connectivity_status(app_handle: AppHandle, recheck: Option<bool>) -> Result<ConnectivityStatus, String> {
+    if recheck.unwrap_or(false) {
+        let was_online = is_online();
+        let online = probe().await;
+        if online != was_online {
+            let _ = app_handle.emit("connectivity-changed", status());
+        }
+        if online {
+            flush_pending(&app_handle).await;
+        }
+    }
+    Ok(status())
+}
@@ -0,0 +1,129 @@
+//! 设备码登录 (与 Codex CLI 的 `codex login --device-auth` 相同的流程)
+//!
+//! 适用于无浏览器或通过 SSH 使用的远程机器: 展示用户码和验证地址，
+//! 用户在任意设备上完成授权后，后台轮询拿到授权码，换取令牌并保存账号
+
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use crate::account::Account;
+use crate::oauth::{self, DevicePoll};
+
+/// slow_down 时每次增加的轮询间隔
+const SLOW_DOWN_STEP: u64 = 5;
+
+/// 用户码有效期 (服务端不返回有效期，与 Codex CLI 一致取 15 分钟)
+const DEVICE_CODE_TTL: u64 = 15 * 60;
+
+/// 进行中的设备码登录
+static ACTIVE_LOGINS: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+
+fn active_logins() -> std::sync::MutexGuard<'static, HashSet<String>> {
+    ACTIVE_LOGINS
+        .get_or_init(|| Mutex::new(HashSet::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// 返回给前端展示的登录信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceLoginInfo {
+    pub login_id: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub expires_in: u64,
+    pub interval: u64,
+}
+
+/// 登录状态事件 (device-login-status)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceLoginEvent {
+    pub login_id: String,
+    /// "completed" | "failed" | "cancelled"
+    pub status: String,
+    pub account: Option<Account>,
+    pub error: Option<String>,
+}
+
+/// 开始设备码登录
+#[tauri::command]
+pub async fn start_device_login(app_handle: AppHandle) -> Result<DeviceLoginInfo, String> {
+    let device = oauth::request_device_code().await?;
+    let login_id = uuid::Uuid::new_v4().to_string();
+
+    active_logins().insert(login_id.clone());
+
+    let info = DeviceLoginInfo {
+        login_id: login_id.clone(),
+        user_code: device.user_code.clone(),
+        verification_uri: oauth::device_verification_url(),
+        expires_in: DEVICE_CODE_TTL,
+        interval: device.interval,
+    };
+
+    tokio::spawn(async move {
+        let event = poll_until_done(&app_handle, &login_id, device).await;
+        active_logins().remove(&login_id);
+        let _ = app_handle.emit("device-login-status", event);
+    });
+
+    Ok(info)
+}
+
+/// 取消设备码登录
+#[tauri::command]
+pub fn cancel_device_login(login_id: String) -> Result<(), String> {
+    if active_logins().remove(&login_id) {
+        Ok(())
+    } else {
+        Err("登录流程已结束或不存在".to_string())
+    }
+}
+
+/// 按服务端要求的间隔轮询，直到授权完成、被拒绝、过期或被取消
+async fn poll_until_done(app_handle: &AppHandle, login_id: &str, device: oauth::DeviceCodeResponse) -> DeviceLoginEvent {
+    let deadline = Instant::now() + Duration::from_secs(DEVICE_CODE_TTL);
+    let mut interval = device.interval.max(1);
+
+    let finish = |status: &str, account: Option<Account>, error: Option<String>| DeviceLoginEvent {
+        login_id: login_id.to_string(),
+        status: status.to_string(),
+        account,
+        error,
+    };
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+
+        if !active_logins().contains(login_id) {
+            return finish("cancelled", None, None);
+        }
+        if Instant::now() >= deadline {
+            return finish("failed", None, Some("设备码已过期，请重新发起登录".to_string()));
+        }
+
+        match oauth::poll_device_token(&device).await {
+            Ok(DevicePoll::Pending) => {}
+            Ok(DevicePoll::SlowDown) => {
+                interval += SLOW_DOWN_STEP;
+                println!("[DeviceLogin] 服务端要求降低轮询频率，间隔调整为 {} 秒", interval);
+            }
+            Ok(DevicePoll::Rejected(e)) => return finish("failed", None, Some(e)),
+            Ok(DevicePoll::Authorized { authorization_code, code_verifier }) => {
+                let token_res = match oauth::exchange_device_code(&authorization_code, &code_verifier).await {
+                    Ok(token_res) => token_res,
+                    Err(e) => return finish("failed", None, Some(e)),
+                };
+                let state = app_handle.state::<crate::AppState>();
+                return match crate::save_oauth_account(&state.store, token_res).await {
+                    Ok(account) => finish("completed", Some(account), None),
+                    Err(e) => finish("failed", None, Some(e)),
+                };
+            }
+            // 网络抖动、5xx 等临时错误: 继续轮询直到过期
+            Err(e) => println!("[DeviceLogin] 轮询失败: {}", e),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Codex Switcher - API 端点配置
+//!
+//! 所有 HTTP 请求统一从这里读取地址，可通过设置或环境变量指向本地替身服务
+//! 环境变量优先级高于设置，便于测试时临时覆盖
+
+use serde::{Deserialize, Serialize};
+use std::sync::{OnceLock, RwLock};
+
+/// OpenAI 官方授权常量 (参考 codex-main)
+pub const DEFAULT_CLIENT_ID: &str = "app_EMoamEEZ73f0CkXaXp7hrann";
+pub const DEFAULT_AUTH_URL: &str = "https://auth.openai.com/oauth/authorize";
+pub const DEFAULT_TOKEN_URL: &str = "https://auth.openai.com/oauth/token";
+pub const DEFAULT_USAGE_URL: &str = "https://chatgpt.com/backend-api/wham/usage";
+pub const DEFAULT_ACCOUNTS_URL: &str = "https://chatgpt.com/backend-api/accounts/check/v4-2023-04-27";
+/// 设备码登录接口前缀 (其下为 /usercode 与 /token，与 Codex CLI 一致)
+pub const DEFAULT_DEVICE_AUTH_URL: &str = "https://auth.openai.com/api/accounts/deviceauth";
+pub const DEFAULT_REVOKE_URL: &str = "https://auth.openai.com/oauth/revoke";
+pub const DEFAULT_JWKS_URL: &str = "https://auth.openai.com/.well-known/jwks.json";
+pub const DEFAULT_ISSUER: &str = "https://auth.openai.com";
+
+/// 环境变量名
+pub const ENV_CLIENT_ID: &str = "CODEX_SWITCHER_CLIENT_ID";
+pub const ENV_AUTH_URL: &str = "CODEX_SWITCHER_AUTH_URL";
+pub const ENV_TOKEN_URL: &str = "CODEX_SWITCHER_TOKEN_URL";
+pub const ENV_USAGE_URL: &str = "CODEX_SWITCHER_USAGE_URL";
+pub const ENV_ACCOUNTS_URL: &str = "CODEX_SWITCHER_ACCOUNTS_URL";
+pub const ENV_DEVICE_AUTH_URL: &str = "CODEX_SWITCHER_DEVICE_AUTH_URL";
+pub const ENV_REVOKE_URL: &str = "CODEX_SWITCHER_REVOKE_URL";
+pub const ENV_JWKS_URL: &str = "CODEX_SWITCHER_JWKS_URL";
+pub const ENV_ISSUER: &str = "CODEX_SWITCHER_ISSUER";
+
+/// API 端点配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EndpointConfig {
+    /// OAuth 客户端 ID
+    #[serde(default = "default_client_id")]
+    pub client_id: String,
+    /// 授权页地址
+    #[serde(default = "default_auth_url")]
+    pub auth_url: String,
+    /// 令牌地址 (授权码交换与刷新)
+    #[serde(default = "default_token_url")]
+    pub token_url: String,
+    /// 用量查询地址
+    #[serde(default = "default_usage_url")]
+    pub usage_url: String,
+    /// ChatGPT 账号 (工作区) 列表地址
+    #[serde(default = "default_accounts_url")]
+    pub accounts_url: String,
+    /// 设备码登录接口前缀
+    #[serde(default = "default_device_auth_url")]
+    pub device_auth_url: String,
+    /// 令牌撤销地址 (RFC 7009)
+    #[serde(default = "default_revoke_url")]
+    pub revoke_url: String,
+    /// id_token 签名公钥集合地址
+    #[serde(default = "default_jwks_url")]
+    pub jwks_url: String,
+    /// id_token 的签发者 (iss)
+    #[serde(default = "default_issuer")]
+    pub issuer: String,
+}
+
+fn default_client_id() -> String {
+    DEFAULT_CLIENT_ID.to_string()
+}
+
+fn default_auth_url() -> String {
+    DEFAULT_AUTH_URL.to_string()
+}
+
+fn default_token_url() -> String {
+    DEFAULT_TOKEN_URL.to_string()
+}
+
+fn default_usage_url() -> String {
+    DEFAULT_USAGE_URL.to_string()
+}
+
+fn default_accounts_url() -> String {
+    DEFAULT_ACCOUNTS_URL.to_string()
+}
+
+fn default_device_auth_url() -> String {
+    DEFAULT_DEVICE_AUTH_URL.to_string()
+}
+
+fn default_revoke_url() -> String {
+    DEFAULT_REVOKE_URL.to_string()
+}
+
+fn default_jwks_url() -> String {
+    DEFAULT_JWKS_URL.to_string()
+}
+
+fn default_issuer() -> String {
+    DEFAULT_ISSUER.to_string()
+}
+
+impl Default for EndpointConfig {
+    fn default() -> Self {
+        Self {
+            client_id: default_client_id(),
+            auth_url: default_auth_url(),
+            token_url: default_token_url(),
+            usage_url: default_usage_url(),
+            accounts_url: default_accounts_url(),
+            device_auth_url: default_device_auth_url(),
+            revoke_url: default_revoke_url(),
+            jwks_url: default_jwks_url(),
+            issuer: default_issuer(),
+        }
+    }
+}
+
+impl EndpointConfig {
+    /// 用环境变量覆盖配置，空字符串回退到默认值
+    fn resolved(mut self) -> Self {
+        let defaults = Self::default();
+        let pick = |value: String, env: &str, default: String| {
+            match std::env::var(env) {
+                Ok(v) if !v.trim().is_empty() => v.trim().to_string(),
+                _ if value.trim().is_empty() => default,
+                _ => value,
+            }
+        };
+
+        self.client_id = pick(self.client_id, ENV_CLIENT_ID, defaults.client_id);
+        self.auth_url = pick(self.auth_url, ENV_AUTH_URL, defaults.auth_url);
+        self.token_url = pick(self.token_url, ENV_TOKEN_URL, defaults.token_url);
+        self.usage_url = pick(self.usage_url, ENV_USAGE_URL, defaults.usage_url);
+        self.accounts_url = pick(self.accounts_url, ENV_ACCOUNTS_URL, defaults.accounts_url);
+        self.device_auth_url = pick(self.device_auth_url, ENV_DEVICE_AUTH_URL, defaults.device_auth_url);
+        self.revoke_url = pick(self.revoke_url, ENV_REVOKE_URL, defaults.revoke_url);
+        self.jwks_url = pick(self.jwks_url, ENV_JWKS_URL, defaults.jwks_url);
+        self.issuer = pick(self.issuer, ENV_ISSUER, defaults.issuer);
+        self
+    }
+}
+
+/// 当前生效的设置 (由 AppSettings 同步而来)
+static CONFIGURED: OnceLock<RwLock<EndpointConfig>> = OnceLock::new();
+
+fn configured() -> &'static RwLock<EndpointConfig> {
+    CONFIGURED.get_or_init(|| RwLock::new(EndpointConfig::default()))
+}
+
+/// 应用设置中的端点配置 (启动及更新设置时调用)
+pub fn configure(config: EndpointConfig) {
+    if let Ok(mut current) = configured().write() {
+        *current = config;
+    }
+}
+
+/// 获取最终生效的端点配置 (设置 + 环境变量覆盖)
+pub fn current() -> EndpointConfig {
+    configured()
+        .read()
+        .map(|c| c.clone())
+        .unwrap_or_default()
+        .resolved()
+}
@@ -0,0 +1,200 @@
+//! Codex Switcher - 共享 HTTP 客户端
+//!
+//! 根据设置中的代理、自定义根证书和超时构建唯一的 reqwest::Client，
+//! OAuth 与用量查询等所有模块共用
+
+use serde::{Deserialize, Serialize};
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+/// 网络设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkSettings {
+    /// 代理地址，如 "http://proxy.corp:8080"，为空则使用系统环境变量
+    #[serde(default)]
+    pub proxy_url: Option<String>,
+    /// 代理认证用户名
+    #[serde(default)]
+    pub proxy_username: Option<String>,
+    /// 代理认证密码
+    #[serde(default)]
+    pub proxy_password: Option<String>,
+    /// 不走代理的主机列表 (与 NO_PROXY 格式一致)
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+    /// 额外信任的根证书 (PEM 文件路径)
+    #[serde(default)]
+    pub ca_cert_paths: Vec<String>,
+    /// 连接超时（秒）
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout_secs: u64,
+    /// 请求总超时（秒）
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout_secs: u64,
+}
+
+fn default_connect_timeout() -> u64 {
+    10
+}
+
+fn default_request_timeout() -> u64 {
+    30
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            proxy_url: None,
+            proxy_username: None,
+            proxy_password: None,
+            no_proxy: Vec::new(),
+            ca_cert_paths: Vec::new(),
+            connect_timeout_secs: default_connect_timeout(),
+            request_timeout_secs: default_request_timeout(),
+        }
+    }
+}
+
+/// 根据网络设置构建客户端
+pub fn build_client(settings: &NetworkSettings) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder()
+        .user_agent("CodexSwitcher/1.0")
+        .connect_timeout(Duration::from_secs(settings.connect_timeout_secs.max(1)))
+        .timeout(Duration::from_secs(settings.request_timeout_secs.max(1)));
+
+    if let Some(proxy_url) = settings.proxy_url.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        let mut proxy = reqwest::Proxy::all(proxy_url)
+            .map_err(|e| format!("代理地址无效: {}", e))?;
+
+        if let Some(user) = settings.proxy_username.as_deref().filter(|s| !s.is_empty()) {
+            proxy = proxy.basic_auth(user, settings.proxy_password.as_deref().unwrap_or(""));
+        }
+
+        let no_proxy = settings.no_proxy.iter()
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(",");
+        if !no_proxy.is_empty() {
+            proxy = proxy.no_proxy(reqwest::NoProxy::from_string(&no_proxy));
+        }
+
+        builder = builder.proxy(proxy);
+    }
+
+    for path in settings.ca_cert_paths.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        let pem = std::fs::read(path)
+            .map_err(|e| format!("读取证书 {} 失败: {}", path, e))?;
+        let certs = reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("解析证书 {} 失败: {}", path, e))?;
+        if certs.is_empty() {
+            return Err(format!("证书文件 {} 中没有 PEM 证书", path));
+        }
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    builder.build().map_err(|e| format!("创建 HTTP 客户端失败: {}", e))
+}
+
+/// 全局共享客户端
+static CLIENT: OnceLock<RwLock<reqwest::Client>> = OnceLock::new();
+
+fn shared() -> &'static RwLock<reqwest::Client> {
+    CLIENT.get_or_init(|| {
+        let client = build_client(&NetworkSettings::default())
+            .unwrap_or_else(|_| reqwest::Client::new());
+        RwLock::new(client)
+    })
+}
+
+/// 应用网络设置 (启动及更新设置时调用)
+pub fn configure(settings: &NetworkSettings) -> Result<(), String> {
+    let client = build_client(settings)?;
+    let mut current = shared().write().map_err(|_| "HTTP 客户端锁被污染")?;
+    *current = client;
+    Ok(())
+}
+
+/// 获取共享客户端 (内部为 Arc，克隆开销很小)
+pub fn client() -> reqwest::Client {
+    shared().read().map(|c| c.clone()).unwrap_or_else(|e| e.into_inner().clone())
+}
+
+/// 单个目标的连通性检测结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionCheck {
+    /// 检测目标名称
+    pub target: String,
+    pub url: String,
+    /// 是否收到了 HTTP 响应 (任何状态码都视为连通)
+    pub ok: bool,
+    pub status: Option<u16>,
+    pub elapsed_ms: u64,
+    pub error: Option<String>,
+}
+
+/// 测试网络连接 (可传入尚未保存的网络设置)
+#[tauri::command]
+pub async fn test_connection(settings: Option<NetworkSettings>) -> Result<Vec<ConnectionCheck>, String> {
+    let client = match settings {
+        Some(s) => build_client(&s)?,
+        None => client(),
+    };
+
+    let endpoints = crate::endpoints::current();
+    let targets = [
+        ("auth", endpoints.token_url),
+        ("usage", endpoints.usage_url),
+    ];
+
+    let mut results = Vec::new();
+    for (target, url) in targets {
+        let started = Instant::now();
+        let res = client.get(&url).send().await;
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+
+        results.push(match res {
+            Ok(r) => ConnectionCheck {
+                target: target.to_string(),
+                url,
+                ok: true,
+                status: Some(r.status().as_u16()),
+                elapsed_ms,
+                error: None,
+            },
+            Err(e) => ConnectionCheck {
+                target: target.to_string(),
+                url,
+                ok: false,
+                status: None,
+                elapsed_ms,
+                error: Some(describe_error(&e)),
+            },
+        });
+    }
+
+    Ok(results)
+}
+
+/// 给出便于排查的错误描述
+fn describe_error(e: &reqwest::Error) -> String {
+    let kind = if e.is_timeout() {
+        "超时"
+    } else if e.is_connect() {
+        "连接失败"
+    } else {
+        "请求失败"
+    };
+
+    // reqwest 的 Display 不含底层原因 (如证书错误)，逐级拼接
+    let mut detail = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(s) = source {
+        detail.push_str(": ");
+        detail.push_str(&s.to_string());
+        source = s.source();
+    }
+    format!("{}: {}", kind, detail)
+}
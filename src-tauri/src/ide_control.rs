@@ -1,5 +1,9 @@
+use serde::Serialize;
 use std::process::Command;
 
+#[cfg(target_os = "linux")]
+mod linux;
+
 /// IDE 配置：名称和对应的 Bundle ID
 const IDE_CONFIGS: &[(&str, &str)] = &[
     ("Visual Studio Code", "com.microsoft.VSCode"),
@@ -8,8 +12,97 @@ const IDE_CONFIGS: &[(&str, &str)] = &[
     ("Antigravity", "com.google.antigravity"),
 ];
 
+/// Codex 进程类型
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CodexKind {
+    /// IDE 扩展启动的 `codex app-server`
+    AppServer,
+    /// 终端中运行的 codex CLI
+    Cli,
+}
+
+/// Codex 进程
+#[derive(Debug, Clone, Serialize)]
+pub struct CodexProcess {
+    pub pid: u32,
+    pub ppid: u32,
+    pub exe: String,
+    pub kind: CodexKind,
+    /// 所属 IDE 主进程 (不在任何 IDE 之下时为空)
+    pub ide_pid: Option<u32>,
+}
+
+/// IDE 主进程
+#[derive(Debug, Clone, Serialize)]
+pub struct IdeProcess {
+    pub pid: u32,
+    pub exe: String,
+    pub ide: String,
+    pub has_codex_child: bool,
+    pub codex_pids: Vec<u32>,
+}
+
+/// 进程扫描结果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProcessScan {
+    /// 当前平台是否支持进程扫描
+    pub supported: bool,
+    pub ides: Vec<IdeProcess>,
+    pub codex: Vec<CodexProcess>,
+}
+
+/// 单个 IDE 的重载结果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IdeReloadReport {
+    pub ide: String,
+    /// IDE 主进程 (仅 Linux)
+    pub pids: Vec<u32>,
+    /// 已执行的操作
+    pub actions: Vec<String>,
+    /// 收到 SIGTERM 后退出的 Codex 进程
+    pub terminated: Vec<u32>,
+    /// 超时后被强制结束的 Codex 进程
+    pub killed: Vec<u32>,
+    pub error: Option<String>,
+}
+
+/// 重载所有运行中的 IDE
+pub fn reload_running_ides(use_window_reload: bool) -> Vec<IdeReloadReport> {
+    #[cfg(target_os = "linux")]
+    return linux::reload_all(use_window_reload);
+
+    #[cfg(not(target_os = "linux"))]
+    detect_running_ides().into_iter()
+        .map(|ide| {
+            let mut report = IdeReloadReport { ide: ide.clone(), ..Default::default() };
+            match reload_ide(&ide, use_window_reload) {
+                Ok(()) => report.actions.push(if use_window_reload { "Reload Window" } else { "Restart Extension Host" }.to_string()),
+                Err(e) => report.error = Some(e),
+            }
+            report
+        })
+        .collect()
+}
+
+/// 扫描 IDE 与 Codex 进程 (目前仅支持 Linux)
+pub fn scan_processes() -> ProcessScan {
+    #[cfg(target_os = "linux")]
+    return linux::scan();
+    #[cfg(not(target_os = "linux"))]
+    ProcessScan::default()
+}
+
 /// 检测运行中的 IDE
+#[cfg_attr(target_os = "linux", allow(dead_code))]
 pub fn detect_running_ides() -> Vec<String> {
+    if cfg!(target_os = "linux") {
+        let mut running: Vec<String> = scan_processes().ides.into_iter().map(|i| i.ide).collect();
+        running.sort();
+        running.dedup();
+        return running;
+    }
+
     let mut running = Vec::new();
     
     for &(name, bundle_id) in IDE_CONFIGS {
@@ -35,7 +128,8 @@ pub fn detect_running_ides() -> Vec<String> {
     running
 }
 
-/// 重载指定 IDE
+/// 重载指定 IDE (macOS)
+#[cfg_attr(target_os = "linux", allow(dead_code))]
 pub fn reload_ide(name: &str, use_window_reload: bool) -> Result<(), String> {
     // 1. 特殊处理 Windsurf: 这种 IDE 杀掉子进程后会自动重启并加载新 Token，体验最好且无需权限
     if name == "Windsurf" {
@@ -0,0 +1,335 @@
+//! Linux: 扫描 /proc 检测 IDE 与 Codex 进程，并通过重启 Codex 服务进程重载
+//!
+//! Electron 应用会启动大量同名子进程 (渲染、GPU、扩展宿主等)，只把父进程不是同一 IDE 的
+//! 进程视为 IDE 主进程；Codex 扩展的 app-server 由扩展宿主启动，因此按进程树向上查找所属 IDE。
+//! 结束 app-server 后扩展会自动重新启动它，并读取新的 auth.json
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use super::{CodexKind, CodexProcess, IdeProcess, IdeReloadReport, ProcessScan};
+
+/// SIGTERM 之后等待进程退出的时长，超时后 SIGKILL
+const TERM_GRACE: Duration = Duration::from_secs(3);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 可执行文件名 (小写) 与 IDE 名称的对应关系
+const IDE_EXECUTABLES: &[(&str, &str)] = &[
+    ("code", "Visual Studio Code"),
+    ("code-insiders", "Visual Studio Code"),
+    ("codium", "Visual Studio Code"),
+    ("cursor", "Cursor"),
+    ("windsurf", "Windsurf"),
+    ("antigravity", "Antigravity"),
+];
+
+/// 单个进程的信息
+#[derive(Debug, Clone)]
+pub struct ProcInfo {
+    pub pid: u32,
+    pub ppid: u32,
+    /// /proc/<pid>/exe 指向的路径 (无权限读取时为 argv[0])
+    pub exe: String,
+    pub args: Vec<String>,
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path.trim_end_matches(" (deleted)"))
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// 由可执行文件判断 IDE
+pub fn ide_for_exe(exe: &str) -> Option<&'static str> {
+    let name = file_name(exe);
+    IDE_EXECUTABLES.iter()
+        .find(|(exe_name, _)| *exe_name == name)
+        .map(|(_, ide)| *ide)
+}
+
+/// 判断是否为 Codex 进程: 原生二进制，或经 node 启动的 npm 版本
+pub fn codex_kind(exe: &str, args: &[String]) -> Option<CodexKind> {
+    let is_codex = file_name(exe) == "codex"
+        || args.iter().take(2).any(|arg| file_name(arg) == "codex");
+    if !is_codex {
+        return None;
+    }
+    Some(if args.iter().any(|arg| arg == "app-server") {
+        CodexKind::AppServer
+    } else {
+        CodexKind::Cli
+    })
+}
+
+/// 从 /proc/<pid>/stat 解析父进程 ID (进程名可能包含空格和括号)
+pub fn parse_ppid(stat: &str) -> Option<u32> {
+    let (_, rest) = stat.rsplit_once(')')?;
+    // rest: " <state> <ppid> ..."
+    rest.split_whitespace().nth(1)?.parse().ok()
+}
+
+fn read_process(dir: &Path, pid: u32) -> Option<ProcInfo> {
+    let ppid = parse_ppid(&fs::read_to_string(dir.join("stat")).ok()?)?;
+    let args: Vec<String> = fs::read(dir.join("cmdline")).ok()?
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).to_string())
+        .collect();
+    // 内核线程没有命令行
+    if args.is_empty() {
+        return None;
+    }
+    let exe = fs::read_link(dir.join("exe"))
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| args[0].clone());
+    Some(ProcInfo { pid, ppid, exe, args })
+}
+
+/// 读取所有进程 (扫描期间退出的进程直接跳过)
+pub fn list_processes(proc_root: &Path) -> Vec<ProcInfo> {
+    let Ok(entries) = fs::read_dir(proc_root) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse::<u32>().ok()?;
+            read_process(&entry.path(), pid)
+        })
+        .collect()
+}
+
+/// 由进程列表归纳 IDE 主进程及其下的 Codex 进程
+pub fn build_scan(processes: &[ProcInfo]) -> ProcessScan {
+    let by_pid: HashMap<u32, &ProcInfo> = processes.iter().map(|p| (p.pid, p)).collect();
+    let ide_of = |p: &ProcInfo| ide_for_exe(&p.exe);
+
+    let mut ides: Vec<IdeProcess> = processes.iter()
+        .filter_map(|p| {
+            let ide = ide_of(p)?;
+            let parent_is_same_ide = by_pid.get(&p.ppid).and_then(|parent| ide_of(parent)) == Some(ide);
+            (!parent_is_same_ide).then(|| IdeProcess {
+                pid: p.pid,
+                exe: p.exe.clone(),
+                ide: ide.to_string(),
+                has_codex_child: false,
+                codex_pids: Vec::new(),
+            })
+        })
+        .collect();
+    ides.sort_by_key(|i| i.pid);
+
+    let mut codex: Vec<CodexProcess> = processes.iter()
+        .filter_map(|p| {
+            let kind = codex_kind(&p.exe, &p.args)?;
+            // 向上查找最近的 IDE 主进程 (限制深度，防止异常数据成环)
+            let mut owner = None;
+            let mut current = p.ppid;
+            for _ in 0..64 {
+                if let Some(ide) = ides.iter().find(|i| i.pid == current) {
+                    owner = Some(ide.pid);
+                    break;
+                }
+                match by_pid.get(&current) {
+                    Some(parent) if parent.ppid != current => current = parent.ppid,
+                    _ => break,
+                }
+            }
+            Some(CodexProcess {
+                pid: p.pid,
+                ppid: p.ppid,
+                exe: p.exe.clone(),
+                kind,
+                ide_pid: owner,
+            })
+        })
+        .collect();
+    codex.sort_by_key(|c| c.pid);
+
+    for ide in &mut ides {
+        ide.codex_pids = codex.iter().filter(|c| c.ide_pid == Some(ide.pid)).map(|c| c.pid).collect();
+        ide.has_codex_child = !ide.codex_pids.is_empty();
+    }
+
+    ProcessScan { supported: true, ides, codex }
+}
+
+/// 扫描 /proc
+pub fn scan() -> ProcessScan {
+    build_scan(&list_processes(Path::new("/proc")))
+}
+
+/// 进程是否仍在运行 (已退出但未被回收的僵尸进程视为已退出)
+fn is_alive(pid: u32) -> bool {
+    fs::read_to_string(format!("/proc/{}/stat", pid))
+        .ok()
+        .and_then(|stat| {
+            let (_, rest) = stat.rsplit_once(')')?;
+            rest.split_whitespace().next().map(|state| state != "Z" && state != "X")
+        })
+        .unwrap_or(false)
+}
+
+fn send_signal(pid: u32, signal: &str) -> Result<(), String> {
+    let output = Command::new("kill")
+        .arg(format!("-{}", signal))
+        .arg(pid.to_string())
+        .output()
+        .map_err(|e| format!("无法执行 kill: {}", e))?;
+    if output.status.success() || !is_alive(pid) {
+        Ok(())
+    } else {
+        Err(format!("向进程 {} 发送 {} 失败: {}", pid, signal, String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+/// 停止结果
+#[derive(Debug, Default, PartialEq)]
+pub struct StopResult {
+    /// 收到 SIGTERM 后自行退出
+    pub terminated: Vec<u32>,
+    /// 超时后被 SIGKILL
+    pub killed: Vec<u32>,
+    pub errors: Vec<String>,
+}
+
+/// 先 SIGTERM，等待 grace 后对仍在运行的进程 SIGKILL
+pub fn stop_gracefully(pids: &[u32], grace: Duration) -> StopResult {
+    let mut result = StopResult::default();
+    let mut pending = Vec::new();
+    for &pid in pids {
+        match send_signal(pid, "TERM") {
+            Ok(()) => pending.push(pid),
+            Err(e) => result.errors.push(e),
+        }
+    }
+
+    let deadline = Instant::now() + grace;
+    while pending.iter().any(|&pid| is_alive(pid)) && Instant::now() < deadline {
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    for pid in pending {
+        if !is_alive(pid) {
+            result.terminated.push(pid);
+            continue;
+        }
+        match send_signal(pid, "KILL") {
+            Ok(()) => result.killed.push(pid),
+            Err(e) => result.errors.push(e),
+        }
+    }
+    result
+}
+
+/// 重启各 IDE 下的 Codex app-server，按 IDE 汇报
+pub fn reload_all(use_window_reload: bool) -> Vec<IdeReloadReport> {
+    let scan = scan();
+    let mut names: Vec<&str> = scan.ides.iter().map(|i| i.ide.as_str()).collect();
+    names.sort();
+    names.dedup();
+
+    names.into_iter()
+        .map(|name| {
+            let ides: Vec<&IdeProcess> = scan.ides.iter().filter(|i| i.ide == name).collect();
+            let app_servers: Vec<u32> = scan.codex.iter()
+                .filter(|c| c.kind == CodexKind::AppServer && ides.iter().any(|i| Some(i.pid) == c.ide_pid))
+                .map(|c| c.pid)
+                .collect();
+
+            let mut report = IdeReloadReport {
+                ide: name.to_string(),
+                pids: ides.iter().map(|i| i.pid).collect(),
+                ..Default::default()
+            };
+            if app_servers.is_empty() {
+                report.actions.push("未发现 Codex 服务进程，扩展下次启动时会读取新的凭据".to_string());
+            } else {
+                let stopped = stop_gracefully(&app_servers, TERM_GRACE);
+                if !stopped.terminated.is_empty() {
+                    report.actions.push(format!("已结束 Codex 服务进程 {:?}，扩展将自动重启", stopped.terminated));
+                }
+                if !stopped.killed.is_empty() {
+                    report.actions.push(format!("进程 {:?} 未响应 SIGTERM，已强制结束", stopped.killed));
+                }
+                report.terminated = stopped.terminated;
+                report.killed = stopped.killed;
+                if !stopped.errors.is_empty() {
+                    report.error = Some(stopped.errors.join("; "));
+                }
+            }
+            // VS Code 系列的命令行 (code / cursor / windsurf / antigravity) 没有重载窗口的参数
+            if use_window_reload {
+                report.actions.push("该 IDE 的命令行不支持重载窗口，如需重载请在 IDE 中执行 Reload Window".to_string());
+            }
+            report
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, ppid: u32, exe: &str, args: &[&str]) -> ProcInfo {
+        ProcInfo {
+            pid,
+            ppid,
+            exe: exe.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_ppid_with_spaces_in_name() {
+        assert_eq!(parse_ppid("1234 (code) S 1 1234 1234 0 -1"), Some(1));
+        assert_eq!(parse_ppid("99 (Web Content (x)) S 42 99 99 0 -1"), Some(42));
+        assert_eq!(parse_ppid("garbage"), None);
+    }
+
+    #[test]
+    fn test_stop_gracefully_escalates_to_sigkill() {
+        let mut polite = Command::new("sleep").arg("30").spawn().unwrap();
+        let mut stubborn = Command::new("sh")
+            .args(["-c", "trap '' TERM; while :; do sleep 0.1; done"])
+            .spawn()
+            .unwrap();
+        // 等待 sh 安装 trap
+        std::thread::sleep(Duration::from_millis(200));
+
+        let result = stop_gracefully(&[polite.id(), stubborn.id()], Duration::from_millis(500));
+        assert_eq!(result.terminated, vec![polite.id()]);
+        assert_eq!(result.killed, vec![stubborn.id()]);
+        assert!(result.errors.is_empty());
+
+        polite.wait().unwrap();
+        stubborn.wait().unwrap();
+    }
+
+    #[test]
+    fn test_build_scan_groups_codex_under_ide() {
+        let ext = "/home/u/.vscode/extensions/openai.chatgpt-1.0/bin/linux-x86_64/codex";
+        let processes = vec![
+            process(1, 0, "/usr/lib/systemd/systemd", &["/sbin/init"]),
+            process(100, 1, "/usr/share/code/code", &["/usr/share/code/code"]),
+            process(101, 100, "/usr/share/code/code", &["/usr/share/code/code", "--type=renderer"]),
+            process(102, 100, "/usr/share/code/code", &["/usr/share/code/code", "--type=utility"]),
+            process(103, 102, ext, &[ext, "app-server"]),
+            process(200, 1, "/tmp/.mount_cursorAbc/cursor (deleted)", &["/tmp/.mount_cursorAbc/cursor"]),
+            process(300, 1, "/usr/bin/bash", &["bash"]),
+            process(301, 300, "/usr/bin/node", &["node", "/usr/local/bin/codex", "exec", "hi"]),
+        ];
+
+        let scan = build_scan(&processes);
+        let ides: Vec<_> = scan.ides.iter().map(|i| (i.pid, i.ide.as_str(), i.has_codex_child)).collect();
+        assert_eq!(ides, vec![(100, "Visual Studio Code", true), (200, "Cursor", false)]);
+        assert_eq!(scan.ides[0].codex_pids, vec![103]);
+
+        let codex: Vec<_> = scan.codex.iter().map(|c| (c.pid, c.kind, c.ide_pid)).collect();
+        assert_eq!(codex, vec![(103, CodexKind::AppServer, Some(100)), (301, CodexKind::Cli, None)]);
+    }
+}
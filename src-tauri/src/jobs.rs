@@ -0,0 +1,397 @@
+//! Codex Switcher - 内置后台任务
+//!
+//! Token 刷新、配额轮询、存储备份、历史记录清理与 auth.json 漂移检查，
+//! 由 `scheduler` 按各自的间隔执行
+
+use chrono::Utc;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::account::{AccountStore, AppSettings};
+use crate::scheduler::{Job, JobContext, JobFuture, JobReport, JobSettings};
+use crate::{connectivity, quota_poll, refresh_health, schedule, token_refresh};
+
+/// 存储备份最多保留份数
+const MAX_BACKUPS: usize = 7;
+/// 撤销记录保留天数
+const HISTORY_RETENTION_DAYS: i64 = 90;
+
+/// 所有内置任务
+pub fn builtin() -> Vec<Job> {
+    vec![
+        Job {
+            name: "token_refresh",
+            label: "Token 刷新",
+            defaults: JobSettings { enabled: true, interval_minutes: 30, jitter_secs: 60 },
+            // 沿用设置中的后台刷新开关与间隔
+            configure: Some(token_refresh_settings),
+            catch_up: true,
+            run: |ctx| Box::pin(refresh_tokens(ctx)),
+        },
+        Job {
+            name: "quota_poll",
+            label: "配额轮询",
+            // 间隔为当前账号的轮询间隔，闲置账号自动放慢
+            defaults: JobSettings { enabled: false, interval_minutes: 5, jitter_secs: 30 },
+            configure: None,
+            catch_up: true,
+            run: |ctx| Box::pin(quota_poll::poll(ctx)),
+        },
+        Job {
+            name: "backup",
+            label: "存储备份",
+            defaults: JobSettings { enabled: true, interval_minutes: 24 * 60, jitter_secs: 600 },
+            configure: None,
+            catch_up: false,
+            run: |_| Box::pin(async { backup_store() }),
+        },
+        Job {
+            name: "history_compaction",
+            label: "历史记录清理",
+            defaults: JobSettings { enabled: true, interval_minutes: 24 * 60, jitter_secs: 600 },
+            configure: None,
+            catch_up: false,
+            run: |ctx| Box::pin(async move { compact_history(&ctx.store) }),
+        },
+        Job {
+            name: "drift_check",
+            label: "auth.json 漂移检查",
+            defaults: JobSettings { enabled: true, interval_minutes: 10, jitter_secs: 30 },
+            configure: None,
+            catch_up: true,
+            run: run_drift_check,
+        },
+        Job {
+            name: "schedule_switch",
+            label: "定时切换",
+            // 只在进入时段时切换，间隔决定边界处的最大延迟
+            defaults: JobSettings { enabled: true, interval_minutes: 1, jitter_secs: 0 },
+            configure: None,
+            catch_up: true,
+            run: |ctx| Box::pin(schedule::run(ctx)),
+        },
+    ]
+}
+
+fn token_refresh_settings(settings: &AppSettings) -> JobSettings {
+    JobSettings {
+        enabled: settings.background_refresh,
+        interval_minutes: settings.refresh_interval_minutes,
+        jitter_secs: settings.jobs.get("token_refresh").map(|j| j.jitter_secs).unwrap_or(60),
+    }
+}
+
+fn to_details<T: Serialize>(value: &T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
+
+// ===== Token 刷新 =====
+
+/// 单个账号的刷新结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefreshOutcome {
+    /// 已刷新
+    Refreshed,
+    /// Token 仍然有效，无需刷新
+    Valid,
+    /// 没有 refresh_token
+    NoRefreshToken,
+    /// 离线，已加入补刷队列
+    Queued,
+    /// refresh_token 已多次被拒绝，等待重新登录
+    NeedsRelogin,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountRunResult {
+    pub account_id: String,
+    pub account_name: String,
+    pub outcome: RefreshOutcome,
+    pub error: Option<String>,
+}
+
+/// 检查所有账号并刷新即将过期的 Token
+async fn refresh_tokens(ctx: JobContext) -> Result<JobReport, String> {
+    let store = &ctx.store;
+    // 获取所有账号
+    let accounts = {
+        let store = store.lock().map_err(|e| e.to_string())?;
+        store.accounts.values().cloned().collect::<Vec<_>>()
+    };
+
+    let mut results = Vec::new();
+    for account in accounts {
+        let result = |outcome, error| AccountRunResult {
+            account_id: account.id.clone(),
+            account_name: account.name.clone(),
+            outcome,
+            error,
+        };
+
+        let Some(refresh_token) = account.refresh_token.as_deref() else {
+            results.push(result(RefreshOutcome::NoRefreshToken, None));
+            continue;
+        };
+        // 再次刷新只会继续被拒绝
+        if refresh_health::needs_relogin(&account) {
+            results.push(result(RefreshOutcome::NeedsRelogin, None));
+            continue;
+        }
+        // 检查 Token 是否即将过期
+        // expires_at 由本机时间加上 expires_in 算出，须与本机时间比较
+        if !is_token_expiring_soon(&account.auth_json, Utc::now()) {
+            results.push(result(RefreshOutcome::Valid, None));
+            continue;
+        }
+        // 离线时不发请求，留待联网后补刷
+        if !connectivity::is_online() {
+            connectivity::queue_refresh(&account.id);
+            results.push(result(RefreshOutcome::Queued, None));
+            continue;
+        }
+
+        println!("[Scheduler] 账号 {} Token 即将过期，正在刷新...", account.name);
+
+        // 经协调器刷新，新令牌由其写入共享存储，前端经 store-changed 事件更新
+        match token_refresh::refresh(store, &account.id, refresh_token).await {
+            Ok(_) => results.push(result(RefreshOutcome::Refreshed, None)),
+            Err(e) => {
+                println!("[Scheduler] ❌ 账号 {} Token 刷新失败: {}", account.name, e);
+                results.push(result(RefreshOutcome::Failed, Some(e)));
+            }
+        }
+    }
+
+    let count = |outcome: fn(&RefreshOutcome) -> bool| results.iter().filter(|r| outcome(&r.outcome)).count();
+    let refreshed = count(|o| matches!(o, RefreshOutcome::Refreshed));
+    let failed = count(|o| matches!(o, RefreshOutcome::Failed));
+    let relogin = count(|o| matches!(o, RefreshOutcome::NeedsRelogin));
+    Ok(JobReport {
+        summary: if relogin > 0 {
+            format!("刷新 {} 个账号，失败 {} 个，{} 个需要重新登录", refreshed, failed, relogin)
+        } else {
+            format!("刷新 {} 个账号，失败 {} 个", refreshed, failed)
+        },
+        details: to_details(&results),
+    })
+}
+
+/// 检查 Token 是否即将过期（剩余 < 10 分钟）
+fn is_token_expiring_soon(auth_json: &serde_json::Value, now: chrono::DateTime<Utc>) -> bool {
+    // 优先从 tokens.expires_at 获取 (可能是 RFC3339 字符串)
+    let expires_at_val = auth_json.get("tokens")
+        .and_then(|t| t.get("expires_at"))
+        .or_else(|| auth_json.get("expires_at"));
+
+    if let Some(val) = expires_at_val {
+        let timestamp = if let Some(ts) = val.as_i64() {
+            ts
+        } else if let Some(iso_str) = val.as_str() {
+            // 解析 RFC3339 字符串
+            chrono::DateTime::parse_from_rfc3339(iso_str)
+                .map(|dt| dt.timestamp())
+                .unwrap_or(0)
+        } else {
+            0
+        };
+
+        if timestamp > 0 {
+            let remaining = timestamp - now.timestamp();
+            return remaining < 600; // 10 分钟
+        }
+    }
+    false
+}
+
+// ===== 存储备份 =====
+
+/// 备份目录 (与 accounts.json 同级的 backups/)
+pub fn backup_dir() -> PathBuf {
+    AccountStore::config_path().with_file_name("backups")
+}
+
+/// 复制 accounts.json 到备份目录，只保留最近 MAX_BACKUPS 份
+pub fn backup_store() -> Result<JobReport, String> {
+    let source = AccountStore::config_path();
+    if !source.exists() {
+        return Ok(JobReport { summary: "暂无数据，跳过".to_string(), details: serde_json::Value::Null });
+    }
+    let dir = backup_dir();
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("创建备份目录失败: {}", e))?;
+
+    let target = dir.join(format!("accounts-{}.json", Utc::now().format("%Y%m%d-%H%M%S")));
+    {
+        // 与存储写盘互斥，避免复制到一半的文件
+        let _guard = crate::store::write_lock();
+        std::fs::copy(&source, &target)
+            .map_err(|e| format!("备份失败: {}", e))?;
+    }
+
+    // 文件名按时间排序，删除最旧的
+    let mut backups: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .map_err(|e| format!("读取备份目录失败: {}", e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("accounts-") && n.ends_with(".json"))
+        })
+        .collect();
+    backups.sort();
+    let overflow = backups.len().saturating_sub(MAX_BACKUPS);
+    for old in backups.drain(..overflow) {
+        let _ = std::fs::remove_file(old);
+    }
+
+    Ok(JobReport {
+        summary: format!("已备份到 {}", target.display()),
+        details: serde_json::json!({ "path": target, "kept": backups.len() }),
+    })
+}
+
+// ===== 历史记录清理 =====
+
+/// 删除超过保留期的撤销记录
+pub fn compact_history(store: &Mutex<AccountStore>) -> Result<JobReport, String> {
+    let mut store = store.lock().map_err(|e| e.to_string())?;
+    let cutoff = Utc::now() - chrono::Duration::days(HISTORY_RETENTION_DAYS);
+    let before = store.revocations.len();
+    store.revocations.retain(|r| r.deleted_at >= cutoff);
+    let removed = before - store.revocations.len();
+    if removed > 0 {
+        store.save()?;
+    }
+    Ok(JobReport {
+        summary: format!("清理 {} 条过期的撤销记录", removed),
+        details: serde_json::json!({ "removed": removed }),
+    })
+}
+
+// ===== auth.json 漂移检查 =====
+
+/// auth.json 与当前账号的比对结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftOutcome {
+    /// 一致
+    InSync,
+    NoCurrentAccount,
+    /// auth.json 不存在或无法解析
+    MissingAuthJson,
+    /// auth.json 已被替换为其他账号 (如在 Codex CLI 中重新登录)，不做处理
+    ForeignAccount,
+    /// Codex CLI 自行刷新过令牌: 已将 auth.json 中较新的令牌写回存储
+    AdoptedFromAuthJson,
+    /// 存储中的令牌较新: 已重写 auth.json (需在设置中开启)
+    RewroteAuthJson,
+    /// 存储中的令牌较新，但未开启重写，只报告
+    StoreNewer,
+    /// 令牌不一致且无法判断哪一方较新 (缺少 last_refresh 或时间相同)，不做处理
+    Diverged,
+}
+
+impl DriftOutcome {
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::InSync => "auth.json 与当前账号一致",
+            Self::NoCurrentAccount => "没有当前账号",
+            Self::MissingAuthJson => "未找到 auth.json",
+            Self::ForeignAccount => "auth.json 已被替换为其他账号",
+            Self::AdoptedFromAuthJson => "已采用 Codex CLI 刷新后的令牌",
+            Self::RewroteAuthJson => "已用存储中较新的令牌重写 auth.json",
+            Self::StoreNewer => "存储中的令牌比 auth.json 新，未开启重写 auth.json",
+            Self::Diverged => "auth.json 与当前账号的令牌不一致，无法判断哪一方较新",
+        }
+    }
+}
+
+fn token_field<'a>(auth_json: &'a serde_json::Value, key: &str) -> Option<&'a str> {
+    auth_json.get("tokens").and_then(|t| t.get(key)).and_then(|v| v.as_str())
+}
+
+fn last_refresh(auth_json: &serde_json::Value) -> Option<chrono::DateTime<Utc>> {
+    auth_json.get("last_refresh")
+        .and_then(|v| v.as_str())
+        .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// 比对 auth.json 与当前账号的令牌
+///
+/// 只有双方都带 last_refresh 时才能判断新旧: auth.json 较新时写回存储；存储较新时
+/// 仅在设置开启后重写 auth.json。其余情况只报告，不覆盖任何一方
+pub fn check_drift(store: &Mutex<AccountStore>) -> Result<DriftOutcome, String> {
+    let mut store = store.lock().map_err(|e| e.to_string())?;
+    let Some(current) = store.current.clone() else {
+        return Ok(DriftOutcome::NoCurrentAccount);
+    };
+    let Some(stored) = store.accounts.get(&current).map(|a| a.auth_json.clone()) else {
+        return Ok(DriftOutcome::NoCurrentAccount);
+    };
+    let Ok(auth) = AccountStore::read_codex_auth() else {
+        return Ok(DriftOutcome::MissingAuthJson);
+    };
+
+    if token_field(&auth, "account_id") != token_field(&stored, "account_id") {
+        return Ok(DriftOutcome::ForeignAccount);
+    }
+    let same = |key| token_field(&auth, key) == token_field(&stored, key);
+    if same("access_token") && same("refresh_token") {
+        return Ok(DriftOutcome::InSync);
+    }
+
+    let (Some(auth_refresh), Some(stored_refresh)) = (last_refresh(&auth), last_refresh(&stored)) else {
+        println!("[Drift] auth.json 与当前账号的令牌不一致，缺少 last_refresh，不做处理");
+        return Ok(DriftOutcome::Diverged);
+    };
+    if auth_refresh > stored_refresh {
+        token_refresh::adopt_external(&mut store, &current, &auth)?;
+        store.save()?;
+        Ok(DriftOutcome::AdoptedFromAuthJson)
+    } else if stored_refresh > auth_refresh {
+        if !store.settings.rewrite_auth_json_on_drift {
+            println!("[Drift] 存储中的令牌比 auth.json 新，未开启重写，只报告");
+            return Ok(DriftOutcome::StoreNewer);
+        }
+        AccountStore::write_codex_auth(&stored)?;
+        Ok(DriftOutcome::RewroteAuthJson)
+    } else {
+        println!("[Drift] auth.json 与当前账号的令牌不一致，last_refresh 相同，不做处理");
+        Ok(DriftOutcome::Diverged)
+    }
+}
+
+fn run_drift_check(ctx: JobContext) -> JobFuture {
+    Box::pin(async move {
+        let outcome = check_drift(&ctx.store)?;
+        Ok(JobReport {
+            summary: outcome.describe().to_string(),
+            details: to_details(&outcome),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expiry_uses_local_clock_despite_skew() {
+        let local_now = chrono::DateTime::parse_from_rfc3339("2026-03-02T09:00:00Z").unwrap().with_timezone(&Utc);
+        // 本机时间比服务端慢 20 分钟
+        let server_now = local_now + chrono::Duration::seconds(1200);
+        let auth = |secs: i64| serde_json::json!({
+            "tokens": { "expires_at": (local_now + chrono::Duration::seconds(secs)).to_rfc3339() }
+        });
+
+        // 刚刷新的令牌 (本机时间 + expires_in) 与本机时间比较才不会被误判为即将过期
+        assert!(!is_token_expiring_soon(&auth(3600), local_now));
+        assert!(is_token_expiring_soon(&auth(300), local_now));
+        assert!(is_token_expiring_soon(&auth(1500), server_now));
+        assert!(!is_token_expiring_soon(&auth(1500), local_now));
+    }
+}
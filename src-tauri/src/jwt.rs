@@ -0,0 +1,323 @@
+//! Codex Switcher - id_token 签名校验
+//!
+//! 从签发者的 JWKS 获取公钥 (带缓存)，校验 RS256 签名以及 iss、aud、exp，
+//! 防止篡改过的导入数据冒充任意邮箱或账号 ID
+
+use base64::{engine::general_purpose, Engine as _};
+use ring::signature::{RsaPublicKeyComponents, RSA_PKCS1_2048_8192_SHA256};
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::resilience::{send_with_retry, RetryPolicy};
+
+/// JWKS 缓存有效期
+const JWKS_TTL: Duration = Duration::from_secs(60 * 60);
+/// 遇到未知 kid 时两次重新拉取的最小间隔
+const MIN_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+/// 校验 exp 时允许的时钟偏差（秒）
+const LEEWAY_SECS: i64 = 60;
+
+/// 单个公钥 (仅使用 RSA 的 n / e)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Jwk {
+    #[serde(default)]
+    pub kid: Option<String>,
+    pub kty: String,
+    #[serde(default)]
+    pub alg: Option<String>,
+    #[serde(default)]
+    pub n: Option<String>,
+    #[serde(default)]
+    pub e: Option<String>,
+}
+
+/// 公钥集合
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+impl JwkSet {
+    fn find(&self, kid: Option<&str>) -> Option<&Jwk> {
+        let mut rsa = self.keys.iter().filter(|k| k.kty == "RSA");
+        match kid {
+            Some(kid) => rsa.find(|k| k.kid.as_deref() == Some(kid)),
+            // 没有 kid 时只接受唯一的 RSA 公钥
+            None => {
+                let first = rsa.next();
+                if rsa.next().is_some() { None } else { first }
+            }
+        }
+    }
+}
+
+/// 校验选项
+#[derive(Debug, Clone)]
+pub struct VerifyOptions {
+    /// 期望的签发者
+    pub issuer: String,
+    /// 期望的受众 (OAuth client_id)
+    pub audience: String,
+    /// 是否接受已过期的令牌
+    ///
+    /// 导入本地 auth.json 时使用: id_token 有效期很短，其中保存的往往已过期，
+    /// 但签名仍能证明身份信息出自签发者
+    pub allow_expired: bool,
+}
+
+impl VerifyOptions {
+    /// 按当前端点配置生成
+    pub fn current(allow_expired: bool) -> Self {
+        let endpoints = crate::endpoints::current();
+        Self {
+            issuer: endpoints.issuer,
+            audience: endpoints.client_id,
+            allow_expired,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Header {
+    alg: String,
+    #[serde(default)]
+    kid: Option<String>,
+}
+
+fn decode_segment(segment: &str) -> Result<Vec<u8>, String> {
+    general_purpose::URL_SAFE_NO_PAD
+        .decode(segment.trim_end_matches('='))
+        .map_err(|_| "id_token 编码无效".to_string())
+}
+
+fn split_token(token: &str) -> Result<(&str, &str, &str), String> {
+    let mut parts = token.split('.');
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(h), Some(p), Some(s), None) => Ok((h, p, s)),
+        _ => Err("id_token 格式无效".to_string()),
+    }
+}
+
+fn parse_header(token: &str) -> Result<Header, String> {
+    let (header, _, _) = split_token(token)?;
+    serde_json::from_slice(&decode_segment(header)?)
+        .map_err(|_| "id_token 头部无效".to_string())
+}
+
+/// 不校验签名直接解码 claims (仅用于展示本地保存的令牌信息)
+pub fn decode_unverified(token: &str) -> Option<serde_json::Value> {
+    let (_, payload, _) = split_token(token).ok()?;
+    serde_json::from_slice(&decode_segment(payload).ok()?).ok()
+}
+
+/// 使用给定的公钥集合校验 id_token，返回其 claims
+pub fn verify_with_keys(
+    token: &str,
+    keys: &JwkSet,
+    options: &VerifyOptions,
+    now: i64,
+) -> Result<serde_json::Value, String> {
+    let (header_b64, payload_b64, signature_b64) = split_token(token)?;
+    let header = parse_header(token)?;
+    if header.alg != "RS256" {
+        return Err(format!("不支持的 id_token 签名算法: {}", header.alg));
+    }
+
+    // 1. 签名
+    let jwk = keys.find(header.kid.as_deref())
+        .ok_or("找不到与 id_token 匹配的签名公钥")?;
+    let n = decode_segment(jwk.n.as_deref().ok_or("签名公钥缺少 n")?)?;
+    let e = decode_segment(jwk.e.as_deref().ok_or("签名公钥缺少 e")?)?;
+    let signature = decode_segment(signature_b64)?;
+    let message = format!("{}.{}", header_b64, payload_b64);
+    RsaPublicKeyComponents { n: &n, e: &e }
+        .verify(&RSA_PKCS1_2048_8192_SHA256, message.as_bytes(), &signature)
+        .map_err(|_| "id_token 签名校验失败".to_string())?;
+
+    // 2. 声明
+    let claims: serde_json::Value = serde_json::from_slice(&decode_segment(payload_b64)?)
+        .map_err(|_| "id_token 内容无效".to_string())?;
+
+    let iss = claims.get("iss").and_then(|v| v.as_str()).unwrap_or("");
+    if iss.trim_end_matches('/') != options.issuer.trim_end_matches('/') {
+        return Err(format!("id_token 签发者不匹配: {}", iss));
+    }
+
+    let aud_ok = match claims.get("aud") {
+        Some(serde_json::Value::String(aud)) => aud == &options.audience,
+        Some(serde_json::Value::Array(list)) => list.iter().any(|a| a.as_str() == Some(&options.audience)),
+        _ => false,
+    };
+    if !aud_ok {
+        return Err("id_token 受众 (aud) 与客户端不匹配".to_string());
+    }
+
+    let exp = claims.get("exp").and_then(|v| v.as_i64()).ok_or("id_token 缺少 exp")?;
+    if !options.allow_expired && exp + LEEWAY_SECS < now {
+        return Err("id_token 已过期".to_string());
+    }
+
+    Ok(claims)
+}
+
+struct CachedKeys {
+    url: String,
+    keys: JwkSet,
+    fetched_at: Instant,
+}
+
+static JWKS_CACHE: OnceLock<Mutex<Option<CachedKeys>>> = OnceLock::new();
+
+fn cache() -> std::sync::MutexGuard<'static, Option<CachedKeys>> {
+    JWKS_CACHE
+        .get_or_init(|| Mutex::new(None))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// 拉取公钥集合
+async fn fetch_jwks(url: &str) -> Result<JwkSet, String> {
+    let client = crate::http_client::client();
+    let response = send_with_retry(RetryPolicy::default(), || client.get(url))
+        .await
+        .map_err(|e| format!("获取签名公钥失败: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!("获取签名公钥失败: HTTP {}", response.status()));
+    }
+    response.json::<JwkSet>()
+        .await
+        .map_err(|e| format!("解析签名公钥失败: {}", e))
+}
+
+/// 获取包含指定 kid 的公钥集合: 缓存过期或找不到 kid 时重新拉取 (限频)
+async fn keys_for(url: &str, kid: Option<&str>) -> Result<JwkSet, String> {
+    {
+        let cached = cache();
+        if let Some(c) = cached.as_ref().filter(|c| c.url == url) {
+            let age = c.fetched_at.elapsed();
+            if age < JWKS_TTL && c.keys.find(kid).is_some() {
+                return Ok(c.keys.clone());
+            }
+            // 刚拉取过仍找不到 kid，避免被伪造的 kid 反复触发请求
+            if age < MIN_REFETCH_INTERVAL {
+                return Ok(c.keys.clone());
+            }
+        }
+    }
+
+    let keys = fetch_jwks(url).await?;
+    *cache() = Some(CachedKeys {
+        url: url.to_string(),
+        keys: keys.clone(),
+        fetched_at: Instant::now(),
+    });
+    Ok(keys)
+}
+
+/// 错误是否由无法获取签名公钥 (离线或服务端异常) 引起，而非令牌本身无效
+pub fn is_keys_unavailable(error: &str) -> bool {
+    error.starts_with("获取签名公钥失败") || error.starts_with("解析签名公钥失败")
+}
+
+/// 按当前端点配置校验 id_token，返回其 claims
+pub async fn verify_id_token(token: &str, allow_expired: bool) -> Result<serde_json::Value, String> {
+    let header = parse_header(token)?;
+    let url = crate::endpoints::current().jwks_url;
+    let keys = keys_for(&url, header.kid.as_deref()).await?;
+    verify_with_keys(token, &keys, &VerifyOptions::current(allow_expired), crate::clock::now().timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
+
+    fn key_pair() -> RsaKeyPair {
+        let pem = include_str!("../tests/fixtures/jwt_test_key.pem");
+        let body: String = pem.lines().filter(|l| !l.starts_with("-----")).collect();
+        let der = general_purpose::STANDARD.decode(body).unwrap();
+        RsaKeyPair::from_pkcs8(&der).unwrap()
+    }
+
+    fn jwks(key: &RsaKeyPair, kid: &str) -> JwkSet {
+        let public = RsaPublicKeyComponents::<Vec<u8>>::from(key.public());
+        JwkSet {
+            keys: vec![Jwk {
+                kid: Some(kid.to_string()),
+                kty: "RSA".to_string(),
+                alg: Some("RS256".to_string()),
+                n: Some(general_purpose::URL_SAFE_NO_PAD.encode(&public.n)),
+                e: Some(general_purpose::URL_SAFE_NO_PAD.encode(&public.e)),
+            }],
+        }
+    }
+
+    fn sign(key: &RsaKeyPair, kid: &str, claims: serde_json::Value) -> String {
+        let header = general_purpose::URL_SAFE_NO_PAD.encode(
+            serde_json::json!({ "alg": "RS256", "typ": "JWT", "kid": kid }).to_string(),
+        );
+        let payload = general_purpose::URL_SAFE_NO_PAD.encode(claims.to_string());
+        let message = format!("{}.{}", header, payload);
+        let mut signature = vec![0u8; key.public().modulus_len()];
+        key.sign(&RSA_PKCS1_SHA256, &SystemRandom::new(), message.as_bytes(), &mut signature).unwrap();
+        format!("{}.{}", message, general_purpose::URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    #[test]
+    fn test_verify_with_local_key_set() {
+        let key = key_pair();
+        let keys = jwks(&key, "k1");
+        let options = VerifyOptions {
+            issuer: "https://auth.example.com".to_string(),
+            audience: "app_test".to_string(),
+            allow_expired: false,
+        };
+        let now = 1_700_000_000;
+        let claims = |iss: &str, aud: serde_json::Value, exp: i64| serde_json::json!({
+            "iss": iss, "aud": aud, "exp": exp, "email": "a@example.com"
+        });
+
+        let token = sign(&key, "k1", claims("https://auth.example.com", "app_test".into(), now + 600));
+        let verified = verify_with_keys(&token, &keys, &options, now).unwrap();
+        assert_eq!(verified["email"], "a@example.com");
+
+        // aud 为数组
+        let token = sign(&key, "k1", claims("https://auth.example.com/", serde_json::json!(["x", "app_test"]), now + 600));
+        assert!(verify_with_keys(&token, &keys, &options, now).is_ok());
+
+        // 篡改 payload
+        let (h, _, s) = split_token(&token).unwrap();
+        let forged_payload = general_purpose::URL_SAFE_NO_PAD.encode(
+            claims("https://auth.example.com", "app_test".into(), now + 600)
+                .to_string()
+                .replace("a@example.com", "boss@example.com"),
+        );
+        let forged = format!("{}.{}.{}", h, forged_payload, s);
+        assert!(verify_with_keys(&forged, &keys, &options, now).unwrap_err().contains("签名"));
+
+        // 未签名令牌
+        let unsigned = format!(
+            "{}.{}.",
+            general_purpose::URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#),
+            forged_payload
+        );
+        assert!(verify_with_keys(&unsigned, &keys, &options, now).is_err());
+
+        // 未知 kid
+        let token = sign(&key, "other", claims("https://auth.example.com", "app_test".into(), now + 600));
+        assert!(verify_with_keys(&token, &keys, &options, now).is_err());
+
+        // iss / aud / exp
+        let token = sign(&key, "k1", claims("https://evil.example.com", "app_test".into(), now + 600));
+        assert!(verify_with_keys(&token, &keys, &options, now).unwrap_err().contains("签发者"));
+        let token = sign(&key, "k1", claims("https://auth.example.com", "other_app".into(), now + 600));
+        assert!(verify_with_keys(&token, &keys, &options, now).unwrap_err().contains("aud"));
+        let token = sign(&key, "k1", claims("https://auth.example.com", "app_test".into(), now - 3600));
+        assert!(verify_with_keys(&token, &keys, &options, now).unwrap_err().contains("过期"));
+        let lenient = VerifyOptions { allow_expired: true, ..options };
+        assert!(verify_with_keys(&token, &keys, &lenient, now).is_ok());
+    }
+}
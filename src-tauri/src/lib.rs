@@ -3,30 +3,63 @@
 //! 暴露所有 Tauri 命令供前端调用
 
 mod ide_control;
-mod account;
-mod usage;
-mod oauth;
+pub mod account;
+pub mod claims;
+pub mod clock;
+pub mod connectivity;
+pub mod endpoints;
+pub mod jwt;
+pub mod http_client;
+pub mod jobs;
+pub mod usage;
+pub mod oauth;
+pub mod quota_poll;
+pub mod refresh_health;
+pub mod resilience;
+pub mod schedule;
+pub mod scheduler;
+pub mod revocation;
+pub mod store;
+pub mod token_refresh;
+pub mod workspace;
 mod oauth_server;
+mod device_login;
 mod tray;
-mod scheduler;
 
 
 use std::sync::Mutex;
 use account::{Account, AccountStore};
 use usage::{UsageFetcher, UsageDisplay};
-use tauri::{State, Manager};
-use chrono::Utc;
+use tauri::{AppHandle, State, Manager};
 use base64::Engine;
 
 /// 应用状态
 pub struct AppState {
-    store: Mutex<AccountStore>,
+    store: store::StoreService,
 }
 
 impl AppState {
     pub fn new() -> Self {
+        let mut store = AccountStore::load();
+        let repaired = workspace::repair_account_ids(&mut store);
+        if repaired > 0 {
+            let current = store.current.as_ref().and_then(|id| store.accounts.get(id));
+            if let Some(account) = current {
+                let _ = AccountStore::write_codex_auth(&account.auth_json);
+            }
+        }
+        // 旧版本删除账号时不会清理定时切换规则
+        if repaired + store.prune_schedules() > 0 {
+            if let Err(e) = store.save() {
+                eprintln!("保存修正后的账号数据失败: {}", e);
+            }
+        }
+        endpoints::configure(store.settings.endpoints.clone());
+        if let Err(e) = http_client::configure(&store.settings.network) {
+            eprintln!("网络设置无效，使用默认配置: {}", e);
+        }
         Self {
-            store: Mutex::new(AccountStore::load()),
+            store: store::StoreService::from_store(store),
         }
     }
 }
@@ -55,60 +88,143 @@ fn get_settings(state: State<AppState>) -> Result<account::AppSettings, String>
 /// 更新全局设置
 #[tauri::command]
 fn update_settings(state: State<AppState>, settings: account::AppSettings) -> Result<(), String> {
+    // 先校验网络设置，无效时拒绝保存
+    http_client::configure(&settings.network)?;
+
     let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    endpoints::configure(settings.endpoints.clone());
     store.settings = settings;
     store.save()?;
+    // 后台任务的开关与间隔即时生效
+    scheduler::reconfigure();
     Ok(())
 }
 
 /// 从当前 Codex 登录状态导入账号
 #[tauri::command]
-fn import_current_account(state: State<AppState>, name: String, notes: Option<String>) -> Result<Account, String> {
+async fn import_current_account(state: State<'_, AppState>, name: String, notes: Option<String>) -> Result<Account, String> {
     let auth_json = AccountStore::read_codex_auth()?;
+    let identity = verify_auth_json(&auth_json).await?;
     
     let mut store = state.store.lock().map_err(|e| e.to_string())?;
-    let account = store.add_account(name, auth_json, notes);
+    let id = store.add_account(name, auth_json, notes).id;
+    if let IdentityCheck::Unverified(reason) = identity {
+        if let Some(account) = store.accounts.get_mut(&id) {
+            account.unverified = Some(reason);
+        }
+    }
+    // 旧版本写入的 auth.json 可能带有改写过的 account_id
+    workspace::repair_account_ids(&mut store);
     store.save()?;
     
-    Ok(account)
+    Ok(store.accounts[&id].clone())
 }
 
-/// 检查 JWT Access Token 是否过期
-fn is_token_expired(token: &str) -> bool {
+/// 导入数据的身份校验结果
+#[derive(Debug, Clone)]
+pub enum IdentityCheck {
+    Verified(oauth::UserInfo),
+    /// 缺少 id_token 或无法获取签名公钥，暂时无法校验 (附原因)
+    Unverified(String),
+}
+
+/// 校验导入数据中的身份信息
+///
+/// 保存的 id_token 通常早已过期，因此只校验签名、签发者与受众；tokens.account_id 须为 id_token
+/// 授权的账号之一，邮箱和账号 ID 须与 access_token 中记录的一致，防止拼接他人的令牌。
+/// 没有 id_token 或离线无法获取签名公钥时返回 Unverified，由调用方标记后导入
+pub async fn verify_auth_json(auth_json: &serde_json::Value) -> Result<IdentityCheck, String> {
+    let tokens = auth_json.get("tokens");
+    let field = |name: &str| tokens.and_then(|t| t.get(name)).and_then(|v| v.as_str());
+    let Some(id_token) = field("id_token") else {
+        return Ok(IdentityCheck::Unverified("缺少 id_token，无法确认账号身份".to_string()));
+    };
+
+    let verified = match jwt::verify_id_token(id_token, true).await {
+        Ok(verified) => verified,
+        Err(e) if jwt::is_keys_unavailable(&e) => {
+            return Ok(IdentityCheck::Unverified(format!("无法获取签名公钥，联网后重新登录即可校验 ({})", e)));
+        }
+        Err(e) => return Err(e),
+    };
+    let user_info = oauth::user_info_from_claims(&verified).ok_or("id_token 中缺少邮箱信息")?;
+
+    if let Some(account_id) = field("account_id") {
+        // 旧版本的工作区条目可能写入了令牌中的组织 ID
+        let authorised = user_info.account_id.as_deref() == Some(account_id)
+            || claims::organizations(&verified).iter().any(|o| o.id == account_id);
+        if !authorised {
+            return Err(format!(
+                "tokens.account_id ({}) 不属于 id_token 授权的账号 ({})",
+                account_id,
+                user_info.account_id.as_deref().unwrap_or("无")
+            ));
+        }
+    }
+
+    // access_token 未经校验，仅用于一致性检查
+    if let Some(token_claims) = field("access_token").and_then(jwt::decode_unverified) {
+        let claim = |ns: &str, name: &str| token_claims.get(ns).and_then(|v| v.get(name)).and_then(|v| v.as_str());
+        if let Some(email) = claim(claims::PROFILE_NAMESPACE, "email") {
+            if !email.eq_ignore_ascii_case(&user_info.email) {
+                return Err(format!("access_token 的邮箱 ({}) 与 id_token ({}) 不一致", email, user_info.email));
+            }
+        }
+        if let Some(account_id) = claim(claims::AUTH_NAMESPACE, "chatgpt_account_id") {
+            if user_info.account_id.as_deref() != Some(account_id) {
+                return Err(format!("access_token 的账号 ID ({}) 与 id_token 不一致", account_id));
+            }
+        }
+    }
+
+    Ok(IdentityCheck::Verified(user_info))
+}
+
+/// 读取 JWT 的 exp 字段 (秒级时间戳)
+fn token_exp(token: &str) -> Option<i64> {
     // JWT 格式: header.payload.signature
     let parts: Vec<&str> = token.split('.').collect();
     if parts.len() != 3 {
-        return true; // 无效格式，视为过期
+        return None;
     }
     
     // 解码 payload (Base64URL)
-    let payload = match base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(parts[1]) {
-        Ok(p) => p,
-        Err(_) => return true,
-    };
-    
-    let json: serde_json::Value = match serde_json::from_slice(&payload) {
-        Ok(j) => j,
-        Err(_) => return true,
-    };
-    
-    // 获取 exp 字段
-    let exp = match json.get("exp").and_then(|v| v.as_i64()) {
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(parts[1]).ok()?;
+    let json: serde_json::Value = serde_json::from_slice(&payload).ok()?;
+    json.get("exp").and_then(|v| v.as_i64())
+}
+
+/// 检查 JWT Access Token 是否过期
+fn is_token_expired(token: &str) -> bool {
+    // 无效格式或缺少 exp，视为过期
+    let exp = match token_exp(token) {
         Some(e) => e,
         None => return true,
     };
     
-    // 提前 5 分钟视为过期，避免边界问题
-    let now = chrono::Utc::now().timestamp();
+    // 提前 5 分钟视为过期，避免边界问题 (以校正后的时间判断)
+    let now = clock::now().timestamp();
     exp < (now + 300)
 }
 
+/// 刷新指定账号的 Token 并保存
+pub async fn refresh_account_tokens(store: &Mutex<AccountStore>, id: &str) -> Result<(), String> {
+    token_refresh::refresh_current(store, id).await.map(|_| ())
+}
+
 /// 切换到指定账号（异步版本，自动刷新 Token）
 #[tauri::command]
 async fn switch_account(state: tauri::State<'_, AppState>, id: String) -> Result<(), String> {
+    switch_account_inner(&state.store, &id).await
+}
+
+/// 切换账号的核心流程，与 Tauri 状态解耦以便复用和测试
+pub async fn switch_account_inner(store: &Mutex<AccountStore>, id: &str) -> Result<(), String> {
+    let id = id.to_string();
+
     // 1. 获取账号数据
     let (auth_json, refresh_token) = {
-        let store = state.store.lock().map_err(|e| e.to_string())?;
+        let store = store.lock().map_err(|e| e.to_string())?;
         let account = store.accounts.get(&id)
             .ok_or_else(|| format!("账号 {} 不存在", id))?;
         
@@ -116,9 +232,6 @@ async fn switch_account(state: tauri::State<'_, AppState>, id: String) -> Result
     };
 
     // 2. 判断是否需要刷新 Token
-    let mut final_auth_json = auth_json.clone();
-    let mut final_refresh_token = refresh_token.clone();
-    
     let access_token = auth_json.get("tokens")
         .and_then(|t| t.get("access_token"))
         .and_then(|at| at.as_str())
@@ -126,54 +239,47 @@ async fn switch_account(state: tauri::State<'_, AppState>, id: String) -> Result
         
     let should_refresh = is_token_expired(access_token);
 
-    if should_refresh && refresh_token.is_some() {
-        let rt = refresh_token.as_ref().unwrap();
+    // 离线时无法刷新: access_token 仍未过期则照常切换，刷新留待联网后进行
+    if should_refresh && !connectivity::is_online() {
+        let still_valid = token_exp(access_token)
+            .map(|exp| exp > clock::now().timestamp())
+            .unwrap_or(false);
+        if !still_valid {
+            return Err("OFFLINE:当前处于离线状态，且该账号的 Token 已过期，请联网后再切换".to_string());
+        }
+        if refresh_token.is_some() {
+            connectivity::queue_refresh(&id);
+        }
+    } else if let Some(rt) = refresh_token.as_deref().filter(|_| should_refresh) {
         println!("Token 已过期或即将过期，正在尝试刷新...");
         
-        match oauth::refresh_access_token(rt).await {
-            Ok(token_res) => {
-                println!("Token 刷新成功！");
-                
-                if let Some(obj) = final_auth_json.as_object_mut() {
-                    if let Some(tokens_obj) = obj.get_mut("tokens").and_then(|v| v.as_object_mut()) {
-                        tokens_obj.insert("access_token".to_string(), serde_json::json!(token_res.access_token));
-                        if let Some(new_rt) = &token_res.refresh_token {
-                            tokens_obj.insert("refresh_token".to_string(), serde_json::json!(new_rt));
-                            final_refresh_token = Some(new_rt.clone());
-                        }
-                        if let Some(new_id) = &token_res.id_token {
-                            tokens_obj.insert("id_token".to_string(), serde_json::json!(new_id));
-                        }
-                        
-                        let seconds = token_res.expires_in.unwrap_or(3600);
-                        let expires_at = (chrono::Utc::now() + chrono::Duration::seconds(seconds as i64)).to_rfc3339();
-                        tokens_obj.insert("expires_at".to_string(), serde_json::json!(expires_at));
-                    }
-                }
-            }
+        // 刷新结果由协调器写入存储
+        match token_refresh::refresh(store, &id, rt).await {
+            Ok(_) => println!("Token 刷新成功！"),
             Err(e) => {
                 eprintln!("Token 刷新失败: {}，将使用旧 Token 尝试", e);
+                if !connectivity::is_online() {
+                    connectivity::queue_refresh(&id);
+                }
             }
         }
     } else {
         println!("Token 仍在有效期内，直接使用。");
     }
 
-    // 无论是否刷新，都更新 last_refresh 以满足 CLI 校验
-    if let Some(obj) = final_auth_json.as_object_mut() {
-        obj.insert("last_refresh".to_string(), serde_json::json!(chrono::Utc::now().to_rfc3339()));
-    }
-
-    // 3. 统一写入 auth.json 并更新 Store
+    // 3. 以存储中的最新令牌写入 auth.json 并更新 Store
     {
-        let mut store = state.store.lock().map_err(|e| e.to_string())?;
+        let mut store = store.lock().map_err(|e| e.to_string())?;
         store.current = Some(id.clone());
         
-        if let Some(account) = store.accounts.get_mut(&id) {
-            account.last_used = Some(chrono::Utc::now());
-            account.auth_json = final_auth_json.clone();
-            account.refresh_token = final_refresh_token;
+        let account = store.accounts.get_mut(&id)
+            .ok_or_else(|| format!("账号 {} 不存在", id))?;
+        account.last_used = Some(chrono::Utc::now());
+        // 无论是否刷新，都更新 last_refresh 以满足 CLI 校验
+        if let Some(obj) = account.auth_json.as_object_mut() {
+            obj.insert("last_refresh".to_string(), serde_json::json!(chrono::Utc::now().to_rfc3339()));
         }
+        let final_auth_json = account.auth_json.clone();
         
         AccountStore::write_codex_auth(&final_auth_json)?;
         store.save()?;
@@ -183,15 +289,6 @@ async fn switch_account(state: tauri::State<'_, AppState>, id: String) -> Result
     Ok(())
 }
 
-/// 删除账号
-#[tauri::command]
-fn delete_account(state: State<AppState>, id: String) -> Result<(), String> {
-    let mut store = state.store.lock().map_err(|e| e.to_string())?;
-    store.delete_account(&id)?;
-    store.save()?;
-    Ok(())
-}
-
 /// 更新账号信息
 #[tauri::command]
 fn update_account(state: State<AppState>, id: String, name: Option<String>, notes: Option<String>) -> Result<(), String> {
@@ -210,24 +307,195 @@ fn export_accounts(state: State<AppState>) -> Result<String, String> {
 
 /// 导入账号配置
 #[tauri::command]
-fn import_accounts(state: State<AppState>, json: String) -> Result<(), String> {
-    let new_store = AccountStore::import(&json)?;
-    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+async fn import_accounts(state: State<'_, AppState>, json: String) -> Result<Vec<account::ImportRecord>, String> {
+    import_accounts_inner(&state.store, &json).await
+}
+
+/// 导入账号配置的核心流程，与 Tauri 状态解耦以便测试
+///
+/// 逐个校验账号: 校验不通过的账号不导入，无法校验的账号标记为未校验后导入，互不影响。
+/// 全部账号都被拒绝时不覆盖现有数据
+pub async fn import_accounts_inner(store: &Mutex<AccountStore>, json: &str) -> Result<Vec<account::ImportRecord>, String> {
+    let mut new_store = AccountStore::import(json)?;
+    let mut records = Vec::new();
+    for account in new_store.accounts.values_mut() {
+        let status = match verify_auth_json(&account.auth_json).await {
+            Ok(IdentityCheck::Verified(_)) => {
+                account.unverified = None;
+                account::ImportStatus::Verified
+            }
+            Ok(IdentityCheck::Unverified(reason)) => {
+                account.unverified = Some(reason.clone());
+                account::ImportStatus::Unverified(reason)
+            }
+            Err(e) => account::ImportStatus::Rejected(e),
+        };
+        records.push(account::ImportRecord {
+            account_id: account.id.clone(),
+            account_name: account.name.clone(),
+            status,
+        });
+    }
+    records.sort_by(|a, b| a.account_name.cmp(&b.account_name));
+
+    let rejected: Vec<&account::ImportRecord> = records.iter()
+        .filter(|r| matches!(r.status, account::ImportStatus::Rejected(_)))
+        .collect();
+    if !records.is_empty() && rejected.len() == records.len() {
+        let details: Vec<String> = rejected.iter()
+            .map(|r| match &r.status {
+                account::ImportStatus::Rejected(e) => format!("「{}」: {}", r.account_name, e),
+                _ => String::new(),
+            })
+            .collect();
+        return Err(format!("所有账号均未通过校验，未导入: {}", details.join("; ")));
+    }
+    for record in &rejected {
+        println!("[Import] 账号「{}」校验不通过，已跳过", record.account_name);
+        new_store.accounts.remove(&record.account_id);
+    }
+    if new_store.current.as_ref().is_some_and(|id| !new_store.accounts.contains_key(id)) {
+        new_store.current = None;
+    }
+    new_store.prune_schedules();
+    workspace::repair_account_ids(&mut new_store);
+
+    let mut store = store.lock().map_err(|e| e.to_string())?;
     *store = new_store;
     store.save()?;
-    Ok(())
+    Ok(records)
 }
 
 /// 完成 OAuth 登录并保存账号
 #[tauri::command]
-async fn finalize_oauth_login(state: tauri::State<'_, AppState>, code: String) -> Result<Account, String> {
-    let token_res = oauth_server::complete_oauth_login(code).await?;
-    
-    let user_info = token_res.id_token.as_ref()
-        .and_then(|id_t| oauth::parse_user_info(id_t))
+async fn finalize_oauth_login(
+    app_handle: AppHandle,
+    state: tauri::State<'_, AppState>,
+    code: String,
+    oauth_state: Option<String>,
+) -> Result<Account, String> {
+    oauth_server::emit_progress(&app_handle, oauth_state.as_deref(), "exchanging", None);
+    let result = match oauth_server::complete_oauth_login(code, oauth_state.clone()).await {
+        Ok(login) => save_login_result(&state.store, login).await,
+        Err(e) => Err(e),
+    };
+    report_oauth_result(&app_handle, oauth_state.as_deref(), &result);
+    result
+}
+
+/// 手动粘贴回调地址 (或授权码) 完成 OAuth 登录
+#[tauri::command]
+async fn submit_oauth_redirect(app_handle: AppHandle, state: tauri::State<'_, AppState>, input: String) -> Result<Account, String> {
+    oauth_server::emit_progress(&app_handle, None, "exchanging", None);
+    let result = match oauth_server::complete_oauth_login_manual(&input).await {
+        Ok(login) => save_login_result(&state.store, login).await,
+        Err(e) => Err(e),
+    };
+    report_oauth_result(&app_handle, None, &result);
+    result
+}
+
+/// 发送浏览器登录的最终进度
+fn report_oauth_result(app_handle: &AppHandle, oauth_state: Option<&str>, result: &Result<Account, String>) {
+    match result {
+        Ok(account) => oauth_server::emit_progress(app_handle, oauth_state, "completed", Some(account.name.clone())),
+        Err(e) => oauth_server::emit_progress(app_handle, oauth_state, "failed", Some(e.clone())),
+    }
+}
+
+/// 保存浏览器登录结果: 重新登录时更新原账号，为工作区授权时保存该工作区的条目，否则新增账号
+async fn save_login_result(store: &Mutex<AccountStore>, login: oauth_server::OAuthResult) -> Result<Account, String> {
+    match (login.relogin_account_id, login.workspace) {
+        (Some(id), _) => relogin_account(store, &id, login.tokens).await,
+        (None, Some(workspace)) => workspace::save_workspace_login(store, &workspace, login.tokens).await,
+        (None, None) => save_oauth_account(store, login.tokens).await,
+    }
+}
+
+/// 用重新登录得到的令牌替换已有账号的令牌
+///
+/// 授权的身份必须与原账号一致 (优先比对 account_id，其次比对邮箱)；原账号两者都没有保存时
+/// 无法确认身份，拒绝并提示作为新账号添加。名称、备注、创建时间及所选工作区等其他数据保持不变
+pub async fn relogin_account(store: &Mutex<AccountStore>, id: &str, token_res: oauth::TokenResponse) -> Result<Account, String> {
+    let id_token = token_res.id_token.as_deref()
+        .ok_or("无法从授权响应中解析用户信息 (Missing ID Token)")?;
+    let user_info = oauth::verified_user_info(id_token, false).await?;
+
+    let mut store = store.lock().map_err(|e| e.to_string())?;
+    let account = store.accounts.get_mut(id)
+        .ok_or_else(|| format!("账号 {} 不存在", id))?;
+
+    let tokens = account.auth_json.get("tokens");
+    let old_account_id = tokens
+        .and_then(|t| t.get("account_id"))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    let old_email = tokens
+        .and_then(|t| t.get("id_token"))
+        .and_then(|v| v.as_str())
+        .and_then(oauth::parse_user_info)
+        .map(|u| u.email);
+
+    let same_email = old_email.as_ref().map(|email| email.eq_ignore_ascii_case(&user_info.email));
+    // 其他工作区的条目: 同一用户且新令牌仍包含该工作区
+    let in_workspaces = || {
+        jwt::decode_unverified(id_token)
+            .map(|c| claims::organizations(&c).iter().any(|o| Some(&o.id) == old_account_id.as_ref()))
+            .unwrap_or(false)
+    };
+    let matches = match (&old_account_id, &user_info.account_id) {
+        (Some(old), Some(new)) if old == new => Some(true),
+        (Some(_), Some(_)) => Some(same_email == Some(true) && in_workspaces()),
+        _ => same_email,
+    };
+    let Some(matches) = matches else {
+        return Err(format!(
+            "账号「{}」没有保存邮箱或账号 ID，无法确认本次授权的 {} 是否为同一账号，请将其作为新账号添加",
+            account.name, user_info.email
+        ));
+    };
+    if !matches {
+        return Err(format!(
+            "身份不匹配: 本次授权的是 {}，而不是账号「{}」{}，请使用对应的 OpenAI 账号登录",
+            user_info.email,
+            account.name,
+            old_email.map(|e| format!(" ({})", e)).unwrap_or_default()
+        ));
+    }
+
+    oauth::apply_tokens(&mut account.auth_json, &token_res);
+    if old_account_id.is_none() {
+        if let Some(tokens_obj) = account.auth_json.get_mut("tokens").and_then(|t| t.as_object_mut()) {
+            tokens_obj.insert("account_id".to_string(), serde_json::json!(user_info.account_id));
+        }
+    }
+    if let Some(rt) = token_res.refresh_token {
+        account.set_refresh_token(rt);
+    }
+    // 新令牌有效，清除失效标记
+    if let Some(quota) = account.cached_quota.as_mut() {
+        quota.is_valid_for_cli = true;
+    }
+    refresh_health::clear(account);
+    account.unverified = None;
+    let account = account.clone();
+
+    // 当前使用中的账号同步写入 auth.json
+    if store.current.as_deref() == Some(id) {
+        AccountStore::write_codex_auth(&account.auth_json)?;
+    }
+    store.save()?;
+    println!("[OAuth] 账号 {} 已重新登录", account.name);
+    Ok(account)
+}
+
+/// 将 OAuth 授权结果保存为新账号 (浏览器登录与设备码登录共用)
+pub async fn save_oauth_account(store: &Mutex<AccountStore>, token_res: oauth::TokenResponse) -> Result<Account, String> {
+    let id_token = token_res.id_token.as_deref()
         .ok_or("无法从授权响应中解析用户信息 (Missing ID Token)")?;
+    let user_info = oauth::verified_user_info(id_token, false).await?;
     
-    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    let mut store = store.lock().map_err(|e| e.to_string())?;
     
     // 计算过期时间
     let expires_at = token_res.expires_in.map(|secs| {
@@ -250,9 +518,9 @@ async fn finalize_oauth_login(state: tauri::State<'_, AppState>, code: String) -
         Some("OpenAI OAuth 登录".to_string())
     );
     
-    account.refresh_token = token_res.refresh_token.clone();
-    if let Some(acc) = store.accounts.get_mut(&account.id) {
-        acc.refresh_token = token_res.refresh_token;
+    if let (Some(rt), Some(acc)) = (token_res.refresh_token, store.accounts.get_mut(&account.id)) {
+        acc.set_refresh_token(rt);
+        account = acc.clone();
     }
     
     store.save()?;
@@ -268,10 +536,15 @@ fn check_codex_login() -> Result<bool, String> {
 /// 获取指定账号的用量信息（不切换账号）
 #[tauri::command]
 async fn get_quota_by_id(state: tauri::State<'_, AppState>, id: String) -> Result<UsageDisplay, String> {
+    get_quota_inner(&state.store, &id).await
+}
+
+/// 查询用量的核心流程，与 Tauri 状态解耦以便复用和测试
+pub async fn get_quota_inner(store: &Mutex<AccountStore>, id: &str) -> Result<UsageDisplay, String> {
     // 1. 从 Store 获取该账号的 Token
     let (access_token, account_id, refresh_token) = {
-        let store = state.store.lock().map_err(|e| e.to_string())?;
-        let account = store.accounts.get(&id)
+        let store = store.lock().map_err(|e| e.to_string())?;
+        let account = store.accounts.get(id)
             .ok_or_else(|| format!("账号 {} 不存在", id))?;
         
         // 从 auth_json 中提取 access_token 和 account_id
@@ -292,90 +565,64 @@ async fn get_quota_by_id(state: tauri::State<'_, AppState>, id: String) -> Resul
         (at, aid, rt)
     };
 
-    // 2. 直接使用该账号的 Token 获取用量
-    let (usage, new_tokens) = UsageFetcher::fetch_usage_direct(access_token, account_id, refresh_token).await?;
-    
-    // 3. 如果有新 Token，更新该账号的数据
-    if let Some(tokens) = new_tokens {
-        let mut store = state.store.lock().map_err(|e| e.to_string())?;
-        if let Some(account) = store.accounts.get_mut(&id) {
-            
-            // 更新 auth_json 中的 Token 信息
-            if let Some(obj) = account.auth_json.as_object_mut() {
-                if let Some(tokens_obj) = obj.get_mut("tokens").and_then(|v| v.as_object_mut()) {
-                    tokens_obj.insert("access_token".to_string(), serde_json::json!(tokens.access_token));
-                    
-                    if let Some(rt) = &tokens.refresh_token {
-                        tokens_obj.insert("refresh_token".to_string(), serde_json::json!(rt));
-                    }
-                    
-                    if let Some(it) = &tokens.id_token {
-                        tokens_obj.insert("id_token".to_string(), serde_json::json!(it));
-                    }
-                    
-                    if let Some(expires_in) = tokens.expires_in {
-                        let expires_at = (chrono::Utc::now() + chrono::Duration::seconds(expires_in as i64)).to_rfc3339();
-                        tokens_obj.insert("expires_at".to_string(), serde_json::json!(expires_at));
-                    }
-                }
-            }
-
-            // 更新 refresh_token 字段
-            if let Some(rt) = tokens.refresh_token {
-                account.refresh_token = Some(rt);
-            }
+    // 2. 离线时直接返回缓存
+    if !connectivity::is_online() {
+        return cached_usage(store, id);
+    }
 
-            // 更新配额缓存
-            account.cached_quota = Some(account::CachedQuota {
-                five_hour_left: usage.five_hour_left as f64,
-                five_hour_reset: usage.five_hour_reset.clone(),
-                five_hour_reset_at: usage.five_hour_reset_at,
-                weekly_left: usage.weekly_left as f64,
-                weekly_reset: usage.weekly_reset.clone(),
-                weekly_reset_at: usage.weekly_reset_at,
-                plan_type: usage.plan_type.clone(),
-                is_valid_for_cli: usage.is_valid_for_cli,
-                updated_at: Utc::now(),
-            });
-        }
-        store.save()?;
-    } else {
-        // 即使没有新 Token，也更新配额缓存
-        let mut store = state.store.lock().map_err(|e| e.to_string())?;
-        if let Some(account) = store.accounts.get_mut(&id) {
-            account.cached_quota = Some(account::CachedQuota {
-                five_hour_left: usage.five_hour_left as f64,
-                five_hour_reset: usage.five_hour_reset.clone(),
-                five_hour_reset_at: usage.five_hour_reset_at,
-                weekly_left: usage.weekly_left as f64,
-                weekly_reset: usage.weekly_reset.clone(),
-                weekly_reset_at: usage.weekly_reset_at,
-                plan_type: usage.plan_type.clone(),
-                is_valid_for_cli: usage.is_valid_for_cli,
-                updated_at: Utc::now(),
-            });
+    // 直接使用该账号的 Token 获取用量，请求中途断网同样回退到缓存
+    // 令牌失效时经协调器刷新，新令牌由其写入存储
+    let refresh = refresh_token.as_deref().map(|rt| move || token_refresh::refresh(store, id, rt));
+    let usage = match UsageFetcher::fetch_usage_direct(access_token, account_id, refresh).await {
+        Ok((usage, _)) => usage,
+        Err(e) if !connectivity::is_online() => {
+            println!("获取用量失败 ({})，已离线，返回缓存数据", e);
+            return cached_usage(store, id);
         }
-        store.save()?;
+        Err(e) => return Err(e),
+    };
+    
+    // 3. 更新配额缓存
+    let mut store = store.lock().map_err(|e| e.to_string())?;
+    if let Some(account) = store.accounts.get_mut(id) {
+        account.cached_quota = Some(account::CachedQuota::from(&usage));
     }
+    store.save()?;
 
     Ok(usage)
 }
 
-/// 重载 IDE 窗口
+/// 离线时由缓存构造用量数据
+fn cached_usage(store: &Mutex<AccountStore>, id: &str) -> Result<UsageDisplay, String> {
+    let store = store.lock().map_err(|e| e.to_string())?;
+    let account = store.accounts.get(id)
+        .ok_or_else(|| format!("账号 {} 不存在", id))?;
+    account.cached_quota.as_ref()
+        .map(UsageDisplay::from)
+        .ok_or_else(|| "OFFLINE:当前处于离线状态，且该账号没有缓存的配额数据".to_string())
+}
+
+/// 扫描运行中的 IDE 与 Codex 进程
+#[tauri::command]
+fn get_ide_processes() -> ide_control::ProcessScan {
+    ide_control::scan_processes()
+}
+
+/// 重载 IDE 窗口，返回每个 IDE 的处理结果
 #[tauri::command]
-async fn reload_ide_windows(use_window_reload: bool) -> Result<Vec<String>, String> {
-    let ides = ide_control::detect_running_ides();
-    let mut reloaded = Vec::new();
-
-    for ide in &ides {
-        if let Err(e) = ide_control::reload_ide(ide, use_window_reload) {
-            println!("重载 {} 失败: {}", ide, e);
-        } else {
-            reloaded.push(ide.clone());
+async fn reload_ide_windows(use_window_reload: bool) -> Result<Vec<ide_control::IdeReloadReport>, String> {
+    // 等待进程退出和 AppleScript 的延时都会阻塞，放到阻塞线程执行
+    let reports = tokio::task::spawn_blocking(move || ide_control::reload_running_ides(use_window_reload))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    for report in &reports {
+        match &report.error {
+            Some(e) => println!("重载 {} 失败: {}", report.ide, e),
+            None => println!("重载 {}: {}", report.ide, report.actions.join("；")),
         }
     }
-
-    Ok(reloaded)
+    Ok(reports)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -391,10 +638,18 @@ pub fn run() {
                 eprintln!("初始化托盘失败: {:?}", e);
             }
             
-            // 启动后台调度器
-            let store = app.state::<AppState>().store.lock().unwrap().clone();
-            let store_arc = std::sync::Arc::new(std::sync::Mutex::new(store));
-            scheduler::start(store_arc, app.handle().clone());
+            // 存储变更转发给前端
+            store::forward_events(app.handle().clone());
+            refresh_health::forward_events(app.handle().clone());
+
+            // 启动后台任务 (与命令共用同一份存储)
+            scheduler::start(app.state::<AppState>().store.clone(), app.handle().clone(), jobs::builtin());
+
+            // 启动连通性监测
+            connectivity::start(app.handle().clone());
+
+            // 监测系统睡眠与时钟跳变
+            clock::start(app.handle().clone());
             
             Ok(())
         })
@@ -416,14 +671,34 @@ pub fn run() {
             get_current_account_id,
             import_current_account,
             switch_account,
-            delete_account,
+            revocation::delete_account,
+            revocation::delete_accounts,
+            revocation::get_revocation_log,
+            claims::get_account_claims,
+            claims::list_account_claims,
+            workspace::list_account_workspaces,
             update_account,
             export_accounts,
             import_accounts,
             check_codex_login,
             get_quota_by_id,
             oauth_server::start_oauth_login,
+            oauth_server::check_oauth_port,
+            oauth_server::release_oauth_port,
+            oauth_server::cancel_oauth_login,
+            http_client::test_connection,
+            resilience::get_network_diagnostics,
+            connectivity::get_connectivity_status,
+            schedule::get_switch_schedules,
+            schedule::save_switch_schedules,
+            schedule::preview_switch_schedule,
+            scheduler::get_scheduler_status,
+            scheduler::run_scheduler_now,
             finalize_oauth_login,
+            submit_oauth_redirect,
+            device_login::start_device_login,
+            device_login::cancel_device_login,
+            get_ide_processes,
             reload_ide_windows,
             get_settings,
             update_settings,
@@ -1,13 +1,8 @@
 use serde::{Deserialize, Serialize};
-use reqwest;
 use sha2::{Sha256, Digest};
 use base64::{engine::general_purpose, Engine as _};
 use rand::{rng, RngCore};
-
-/// OpenAI 官方授权常量 (参考 codex-main)
-pub const CLIENT_ID: &str = "app_EMoamEEZ73f0CkXaXp7hrann";
-pub const AUTH_URL: &str = "https://auth.openai.com/oauth/authorize";
-pub const TOKEN_URL: &str = "https://auth.openai.com/oauth/token";
+use crate::resilience::{send_with_retry, RetryPolicy};
 
 /// PKCE 相关的代码
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,6 +39,49 @@ pub struct TokenResponse {
     pub expires_in: Option<u64>,
 }
 
+/// 设备码申请响应 (Codex 设备码登录)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceCodeResponse {
+    pub device_auth_id: String,
+    #[serde(alias = "usercode")]
+    pub user_code: String,
+    /// 轮询间隔（秒），服务端以字符串或数字返回，缺省 5 秒
+    #[serde(default = "default_device_interval", deserialize_with = "deserialize_interval")]
+    pub interval: u64,
+}
+
+fn default_device_interval() -> u64 {
+    5
+}
+
+fn deserialize_interval<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(match value {
+        serde_json::Value::Number(n) => n.as_u64().unwrap_or_else(default_device_interval),
+        serde_json::Value::String(s) => s.trim().parse().unwrap_or_else(|_| default_device_interval()),
+        _ => default_device_interval(),
+    })
+}
+
+/// 设备码轮询结果
+#[derive(Debug, Clone)]
+pub enum DevicePoll {
+    /// 用户尚未完成授权
+    Pending,
+    /// 轮询过快，需要加大间隔
+    SlowDown,
+    /// 用户已授权: 服务端返回授权码及对应的 PKCE verifier
+    Authorized { authorization_code: String, code_verifier: String },
+    /// 被拒绝、已过期或其他不可恢复的错误，停止轮询
+    Rejected(String),
+}
+
+#[derive(Debug, Deserialize)]
+struct DeviceAuthorization {
+    authorization_code: String,
+    code_verifier: String,
+}
+
 /// 用户信息预提取 (通过解析 id_token)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserInfo {
@@ -57,24 +95,27 @@ pub async fn exchange_code(
     redirect_uri: &str, 
     code_verifier: &str
 ) -> Result<TokenResponse, String> {
-    let client = reqwest::Client::new();
+    let client = crate::http_client::client();
+    let endpoints = crate::endpoints::current();
     
     // 官方格式: 手动拼接字符串
     let body = format!(
         "grant_type=authorization_code&code={}&redirect_uri={}&client_id={}&code_verifier={}",
         urlencoding::encode(code),
         urlencoding::encode(redirect_uri),
-        urlencoding::encode(CLIENT_ID),
+        urlencoding::encode(&endpoints.client_id),
         urlencoding::encode(code_verifier)
     );
 
-    let response = client
-        .post(TOKEN_URL)
-        .header("Content-Type", "application/x-www-form-urlencoded")
-        .body(body)
-        .send()
-        .await
-        .map_err(|e| format!("请求令牌失败: {}", e))?;
+    // 授权码只能使用一次，不做重试，仅参与熔断统计
+    let response = send_with_retry(RetryPolicy::once(), || {
+        client
+            .post(&endpoints.token_url)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body.clone())
+    })
+    .await
+    .map_err(|e| format!("请求令牌失败: {}", e))?;
 
     if !response.status().is_success() {
         let error_body = response.text().await.unwrap_or_default();
@@ -88,26 +129,30 @@ pub async fn exchange_code(
 
 /// 使用刷新令牌获取新访问令牌
 pub async fn refresh_access_token(refresh_token: &str) -> Result<TokenResponse, String> {
-    let client = reqwest::Client::new();
+    let client = crate::http_client::client();
+    let endpoints = crate::endpoints::current();
     
     let params = [
         ("grant_type", "refresh_token"),
-        ("client_id", CLIENT_ID),
+        ("client_id", endpoints.client_id.as_str()),
         ("refresh_token", refresh_token),
         ("scope", "openid profile email offline_access"),
     ];
 
-    let response = client
-        .post(TOKEN_URL)
-        .header("Content-Type", "application/x-www-form-urlencoded")
-        .form(&params)
-        .send()
-        .await
-        .map_err(|e| format!("刷新令牌失败: {}", e))?;
+    // refresh_token 每次使用都会轮换，可能已被服务端使用的请求不能重发
+    let response = send_with_retry(RetryPolicy::single_use(), || {
+        client
+            .post(&endpoints.token_url)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .form(&params)
+    })
+    .await
+    .map_err(|e| format!("刷新令牌失败: {}", e))?;
 
-    if !response.status().is_success() {
+    let status = response.status();
+    if !status.is_success() {
         let error_body = response.text().await.unwrap_or_default();
-        return Err(format!("刷新令牌被拒绝: {}", error_body));
+        return Err(format!("刷新令牌被拒绝 (HTTP {}): {}", status.as_u16(), error_body));
     }
 
     response.json::<TokenResponse>()
@@ -115,6 +160,148 @@ pub async fn refresh_access_token(refresh_token: &str) -> Result<TokenResponse,
         .map_err(|e| format!("解析刷新响应失败: {}", e))
 }
 
+/// 撤销令牌 (RFC 7009)
+///
+/// token_type_hint 为 "refresh_token" 或 "access_token"。按规范，
+/// 服务端对已失效或未知的令牌同样返回 200
+pub async fn revoke_token(token: &str, token_type_hint: &str) -> Result<(), String> {
+    let client = crate::http_client::client();
+    let endpoints = crate::endpoints::current();
+
+    let params = [
+        ("token", token),
+        ("token_type_hint", token_type_hint),
+        ("client_id", endpoints.client_id.as_str()),
+    ];
+
+    let response = send_with_retry(RetryPolicy::default(), || {
+        client
+            .post(&endpoints.revoke_url)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .form(&params)
+    })
+    .await
+    .map_err(|e| format!("撤销令牌失败: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_body = response.text().await.unwrap_or_default();
+        return Err(format!("撤销令牌被拒绝 (HTTP {}): {}", status.as_u16(), error_body));
+    }
+    Ok(())
+}
+
+/// 设备码登录的验证页 (用户在任意设备上打开并输入用户码)
+pub fn device_verification_url() -> String {
+    format!("{}/codex/device", crate::endpoints::current().issuer.trim_end_matches('/'))
+}
+
+/// 设备码登录交换授权码时使用的 redirect_uri
+fn device_redirect_uri() -> String {
+    format!("{}/deviceauth/callback", crate::endpoints::current().issuer.trim_end_matches('/'))
+}
+
+/// 申请设备码 (POST {device_auth_url}/usercode)
+pub async fn request_device_code() -> Result<DeviceCodeResponse, String> {
+    let client = crate::http_client::client();
+    let endpoints = crate::endpoints::current();
+    let url = format!("{}/usercode", endpoints.device_auth_url.trim_end_matches('/'));
+    let body = serde_json::json!({ "client_id": endpoints.client_id });
+
+    let response = send_with_retry(RetryPolicy::default(), || client.post(&url).json(&body))
+        .await
+        .map_err(|e| format!("申请设备码失败: {}", e))?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err("服务端未开启设备码登录".to_string());
+    }
+    if !status.is_success() {
+        let error_body = response.text().await.unwrap_or_default();
+        return Err(format!("OpenAI 返回错误: {}", error_body));
+    }
+
+    response.json::<DeviceCodeResponse>()
+        .await
+        .map_err(|e| format!("解析设备码响应失败: {}", e))
+}
+
+/// 轮询设备码授权结果一次 (POST {device_auth_url}/token)
+///
+/// 403/404 表示用户尚未授权；返回 Err 的是网络或服务端临时错误，可继续轮询
+pub async fn poll_device_token(device: &DeviceCodeResponse) -> Result<DevicePoll, String> {
+    let client = crate::http_client::client();
+    let endpoints = crate::endpoints::current();
+    let url = format!("{}/token", endpoints.device_auth_url.trim_end_matches('/'));
+    let body = serde_json::json!({
+        "device_auth_id": device.device_auth_id,
+        "user_code": device.user_code,
+    });
+
+    // 授权成功的响应只会返回一次，可能已被服务端处理的请求不能重发
+    let response = send_with_retry(RetryPolicy::single_use(), || client.post(&url).json(&body))
+        .await
+        .map_err(|e| format!("轮询设备码失败: {}", e))?;
+
+    let status = response.status();
+    if status.is_success() {
+        return response.json::<DeviceAuthorization>()
+            .await
+            .map(|a| DevicePoll::Authorized {
+                authorization_code: a.authorization_code,
+                code_verifier: a.code_verifier,
+            })
+            .map_err(|e| format!("解析设备码授权结果失败: {}", e));
+    }
+
+    let error_body = response.text().await.unwrap_or_default();
+    let error = serde_json::from_str::<serde_json::Value>(&error_body)
+        .ok()
+        .and_then(|v| {
+            let e = v.get("error")?;
+            e.as_str().or_else(|| e.get("code").and_then(|c| c.as_str())).map(str::to_string)
+        })
+        .unwrap_or_default();
+
+    match (status.as_u16(), error.as_str()) {
+        (_, "access_denied") => Ok(DevicePoll::Rejected("用户拒绝了授权".to_string())),
+        (_, "expired_token") => Ok(DevicePoll::Rejected("设备码已过期，请重新发起登录".to_string())),
+        (_, "slow_down") | (429, _) => Ok(DevicePoll::SlowDown),
+        (403 | 404, _) => Ok(DevicePoll::Pending),
+        (400..=499, _) => Ok(DevicePoll::Rejected(format!("OpenAI 返回错误 (HTTP {}): {}", status.as_u16(), error_body))),
+        _ => Err(format!("OpenAI 返回错误 (HTTP {}): {}", status.as_u16(), error_body)),
+    }
+}
+
+/// 用设备码授权得到的授权码交换令牌
+pub async fn exchange_device_code(authorization_code: &str, code_verifier: &str) -> Result<TokenResponse, String> {
+    exchange_code(authorization_code, &device_redirect_uri(), code_verifier).await
+}
+
+/// 将刷新结果写回 auth_json 的 tokens 字段
+pub fn apply_tokens(auth_json: &mut serde_json::Value, tokens: &TokenResponse) {
+    let expires_in = tokens.expires_in.unwrap_or(3600);
+    let expires_at = (chrono::Utc::now() + chrono::Duration::seconds(expires_in as i64)).to_rfc3339();
+
+    if let Some(obj) = auth_json.as_object_mut() {
+        let tokens_obj = obj.entry("tokens")
+            .or_insert_with(|| serde_json::json!({}));
+        if !tokens_obj.is_object() {
+            *tokens_obj = serde_json::json!({});
+        }
+        if let Some(tokens_obj) = tokens_obj.as_object_mut() {
+            tokens_obj.insert("access_token".to_string(), serde_json::json!(tokens.access_token));
+            if let Some(rt) = &tokens.refresh_token {
+                tokens_obj.insert("refresh_token".to_string(), serde_json::json!(rt));
+            }
+            if let Some(it) = &tokens.id_token {
+                tokens_obj.insert("id_token".to_string(), serde_json::json!(it));
+            }
+            tokens_obj.insert("expires_at".to_string(), serde_json::json!(expires_at));
+        }
+    }
+}
+
 /// 从 ID Token 中提取用户信息 (JWT 解析)
 pub fn parse_user_info(id_token: &str) -> Option<UserInfo> {
     let parts: Vec<&str> = id_token.split('.').collect();
@@ -122,7 +309,16 @@ pub fn parse_user_info(id_token: &str) -> Option<UserInfo> {
     
     let payload = general_purpose::URL_SAFE_NO_PAD.decode(parts[1]).ok()?;
     let json: serde_json::Value = serde_json::from_slice(&payload).ok()?;
-    
+    user_info_from_claims(&json)
+}
+
+/// 校验 id_token 签名及 iss / aud / exp 后提取用户信息
+pub async fn verified_user_info(id_token: &str, allow_expired: bool) -> Result<UserInfo, String> {
+    let claims = crate::jwt::verify_id_token(id_token, allow_expired).await?;
+    user_info_from_claims(&claims).ok_or_else(|| "id_token 中缺少邮箱信息".to_string())
+}
+
+pub(crate) fn user_info_from_claims(json: &serde_json::Value) -> Option<UserInfo> {
     let email = json.get("email")?.as_str()?.to_string();
     
     // 从 OpenAI 特有的 claims 中获取 account_id
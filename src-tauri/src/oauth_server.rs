@@ -1,25 +1,150 @@
-use std::sync::OnceLock;
-use std::sync::Mutex;
-use tauri::{AppHandle, Emitter};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, State};
 use tauri_plugin_opener::OpenerExt;
 use tokio::net::TcpListener;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use url::Url;
 use crate::oauth;
+use crate::workspace::WorkspaceTarget;
 use base64::{engine::general_purpose, Engine as _};
 use rand::{rng, RngCore};
 
-/// 使用 OnceLock 代替 lazy_static 存储 OAuth 流程中的临时数据
-static PENDING_LOGIN: OnceLock<Mutex<Option<PendingLogin>>> = OnceLock::new();
+/// 进行中的浏览器登录 (按 state 索引，可同时进行多个) 及正在运行的回调服务
+static PENDING_LOGINS: OnceLock<Mutex<Registry>> = OnceLock::new();
 
-fn get_pending_login() -> &'static Mutex<Option<PendingLogin>> {
-    PENDING_LOGIN.get_or_init(|| Mutex::new(None))
+fn registry() -> MutexGuard<'static, Registry> {
+    PENDING_LOGINS
+        .get_or_init(|| Mutex::new(Registry::default()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+#[derive(Default)]
+struct Registry {
+    logins: HashMap<String, PendingLogin>,
+    /// 正在运行回调服务的端口 (多个登录共用同一端口)
+    servers: HashSet<u16>,
 }
 
 struct PendingLogin {
     pkce: oauth::PkceCodes,
-    state: String,
     port: u16,
+    expires_at: Instant,
+    /// 回调服务已收到的授权码 (用于仅凭 code 找回对应的登录)
+    code: Option<String>,
+    /// 重新登录的目标账号 (为空表示新增账号)
+    relogin_account_id: Option<String>,
+    /// 限定授权的工作区
+    workspace: Option<WorkspaceTarget>,
+}
+
+/// 授权码交换结果
+pub struct OAuthResult {
+    pub tokens: oauth::TokenResponse,
+    /// 重新登录的目标账号
+    pub relogin_account_id: Option<String>,
+    /// 限定授权的工作区
+    pub workspace: Option<WorkspaceTarget>,
+}
+
+impl Registry {
+    /// 清除已过期的登录，返回被清除的 state
+    fn purge_expired(&mut self, now: Instant) -> Vec<String> {
+        let expired: Vec<String> = self.logins.iter()
+            .filter(|(_, l)| l.expires_at <= now)
+            .map(|(s, _)| s.clone())
+            .collect();
+        for state in &expired {
+            self.logins.remove(state);
+        }
+        expired
+    }
+
+    /// 该端口上是否还有等待回调的登录
+    fn awaiting_callback(&self, port: u16) -> bool {
+        self.logins.values().any(|l| l.port == port && l.code.is_none())
+    }
+
+    /// 取出要交换令牌的登录: 优先按 state，其次按回调收到的 code，只有一个登录时直接使用
+    fn take_login(&mut self, code: &str, state: Option<&str>) -> Result<PendingLogin, String> {
+        self.purge_expired(Instant::now());
+
+        let key = match state {
+            Some(state) => {
+                if !self.logins.contains_key(state) {
+                    return Err("State 校验不通过: 该回调地址不属于进行中的登录流程或已过期".to_string());
+                }
+                state.to_string()
+            }
+            None => {
+                let by_code = self.logins.iter()
+                    .find(|(_, l)| l.code.as_deref() == Some(code))
+                    .map(|(s, _)| s.clone());
+                match by_code {
+                    Some(s) => s,
+                    None if self.logins.len() == 1 => self.logins.keys().next().cloned().unwrap_or_default(),
+                    None if self.logins.is_empty() => return Err("登录流程已过期或未启动".to_string()),
+                    None => return Err("存在多个进行中的登录，请粘贴包含 state 的完整回调地址".to_string()),
+                }
+            }
+        };
+
+        self.logins.remove(&key).ok_or_else(|| "登录流程已过期或未启动".to_string())
+    }
+
+    /// 唯一进行中的登录的 state (手动粘贴单独的授权码时使用)
+    fn only_login(&mut self) -> Result<String, String> {
+        self.purge_expired(Instant::now());
+        let mut states = self.logins.keys();
+        match (states.next(), states.next()) {
+            (Some(state), None) => Ok(state.clone()),
+            (None, _) => Err("登录流程已过期或未启动".to_string()),
+            (Some(_), Some(_)) => Err("存在多个进行中的登录，单独的授权码无法确定所属登录，请粘贴包含 state 的完整回调地址".to_string()),
+        }
+    }
+}
+
+/// 单个回调请求头的大小上限
+const MAX_REQUEST_BYTES: usize = 16 * 1024;
+/// 单个连接读取请求的超时 (浏览器预连接可能迟迟不发数据)
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// OAuth 登录进度事件 ("oauth-login-progress")
+#[derive(Debug, Clone, Serialize)]
+pub struct OAuthProgress {
+    /// 所属登录 (手动粘贴时可能未知)
+    pub state: Option<String>,
+    /// listening | browser_opened | callback_received | exchanging | completed | failed | timed_out | cancelled
+    pub stage: String,
+    pub message: Option<String>,
+}
+
+/// 发送登录进度事件
+pub(crate) fn emit_progress(app_handle: &AppHandle, state: Option<&str>, stage: &str, message: Option<String>) {
+    let _ = app_handle.emit("oauth-login-progress", OAuthProgress {
+        state: state.map(str::to_string),
+        stage: stage.to_string(),
+        message,
+    });
+}
+
+/// 浏览器登录已启动
+#[derive(Debug, Clone, Serialize)]
+pub struct OAuthLoginStart {
+    pub state: String,
+    pub auth_url: String,
+    /// 回调端口
+    pub port: u16,
+}
+
+/// 回调服务收到授权码 ("oauth-callback-received")
+#[derive(Debug, Clone, Serialize)]
+struct CallbackReceived {
+    state: String,
+    code: String,
 }
 
 /// 生成与官方一致的 state (Base64 编码的32字节随机数)
@@ -32,113 +157,606 @@ fn generate_state() -> String {
 /// 官方固定端口
 const DEFAULT_PORT: u16 = 1455;
 
+/// 占用端口的进程
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortOwner {
+    pub pid: u32,
+    /// 进程名
+    pub name: String,
+    /// 完整命令行 (获取失败时为空)
+    pub command: Option<String>,
+    /// 是否为 codex 自身 (如正在进行的 `codex login`)
+    pub is_codex: bool,
+}
+
+impl PortOwner {
+    fn describe(&self) -> String {
+        format!("{} (PID {})", self.name, self.pid)
+    }
+}
+
+/// 回调端口检测结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortCheck {
+    pub port: u16,
+    pub available: bool,
+    /// 占用进程 (无法识别时为空)
+    pub owner: Option<PortOwner>,
+}
+
+/// 端口当前是否可以绑定
+fn port_available(port: u16) -> bool {
+    std::net::TcpListener::bind(("127.0.0.1", port)).is_ok()
+}
+
+/// 通过 lsof 查找监听该端口的进程
+fn find_port_owner(port: u16) -> Option<PortOwner> {
+    let output = std::process::Command::new("lsof")
+        .args(["-nP", &format!("-iTCP:{}", port), "-sTCP:LISTEN", "-Fpc"])
+        .output()
+        .ok()?;
+
+    // -F 输出格式: 每行以字段标识开头，p 为 PID，c 为进程名
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut pid = None;
+    let mut name = None;
+    for line in stdout.lines() {
+        if let Some(p) = line.strip_prefix('p') {
+            if pid.is_some() {
+                break;
+            }
+            pid = p.trim().parse::<u32>().ok();
+        } else if let Some(c) = line.strip_prefix('c') {
+            name.get_or_insert_with(|| c.trim().to_string());
+        }
+    }
+    let pid = pid?;
+
+    let command = std::process::Command::new("ps")
+        .args(["-o", "command=", "-p", &pid.to_string()])
+        .output()
+        .ok()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .filter(|c| !c.is_empty());
+    let name = name.unwrap_or_else(|| "未知进程".to_string());
+    let is_codex = name.to_lowercase().contains("codex")
+        || command.as_deref().is_some_and(|c| c.to_lowercase().contains("codex"));
+
+    Some(PortOwner { pid, name, command, is_codex })
+}
+
+/// 检测回调端口是否被占用及占用进程 (前端据此询问用户)
+#[tauri::command]
+pub fn check_oauth_port() -> Result<PortCheck, String> {
+    // 本应用的回调服务占用端口时可直接复用
+    let available = registry().servers.contains(&DEFAULT_PORT) || port_available(DEFAULT_PORT);
+    Ok(PortCheck {
+        port: DEFAULT_PORT,
+        available,
+        owner: if available { None } else { find_port_owner(DEFAULT_PORT) },
+    })
+}
+
+/// 结束占用回调端口的进程 (仅在用户确认后调用)
+///
+/// 只向检测时确认过的 PID 发送 SIGTERM，且发送前再次核对该进程仍在占用端口
+#[tauri::command]
+pub async fn release_oauth_port(pid: u32) -> Result<(), String> {
+    match find_port_owner(DEFAULT_PORT) {
+        Some(owner) if owner.pid == pid => {}
+        Some(owner) => return Err(format!("端口 {} 的占用进程已变为 {}，请重新检测", DEFAULT_PORT, owner.describe())),
+        None if port_available(DEFAULT_PORT) => return Ok(()),
+        None => return Err(format!("无法确认端口 {} 的占用进程，未执行任何操作", DEFAULT_PORT)),
+    }
+
+    let status = std::process::Command::new("kill")
+        .args(["-TERM", &pid.to_string()])
+        .status()
+        .map_err(|e| format!("结束进程失败: {}", e))?;
+    if !status.success() {
+        return Err(format!("结束进程 {} 失败，可能没有权限", pid));
+    }
+
+    // 等待进程退出并释放端口
+    for _ in 0..20 {
+        if port_available(DEFAULT_PORT) {
+            println!("[OAuth] 已结束占用端口 {} 的进程 {}", DEFAULT_PORT, pid);
+            return Ok(());
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+    Err(format!("进程 {} 未在 2 秒内释放端口 {}", pid, DEFAULT_PORT))
+}
+
 /// 准备 OAuth 流程并返回授权 URL
+///
+/// 不会结束任何进程，也不换用其他端口 (redirect_uri 只登记了 1455): 端口被占用时
+/// 报告占用进程并改为手动粘贴回调地址。已有登录在进行时复用其回调服务。
+/// 传入 account_id 时为该账号重新登录，完成后只替换其令牌；传入 workspace 时
+/// 授权页只允许选择该工作区，得到的令牌绑定该工作区
 #[tauri::command]
-pub async fn start_oauth_login(app_handle: AppHandle) -> Result<String, String> {
-    // 1. 强制使用固定端口 1455 (先 kill 占用进程)
-    let _ = std::process::Command::new("sh")
-        .arg("-c")
-        .arg(format!("lsof -ti:{} | xargs kill -9 2>/dev/null", DEFAULT_PORT))
-        .output();
-    
-    // 等待端口释放
-    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-    
-    let listener = TcpListener::bind(format!("127.0.0.1:{}", DEFAULT_PORT)).await
-        .map_err(|e| format!("无法绑定本地端口 {}: {}", DEFAULT_PORT, e))?;
+pub async fn start_oauth_login(
+    app_handle: AppHandle,
+    state: State<'_, crate::AppState>,
+    account_id: Option<String>,
+    workspace: Option<WorkspaceTarget>,
+) -> Result<OAuthLoginStart, String> {
+    let timeout = {
+        let store = state.store.lock().map_err(|e| e.to_string())?;
+        if let Some(id) = &account_id {
+            if !store.accounts.contains_key(id) {
+                return Err(format!("账号 {} 不存在", id));
+            }
+        }
+        Duration::from_secs(store.settings.oauth_timeout_secs.max(30))
+    };
     let port = DEFAULT_PORT;
-    
-    // 2. 生成 PKCE 和 State (与官方一致)
+
+    // 1. 生成 PKCE 和 State (与官方一致)
     let pkce = oauth::generate_pkce();
     let state = generate_state();
-    let redirect_uri = format!("http://localhost:{}/auth/callback", port);
+    let new_login = || PendingLogin {
+        pkce: pkce.clone(),
+        port,
+        expires_at: Instant::now() + timeout,
+        code: None,
+        relogin_account_id: account_id.clone(),
+        workspace: workspace.clone(),
+    };
+
+    // 2. 复用已运行的回调服务 (检查与登记在同一把锁内完成，避免服务恰好退出)
+    let reused = {
+        let mut reg = registry();
+        let running = reg.servers.contains(&port);
+        if running {
+            reg.logins.insert(state.clone(), new_login());
+        }
+        running
+    };
+
+    if !reused {
+        let listener = bind_callback_port(&app_handle).await;
+        let mut reg = registry();
+        reg.logins.insert(state.clone(), new_login());
+        if let Some(listener) = listener {
+            reg.servers.insert(port);
+            let app_handle_clone = app_handle.clone();
+            tokio::spawn(async move {
+                serve_callbacks(listener, port, app_handle_clone).await;
+            });
+        }
+    }
+    emit_progress(&app_handle, Some(&state), "listening", Some(format!("正在等待 http://localhost:{}/auth/callback", port)));
+
+    // 3. 到期后清除该登录 (无论是否有监听端口)
+    let app_handle_clone = app_handle.clone();
+    let state_clone = state.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(timeout).await;
+        if registry().logins.remove(&state_clone).is_some() {
+            println!("[OAuth] 等待回调超时 ({} 秒)", timeout.as_secs());
+            emit_progress(
+                &app_handle_clone,
+                Some(&state_clone),
+                "timed_out",
+                Some(format!("{} 秒内未完成授权，请重新登录", timeout.as_secs())),
+            );
+        }
+    });
     
-    // 3. 构造授权 URL (与官方完全一致: 手动拼接, 不对特殊字符编码)
+    // 4. 构造授权 URL (与官方完全一致: 手动拼接, 不对特殊字符编码)
+    let redirect_uri = format!("http://localhost:{}/auth/callback", port);
+    let endpoints = crate::endpoints::current();
     let qs = format!(
         "response_type=code&client_id={}&redirect_uri={}&scope={}&code_challenge={}&code_challenge_method=S256&id_token_add_organizations=true&codex_cli_simplified_flow=true&state={}&originator=codex_vscode",
-        oauth::CLIENT_ID,
+        endpoints.client_id,
         redirect_uri,
         "openid profile email offline_access",
         pkce.code_challenge,
         state
     );
     
-    let auth_url = format!("{}?{}", oauth::AUTH_URL, qs);
-    
-    // 4. 保存状态，开启监听任务
-    {
-        let mut pending = get_pending_login().lock().unwrap();
-        *pending = Some(PendingLogin { 
-            pkce: pkce.clone(), 
-            state: state.clone(),
-            port 
-        });
+    let mut auth_url = format!("{}?{}", endpoints.auth_url, qs);
+    if let Some(workspace) = &workspace {
+        auth_url.push_str(&format!("&allowed_workspace_id={}", urlencoding::encode(&workspace.id)));
     }
     
-    // 5. 启动异步监听
-    let app_handle_clone = app_handle.clone();
-    tokio::spawn(async move {
-        handle_callback(listener, app_handle_clone, state).await;
-    });
-    
-    // 6. 打开浏览器
-    let _ = app_handle.opener().open_url(&auth_url, None::<String>);
+    // 5. 打开浏览器
+    match app_handle.opener().open_url(&auth_url, None::<String>) {
+        Ok(()) => emit_progress(&app_handle, Some(&state), "browser_opened", None),
+        Err(e) => emit_progress(&app_handle, Some(&state), "browser_opened", Some(format!("无法自动打开浏览器，请手动访问授权地址: {}", e))),
+    }
     
-    Ok(auth_url)
-}
-
-/// 监听回调
-async fn handle_callback(listener: TcpListener, app_handle: AppHandle, expected_state: String) {
-    if let Ok((mut socket, _)) = listener.accept().await {
-        let mut buffer = [0; 4096];
-        if let Ok(n) = socket.read(&mut buffer).await {
-            let request = String::from_utf8_lossy(&buffer[..n]);
-            
-            // 解析 URL
-            let first_line = request.lines().next().unwrap_or("");
-            let parts: Vec<&str> = first_line.split_whitespace().collect();
-            
-            if parts.len() > 1 {
-                let callback_url = format!("http://localhost{}", parts[1]);
-                if let Ok(url) = Url::parse(&callback_url) {
-                    let params: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
-                    
-                    let code = params.get("code");
-                    let state = params.get("state");
-                    
-                    if let (Some(c), Some(s)) = (code, state) {
-                        if s == &expected_state {
-                            // 发送成功 HTML 并通知前端
-                            let response = "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\r\n\
-                                <html><body><h1>授权成功</h1><p>已成功连接 OpenAI，你可以关闭此窗口并回到应用。</p>\
-                                <script>setTimeout(() => window.close(), 3000)</script></body></html>";
-                            let _ = socket.write_all(response.as_bytes()).await;
-                            
-                            // 存储 Code 供后续 finalize_oauth_login 调用
-                            app_handle.emit("oauth-callback-received", c).unwrap();
-                            return;
-                        }
+    Ok(OAuthLoginStart { state, auth_url, port })
+}
+
+/// 绑定回调端口。redirect_uri 只登记了 1455，被占用时不换用其他端口:
+/// 报告占用进程，用户仍可在浏览器授权后手动粘贴回调地址
+async fn bind_callback_port(app_handle: &AppHandle) -> Option<TcpListener> {
+    match TcpListener::bind(format!("127.0.0.1:{}", DEFAULT_PORT)).await {
+        Ok(listener) => Some(listener),
+        Err(e) => {
+            let owner = find_port_owner(DEFAULT_PORT);
+            let msg = format!(
+                "无法绑定本地端口 {} ({}){}，请在授权完成后手动粘贴回调地址",
+                DEFAULT_PORT,
+                e,
+                owner.as_ref().map(|o| format!("，被 {} 占用", o.describe())).unwrap_or_default()
+            );
+            eprintln!("{}", msg);
+            let _ = app_handle.emit("oauth-listener-unavailable", msg);
+            None
+        }
+    }
+}
+
+/// 取消进行中的浏览器登录 (不传 state 时取消全部)
+#[tauri::command]
+pub fn cancel_oauth_login(app_handle: AppHandle, state: Option<String>) -> Result<(), String> {
+    let cancelled: Vec<String> = {
+        let mut reg = registry();
+        match state {
+            Some(state) => reg.logins.remove_entry(&state).map(|(s, _)| s).into_iter().collect(),
+            None => reg.logins.drain().map(|(s, _)| s).collect(),
+        }
+    };
+    if cancelled.is_empty() {
+        return Err("登录流程已结束或不存在".to_string());
+    }
+    for state in &cancelled {
+        emit_progress(&app_handle, Some(state), "cancelled", None);
+    }
+    Ok(())
+}
+
+/// 单个回调请求的处理结果
+#[derive(Debug, PartialEq)]
+enum CallbackRequest {
+    /// 携带授权码的回调
+    Code { state: String, code: String },
+    /// 授权服务器返回的错误 (如用户拒绝授权)
+    OAuthError { state: String, error: String, description: Option<String> },
+    /// 缺少参数
+    Invalid(String),
+    /// 非回调路径 (如 /favicon.ico)
+    NotFound,
+}
+
+/// 解析请求行 (state 由调用方与进行中的登录比对)
+fn classify_request(request: &str) -> CallbackRequest {
+    let first_line = request.lines().next().unwrap_or("");
+    let target = match first_line.split_whitespace().nth(1) {
+        Some(t) => t,
+        None => return CallbackRequest::Invalid("无法解析请求".to_string()),
+    };
+    let url = match Url::parse(&format!("http://localhost{}", target)) {
+        Ok(u) => u,
+        Err(_) => return CallbackRequest::Invalid("无法解析请求地址".to_string()),
+    };
+    if url.path() != "/auth/callback" {
+        return CallbackRequest::NotFound;
+    }
+
+    let params: HashMap<_, _> = url.query_pairs().into_owned().collect();
+    // 错误回调也必须带 state，避免被其他页面结束登录流程
+    let state = match params.get("state").filter(|s| !s.is_empty()) {
+        Some(s) => s.clone(),
+        None => return CallbackRequest::Invalid("回调缺少 state 参数".to_string()),
+    };
+    if let Some(error) = params.get("error") {
+        return CallbackRequest::OAuthError {
+            state,
+            error: error.clone(),
+            description: params.get("error_description").cloned(),
+        };
+    }
+    match params.get("code").filter(|c| !c.is_empty()) {
+        Some(code) => CallbackRequest::Code { state, code: code.clone() },
+        None => CallbackRequest::Invalid("回调缺少 code 参数".to_string()),
+    }
+}
+
+/// 转义 HTML 特殊字符
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// 生成 HTML 响应
+fn html_response(status: &str, title: &str, body: &str) -> String {
+    let html = format!(
+        "<html><head><meta charset=\"utf-8\"><title>{0}</title></head><body><h1>{0}</h1>{1}</body></html>",
+        title, body
+    );
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        html.len(),
+        html
+    )
+}
+
+/// 读取请求头 (直到空行、上限或超时)
+async fn read_request(socket: &mut tokio::net::TcpStream) -> Option<String> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 2048];
+    let read = async {
+        loop {
+            let n = socket.read(&mut chunk).await.ok()?;
+            if n == 0 {
+                break;
+            }
+            buffer.extend_from_slice(&chunk[..n]);
+            if buffer.windows(4).any(|w| w == b"\r\n\r\n") || buffer.len() >= MAX_REQUEST_BYTES {
+                break;
+            }
+        }
+        Some(())
+    };
+    tokio::time::timeout(READ_TIMEOUT, read).await.ok()??;
+    if buffer.is_empty() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&buffer).into_owned())
+}
+
+/// 回调服务: 持续处理请求，直到该端口上没有等待回调的登录
+async fn serve_callbacks(listener: TcpListener, port: u16, app_handle: AppHandle) {
+    let mut tick = tokio::time::interval(Duration::from_secs(1));
+
+    loop {
+        let accepted = tokio::select! {
+            _ = tick.tick() => {
+                // 检查与移除在同一把锁内完成，与 start_oauth_login 的复用判断互斥
+                let mut reg = registry();
+                if !reg.awaiting_callback(port) {
+                    reg.servers.remove(&port);
+                    println!("[OAuth] 端口 {} 上没有等待中的登录，关闭回调服务", port);
+                    return;
+                }
+                continue;
+            }
+            accepted = listener.accept() => accepted,
+        };
+
+        let socket = match accepted {
+            Ok((socket, _)) => socket,
+            Err(e) => {
+                eprintln!("[OAuth] 接受连接失败: {}", e);
+                continue;
+            }
+        };
+
+        // 每个连接单独处理，浏览器的预连接或慢速连接不会阻塞真正的回调
+        tokio::spawn(handle_callback(socket, port, app_handle.clone()));
+    }
+}
+
+/// 处理单个回调连接
+async fn handle_callback(mut socket: tokio::net::TcpStream, port: u16, app_handle: AppHandle) {
+    // 预连接或空请求直接丢弃
+    let Some(request) = read_request(&mut socket).await else {
+        return;
+    };
+
+    let response = match classify_request(&request) {
+        CallbackRequest::Code { state, code } => {
+            let accepted = {
+                let mut reg = registry();
+                match reg.logins.get_mut(&state) {
+                    Some(login) if login.port == port && login.expires_at > Instant::now() => {
+                        login.code = Some(code.clone());
+                        true
                     }
+                    _ => false,
                 }
+            };
+            if accepted {
+                emit_progress(&app_handle, Some(&state), "callback_received", None);
+                // 授权码交由前端调用 finalize_oauth_login 完成交换
+                let _ = app_handle.emit("oauth-callback-received", CallbackReceived { state, code });
+                html_response(
+                    "200 OK",
+                    "授权成功",
+                    "<p>已成功连接 OpenAI，你可以关闭此窗口并回到应用。</p><script>setTimeout(() => window.close(), 3000)</script>",
+                )
+            } else {
+                html_response("400 Bad Request", "授权失败", "<p>State 校验不通过: 登录流程不存在或已过期，请回到应用重新登录。</p>")
             }
         }
-        
-        let response = "HTTP/1.1 400 Bad Request\r\n\r\n授权失败: State 校验不通过或参数缺失";
-        let _ = socket.write_all(response.as_bytes()).await;
-    }
+        CallbackRequest::OAuthError { state, error, description } => {
+            if registry().logins.remove(&state).is_some() {
+                let detail = match &description {
+                    Some(d) => format!("{} ({})", error, d),
+                    None => error.clone(),
+                };
+                emit_progress(&app_handle, Some(&state), "failed", Some(format!("授权失败: {}", detail)));
+                html_response(
+                    "200 OK",
+                    "授权失败",
+                    &format!("<p>OpenAI 返回了错误: <code>{}</code></p><p>请回到应用重新登录。</p>", escape_html(&detail)),
+                )
+            } else {
+                html_response("400 Bad Request", "授权失败", "<p>State 校验不通过: 登录流程不存在或已过期。</p>")
+            }
+        }
+        CallbackRequest::Invalid(reason) => {
+            html_response("400 Bad Request", "授权失败", &format!("<p>{}</p>", escape_html(&reason)))
+        }
+        CallbackRequest::NotFound => {
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+        }
+    };
+    let _ = socket.write_all(response.as_bytes()).await;
 }
 
 /// 最后一步：使用捕获到的 Code 交换 Token (由前端触发)
-#[tauri::command]
-pub async fn complete_oauth_login(code: String) -> Result<oauth::TokenResponse, String> {
-    // 提取所需数据并立即释放锁，避免跨 await 持有 MutexGuard
-    let (code_verifier, port) = {
-        let mut pending_lock = get_pending_login().lock().map_err(|_| "锁被污染")?;
-        let pending = pending_lock.take().ok_or("登录流程已过期或未启动")?;
-        (pending.pkce.code_verifier, pending.port)
+///
+/// 未传 state 时按回调收到的 code 找回对应的登录
+pub async fn complete_oauth_login(code: String, state: Option<String>) -> Result<OAuthResult, String> {
+    exchange_pending(&code, state.as_deref()).await
+}
+
+/// 手动粘贴回调地址完成登录 (端口被占用或浏览器在另一台机器上时使用)
+///
+/// 支持完整的回调 URL、查询字符串或单独的授权码。回调地址必须带 state 且属于进行中的登录；
+/// 单独的授权码只在恰好有一个进行中的登录时接受
+pub async fn complete_oauth_login_manual(input: &str) -> Result<OAuthResult, String> {
+    let (code, state) = parse_redirect_input(input)?;
+    let state = match state {
+        Some(state) => state,
+        None => registry().only_login()?,
     };
+    exchange_pending(&code, Some(&state)).await
+}
+
+/// 使用对应登录的 PKCE verifier 交换 Token
+async fn exchange_pending(code: &str, state: Option<&str>) -> Result<OAuthResult, String> {
+    // 提取所需数据并立即释放锁，避免跨 await 持有 MutexGuard
+    let login = registry().take_login(code, state)?;
+    let redirect_uri = format!("http://localhost:{}/auth/callback", login.port);
     
-    let redirect_uri = format!("http://localhost:{}/auth/callback", port);
-    
-    oauth::exchange_code(&code, &redirect_uri, &code_verifier).await
+    let tokens = oauth::exchange_code(code, &redirect_uri, &login.pkce.code_verifier).await?;
+    Ok(OAuthResult {
+        tokens,
+        relogin_account_id: login.relogin_account_id,
+        workspace: login.workspace,
+    })
+}
+
+/// 解析用户粘贴的内容，返回 (code, state)
+fn parse_redirect_input(input: &str) -> Result<(String, Option<String>), String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("请粘贴回调地址或授权码".to_string());
+    }
+
+    // 单独的授权码
+    if !input.contains('=') && !input.contains('?') {
+        return Ok((input.to_string(), None));
+    }
+
+    // 完整 URL、路径或查询字符串
+    let query = match input.split_once('?') {
+        Some((_, q)) => q,
+        None => input,
+    };
+    let query = query.split('#').next().unwrap_or("");
+    let params: std::collections::HashMap<String, String> = url::form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+
+    if let Some(error) = params.get("error") {
+        let desc = params.get("error_description").map(|d| format!(" ({})", d)).unwrap_or_default();
+        return Err(format!("授权失败: {}{}", error, desc));
+    }
+
+    let code = params.get("code")
+        .filter(|c| !c.is_empty())
+        .ok_or("回调地址中没有 code 参数")?;
+    // 回调地址必须能对应到发起的登录，否则可能是他人构造的授权码
+    let state = params.get("state")
+        .filter(|s| !s.is_empty())
+        .ok_or("回调地址中没有 state 参数，无法确认属于哪个登录")?;
+
+    Ok((code.clone(), Some(state.clone())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_redirect_input() {
+        let (code, state) = parse_redirect_input(
+            "http://localhost:1455/auth/callback?code=ac_123&scope=openid&state=xyz"
+        ).unwrap();
+        assert_eq!(code, "ac_123");
+        assert_eq!(state.as_deref(), Some("xyz"));
+
+        let (code, state) = parse_redirect_input("  ac_456 ").unwrap();
+        assert_eq!(code, "ac_456");
+        assert_eq!(state, None);
+
+        let (code, _) = parse_redirect_input("code=ac%2F789&state=s").unwrap();
+        assert_eq!(code, "ac/789");
+
+        let err = parse_redirect_input("http://localhost:1455/auth/callback?error=access_denied&state=s").unwrap_err();
+        assert!(err.contains("access_denied"));
+        assert!(parse_redirect_input("http://localhost:1455/auth/callback?state=s").is_err());
+
+        // 回调地址缺少 state 时拒绝
+        let err = parse_redirect_input("http://localhost:1455/auth/callback?code=ac_123").unwrap_err();
+        assert!(err.contains("state"));
+        assert!(parse_redirect_input("code=ac_123&state=").is_err());
+    }
+
+    #[test]
+    fn test_classify_request() {
+        let get = |target: &str| format!("GET {} HTTP/1.1\r\nHost: localhost:1455\r\n\r\n", target);
+
+        assert_eq!(
+            classify_request(&get("/auth/callback?code=ac_1&state=s1")),
+            CallbackRequest::Code { state: "s1".to_string(), code: "ac_1".to_string() }
+        );
+        assert_eq!(classify_request(&get("/favicon.ico")), CallbackRequest::NotFound);
+        assert!(matches!(classify_request(&get("/auth/callback?code=ac_1")), CallbackRequest::Invalid(_)));
+        assert_eq!(
+            classify_request(&get("/auth/callback?error=access_denied&error_description=User%20denied&state=s1")),
+            CallbackRequest::OAuthError {
+                state: "s1".to_string(),
+                error: "access_denied".to_string(),
+                description: Some("User denied".to_string()),
+            }
+        );
+        assert!(matches!(classify_request(""), CallbackRequest::Invalid(_)));
+        assert_eq!(escape_html("<b>\"x\"</b>"), "&lt;b&gt;&quot;x&quot;&lt;/b&gt;");
+    }
+
+    #[test]
+    fn test_take_login_with_concurrent_flows() {
+        let login = |port: u16, code: Option<&str>, ttl: Duration| PendingLogin {
+            pkce: oauth::generate_pkce(),
+            port,
+            expires_at: Instant::now() + ttl,
+            code: code.map(str::to_string),
+            relogin_account_id: None,
+            workspace: None,
+        };
+        let ttl = Duration::from_secs(60);
+
+        let mut reg = Registry::default();
+        reg.logins.insert("a".to_string(), login(1455, None, ttl));
+        reg.logins.insert("b".to_string(), login(1455, Some("code_b"), ttl));
+        reg.logins.insert("c".to_string(), login(1455, None, ttl));
+
+        // 按 state 或回调收到的 code 找回，互不覆盖
+        assert!(reg.take_login("code_a", Some("a")).is_ok());
+        assert!(reg.take_login("code_b", None).is_ok());
+        assert!(reg.take_login("code_a", Some("a")).is_err());
+        assert!(reg.take_login("x", Some("unknown")).is_err());
+
+        // 只剩一个登录时允许只凭 code 完成
+        assert!(reg.take_login("code_c", None).is_ok());
+        assert!(reg.take_login("code_c", None).is_err());
+
+        // 单独的授权码只在恰好一个登录时可用
+        assert!(reg.only_login().is_err());
+        reg.logins.insert("d".to_string(), login(1455, None, ttl));
+        assert_eq!(reg.only_login().unwrap(), "d");
+
+        // 多个登录且无法定位时拒绝
+        reg.logins.insert("e".to_string(), login(1457, None, ttl));
+        assert!(matches!(reg.take_login("code", None), Err(e) if e.contains("多个")));
+        assert!(matches!(reg.only_login(), Err(e) if e.contains("多个")));
+
+        // 过期的登录被清除
+        reg.logins.insert("f".to_string(), login(1455, None, Duration::ZERO));
+        assert_eq!(reg.purge_expired(Instant::now()), vec!["f".to_string()]);
+        assert!(reg.awaiting_callback(1457));
+        assert!(!reg.awaiting_callback(1460));
+    }
 }
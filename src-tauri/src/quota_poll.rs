@@ -0,0 +1,329 @@
+//! Codex Switcher - 后台配额轮询
+//!
+//! 当前账号按任务间隔轮询；闲置账号放慢到 IDLE_INTERVAL_MINUTES，且所有窗口
+//! 距离重置都很远时暂停到重置时刻 (闲置账号的剩余额度通常只在重置时变化)。
+//! 每轮结束后以 quota-updated 事件发送各账号配额的变化
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tauri::Emitter;
+
+use crate::account::CachedQuota;
+use crate::connectivity;
+use crate::scheduler::{JobContext, JobReport};
+
+/// 闲置账号的轮询间隔
+const IDLE_INTERVAL_MINUTES: i64 = 60;
+/// 闲置账号最近一次重置在该时长之后时暂停轮询
+const PAUSE_BEYOND_RESET_MINUTES: i64 = 180;
+
+/// 账号本轮的轮询安排
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "status", content = "until")]
+pub enum Cadence {
+    /// 本轮轮询
+    Due,
+    /// 未到间隔
+    Wait(DateTime<Utc>),
+    /// 闲置且远离重置，暂停到重置时刻
+    Paused(DateTime<Utc>),
+}
+
+/// 缓存中所有窗口的重置时间
+fn reset_times(quota: &CachedQuota) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+    quota.windows.iter()
+        .map(|w| w.reset_at)
+        .chain([quota.five_hour_reset_at, quota.weekly_reset_at])
+        .flatten()
+        .filter_map(|ts| DateTime::from_timestamp(ts, 0))
+}
+
+/// 决定账号本轮是否需要轮询
+///
+/// 缓存时间 updated_at 为本机时间，与 `now` 比较；重置时间为服务端时间，与校正后的
+/// `server_now` 比较。返回的时刻均为本机时间
+pub fn cadence(
+    quota: Option<&CachedQuota>,
+    is_current: bool,
+    interval_minutes: u32,
+    now: DateTime<Utc>,
+    server_now: DateTime<Utc>,
+) -> Cadence {
+    let Some(quota) = quota else {
+        return Cadence::Due;
+    };
+    // 系统时间回拨后缓存时间可能在未来
+    let updated_at = quota.updated_at.min(now);
+    let skew = server_now - now;
+
+    if !is_current {
+        // 上次轮询之后已有窗口重置: 立即更新
+        let polled_at = updated_at + skew;
+        if reset_times(quota).any(|t| t > polled_at && t <= server_now) {
+            return Cadence::Due;
+        }
+        if let Some(next_reset) = reset_times(quota).filter(|t| *t > server_now).min() {
+            if next_reset - server_now > chrono::Duration::minutes(PAUSE_BEYOND_RESET_MINUTES) {
+                return Cadence::Paused(next_reset - skew);
+            }
+        }
+    }
+
+    let interval = if is_current {
+        interval_minutes as i64
+    } else {
+        IDLE_INTERVAL_MINUTES.max(interval_minutes as i64)
+    };
+    let next = updated_at + chrono::Duration::minutes(interval);
+    if next <= now {
+        Cadence::Due
+    } else {
+        Cadence::Wait(next)
+    }
+}
+
+/// 单个窗口的变化
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WindowChange {
+    pub bucket: String,
+    pub name: String,
+    /// 剩余百分比 (新增窗口时 before 为空，消失的窗口 after 为空)
+    pub left_before: Option<i32>,
+    pub left_after: Option<i32>,
+    pub reset_at_before: Option<i64>,
+    pub reset_at_after: Option<i64>,
+}
+
+/// 账号配额的变化
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaDiff {
+    pub account_id: String,
+    pub account_name: String,
+    /// 首次获取到配额
+    pub first_poll: bool,
+    pub windows: Vec<WindowChange>,
+    pub plan_type_before: Option<String>,
+    pub plan_type_after: String,
+    pub valid_for_cli_before: Option<bool>,
+    pub valid_for_cli_after: bool,
+}
+
+/// 对比两次配额，返回变化的窗口 (没有变化时为空)
+pub fn diff_windows(before: Option<&CachedQuota>, after: &CachedQuota) -> Vec<WindowChange> {
+    let before_windows = before.map(|q| q.windows.as_slice()).unwrap_or_default();
+    let find = |windows: &[crate::usage::RateWindow], bucket: &str, name: &str| {
+        windows.iter()
+            .find(|w| w.bucket == bucket && w.name == name)
+            .map(|w| (w.left_percent, w.reset_at))
+    };
+
+    let mut changes: Vec<WindowChange> = after.windows.iter()
+        .map(|w| (w.bucket.as_str(), w.name.as_str()))
+        .chain(before_windows.iter().map(|w| (w.bucket.as_str(), w.name.as_str())))
+        .fold(Vec::new(), |mut keys, key| {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+            keys
+        })
+        .into_iter()
+        .filter_map(|(bucket, name)| {
+            let old = find(before_windows, bucket, name);
+            let new = find(&after.windows, bucket, name);
+            (old != new).then(|| WindowChange {
+                bucket: bucket.to_string(),
+                name: name.to_string(),
+                left_before: old.map(|(left, _)| left),
+                left_after: new.map(|(left, _)| left),
+                reset_at_before: old.and_then(|(_, reset)| reset),
+                reset_at_after: new.and_then(|(_, reset)| reset),
+            })
+        })
+        .collect();
+    changes.sort_by(|a, b| (&a.bucket, &a.name).cmp(&(&b.bucket, &b.name)));
+    changes
+}
+
+fn diff(account_id: &str, account_name: &str, before: Option<&CachedQuota>, after: &CachedQuota) -> Option<QuotaDiff> {
+    let windows = diff_windows(before, after);
+    let plan_changed = before.map(|q| q.plan_type != after.plan_type).unwrap_or(true);
+    let valid_changed = before.map(|q| q.is_valid_for_cli != after.is_valid_for_cli).unwrap_or(true);
+    if windows.is_empty() && !plan_changed && !valid_changed {
+        return None;
+    }
+    Some(QuotaDiff {
+        account_id: account_id.to_string(),
+        account_name: account_name.to_string(),
+        first_poll: before.is_none(),
+        windows,
+        plan_type_before: before.map(|q| q.plan_type.clone()),
+        plan_type_after: after.plan_type.clone(),
+        valid_for_cli_before: before.map(|q| q.is_valid_for_cli),
+        valid_for_cli_after: after.is_valid_for_cli,
+    })
+}
+
+/// 单个账号的轮询结果
+#[derive(Debug, Clone, Serialize)]
+pub struct PollResult {
+    pub account_id: String,
+    pub account_name: String,
+    pub cadence: Cadence,
+    pub changed: bool,
+    pub error: Option<String>,
+}
+
+/// 轮询到期账号的用量，更新配额缓存并发送变化
+pub async fn poll(ctx: JobContext) -> Result<JobReport, String> {
+    if !connectivity::is_online() {
+        return Ok(JobReport { summary: "离线，跳过".to_string(), details: serde_json::Value::Null });
+    }
+
+    // 缓存时间为本机时间，重置时间来自服务端，分别比较
+    let now = Utc::now();
+    let server_now = crate::clock::now();
+    let accounts: Vec<_> = {
+        let store = ctx.store.lock().map_err(|e| e.to_string())?;
+        store.list_accounts().into_iter()
+            .map(|a| {
+                let is_current = store.current.as_deref() == Some(a.id.as_str());
+                (a.id.clone(), a.name.clone(), is_current, a.cached_quota.clone())
+            })
+            .collect()
+    };
+
+    let mut results = Vec::new();
+    let mut diffs = Vec::new();
+    for (account_id, account_name, is_current, before) in accounts {
+        let cadence = cadence(before.as_ref(), is_current, ctx.settings.interval_minutes, now, server_now);
+        let mut result = PollResult {
+            account_id: account_id.clone(),
+            account_name: account_name.clone(),
+            cadence: cadence.clone(),
+            changed: false,
+            error: None,
+        };
+        if cadence == Cadence::Due {
+            match crate::get_quota_inner(&ctx.store, &account_id).await {
+                Ok(_) => {
+                    let after = ctx.store.lock().map_err(|e| e.to_string())?
+                        .accounts.get(&account_id)
+                        .and_then(|a| a.cached_quota.clone());
+                    if let Some(d) = after.and_then(|after| diff(&account_id, &account_name, before.as_ref(), &after)) {
+                        result.changed = true;
+                        diffs.push(d);
+                    }
+                }
+                Err(e) => result.error = Some(e),
+            }
+        }
+        results.push(result);
+    }
+
+    if !diffs.is_empty() {
+        let _ = ctx.app_handle.emit("quota-updated", &diffs);
+    }
+
+    let polled = results.iter().filter(|r| r.cadence == Cadence::Due).count();
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    Ok(JobReport {
+        summary: format!("轮询 {} 个账号，{} 个有变化，失败 {} 个", polled, diffs.len(), failed),
+        details: serde_json::to_value(&results).unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usage::RateWindow;
+
+    fn window(name: &str, left: i32, reset_at: i64) -> RateWindow {
+        RateWindow {
+            bucket: "codex".to_string(),
+            name: name.to_string(),
+            window_seconds: None,
+            used_percent: 100 - left,
+            left_percent: left,
+            reset: String::new(),
+            reset_at: Some(reset_at),
+        }
+    }
+
+    fn quota(updated_at: DateTime<Utc>, windows: Vec<RateWindow>) -> CachedQuota {
+        CachedQuota {
+            five_hour_left: 0.0,
+            five_hour_reset: String::new(),
+            five_hour_reset_at: None,
+            weekly_left: 0.0,
+            weekly_reset: String::new(),
+            weekly_reset_at: None,
+            plan_type: "plus".to_string(),
+            is_valid_for_cli: true,
+            windows,
+            updated_at,
+        }
+    }
+
+    #[test]
+    fn test_cadence_by_activity_and_reset_distance() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let ts = |minutes: i64| (now + chrono::Duration::minutes(minutes)).timestamp();
+        let polled = |minutes_ago: i64| now - chrono::Duration::minutes(minutes_ago);
+
+        assert_eq!(cadence(None, false, 5, now, now), Cadence::Due);
+
+        // 当前账号按任务间隔
+        let q = quota(polled(3), vec![window("5h", 50, ts(600))]);
+        assert_eq!(cadence(Some(&q), true, 5, now, now), Cadence::Wait(polled(3) + chrono::Duration::minutes(5)));
+        let q = quota(polled(6), vec![window("5h", 50, ts(600))]);
+        assert_eq!(cadence(Some(&q), true, 5, now, now), Cadence::Due);
+
+        // 闲置账号远离重置: 暂停到重置时刻
+        assert_eq!(cadence(Some(&q), false, 5, now, now), Cadence::Paused(DateTime::from_timestamp(ts(600), 0).unwrap()));
+
+        // 闲置账号接近重置: 按闲置间隔
+        let q = quota(polled(30), vec![window("5h", 50, ts(60))]);
+        assert_eq!(cadence(Some(&q), false, 5, now, now), Cadence::Wait(polled(30) + chrono::Duration::minutes(IDLE_INTERVAL_MINUTES)));
+
+        // 上次轮询之后窗口已重置
+        let q = quota(polled(30), vec![window("5h", 0, ts(-10)), window("weekly", 20, ts(5000))]);
+        assert_eq!(cadence(Some(&q), false, 5, now, now), Cadence::Due);
+    }
+
+    #[test]
+    fn test_cadence_with_clock_skew() {
+        // 本机时间比服务端慢 20 分钟
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let server_now = now + chrono::Duration::minutes(20);
+        let reset = |minutes: i64| (server_now + chrono::Duration::minutes(minutes)).timestamp();
+
+        // 刚轮询过 (本机时间)，当前账号按间隔等待而不是立即轮询
+        let q = quota(now, vec![window("5h", 50, reset(600))]);
+        assert_eq!(cadence(Some(&q), true, 5, now, server_now), Cadence::Wait(now + chrono::Duration::minutes(5)));
+
+        // 暂停到重置时刻，换算为本机时间
+        assert_eq!(cadence(Some(&q), false, 5, now, server_now), Cadence::Paused(now + chrono::Duration::minutes(600)));
+
+        // 服务端时间已过重置点才算重置
+        let q = quota(now - chrono::Duration::minutes(30), vec![window("5h", 0, reset(-10)), window("weekly", 20, reset(5000))]);
+        assert_eq!(cadence(Some(&q), false, 5, now, server_now), Cadence::Due);
+        let q = quota(now - chrono::Duration::minutes(30), vec![window("5h", 0, reset(10)), window("weekly", 20, reset(5000))]);
+        assert_eq!(cadence(Some(&q), false, 5, now, server_now), Cadence::Wait(now + chrono::Duration::minutes(30)));
+    }
+
+    #[test]
+    fn test_diff_windows_reports_changed_added_and_removed() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let before = quota(now, vec![window("5h", 80, 100), window("weekly", 50, 200)]);
+        let after = quota(now, vec![window("5h", 60, 100), window("weekly", 50, 200), window("review", 90, 300)]);
+
+        let changes = diff_windows(Some(&before), &after);
+        assert_eq!(changes.len(), 2);
+        assert_eq!((changes[0].name.as_str(), changes[0].left_before, changes[0].left_after), ("5h", Some(80), Some(60)));
+        assert_eq!((changes[1].name.as_str(), changes[1].left_before, changes[1].left_after), ("review", None, Some(90)));
+
+        let removed = diff_windows(Some(&after), &before);
+        assert!(removed.iter().any(|c| c.name == "review" && c.left_after.is_none()));
+        assert!(diff_windows(Some(&before), &before).is_empty());
+    }
+}
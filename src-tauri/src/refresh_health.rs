@@ -0,0 +1,188 @@
+//! Codex Switcher - 账号刷新健康状态
+//!
+//! 记录每个账号连续刷新失败的次数、原因及首次失败时间。refresh_token 连续
+//! RELOGIN_AFTER_REVOKED 次被服务端拒绝后标记为需要重新登录: 后台刷新不再尝试，
+//! 定时切换等自动切换也会跳过该账号。刷新成功或重新登录后清除
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::broadcast;
+
+use crate::account::{Account, AccountStore};
+
+/// 连续被拒绝多少次后需要重新登录
+pub const RELOGIN_AFTER_REVOKED: u32 = 2;
+
+const CHANNEL_CAPACITY: usize = 64;
+
+/// 刷新失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefreshErrorClass {
+    /// refresh_token 被拒绝 (已撤销、已被轮换或过期)
+    Revoked,
+    /// 网络不可达或熔断
+    Network,
+    /// 服务端错误或响应无法解析
+    Server,
+}
+
+/// 账号的刷新健康状态 (刷新正常时为空)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RefreshHealth {
+    pub consecutive_failures: u32,
+    /// 其中被服务端拒绝的次数
+    pub revoked_failures: u32,
+    pub last_error_class: RefreshErrorClass,
+    pub last_error: String,
+    pub first_failed_at: DateTime<Utc>,
+    pub last_failed_at: DateTime<Utc>,
+    pub needs_relogin: bool,
+}
+
+/// 健康状态变化事件
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthChange {
+    pub account_id: String,
+    pub account_name: String,
+    /// 为空表示已恢复
+    pub health: Option<RefreshHealth>,
+}
+
+static CHANNEL: OnceLock<broadcast::Sender<HealthChange>> = OnceLock::new();
+
+fn channel() -> &'static broadcast::Sender<HealthChange> {
+    CHANNEL.get_or_init(|| broadcast::channel(CHANNEL_CAPACITY).0)
+}
+
+/// 订阅健康状态变化
+pub fn subscribe() -> broadcast::Receiver<HealthChange> {
+    channel().subscribe()
+}
+
+fn broadcast(account: &Account) {
+    let _ = channel().send(HealthChange {
+        account_id: account.id.clone(),
+        account_name: account.name.clone(),
+        health: account.refresh_health.clone(),
+    });
+}
+
+/// 按错误信息判断失败原因
+pub fn classify(error: &str) -> RefreshErrorClass {
+    if let Some((_, rest)) = error.split_once("刷新令牌被拒绝 (HTTP ") {
+        let status: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+        return match status.parse::<u16>() {
+            Ok(400 | 401 | 403) => RefreshErrorClass::Revoked,
+            _ => RefreshErrorClass::Server,
+        };
+    }
+    if error.contains("网络请求失败") || error.contains("CIRCUIT_OPEN") {
+        RefreshErrorClass::Network
+    } else {
+        RefreshErrorClass::Server
+    }
+}
+
+/// 账号是否需要重新登录
+pub fn needs_relogin(account: &Account) -> bool {
+    account.refresh_health.as_ref().is_some_and(|h| h.needs_relogin)
+}
+
+/// 在健康状态上记录一次失败
+pub fn apply_failure(health: Option<RefreshHealth>, class: RefreshErrorClass, error: &str, now: DateTime<Utc>) -> RefreshHealth {
+    let mut health = health.unwrap_or(RefreshHealth {
+        consecutive_failures: 0,
+        revoked_failures: 0,
+        last_error_class: class,
+        last_error: String::new(),
+        first_failed_at: now,
+        last_failed_at: now,
+        needs_relogin: false,
+    });
+    health.consecutive_failures += 1;
+    if class == RefreshErrorClass::Revoked {
+        health.revoked_failures += 1;
+    }
+    health.last_error_class = class;
+    health.last_error = error.to_string();
+    health.last_failed_at = now;
+    health.needs_relogin = health.needs_relogin || health.revoked_failures >= RELOGIN_AFTER_REVOKED;
+    health
+}
+
+/// 记录刷新失败: 账号本身及共用同一 refresh_token 的工作区条目
+pub fn record_failure(store: &mut AccountStore, id: &str, refresh_token: &str, error: &str) {
+    let class = classify(error);
+    let now = Utc::now();
+    for account in store.accounts.values_mut() {
+        if account.id != id && account.refresh_token.as_deref() != Some(refresh_token) {
+            continue;
+        }
+        let was_flagged = needs_relogin(account);
+        account.refresh_health = Some(apply_failure(account.refresh_health.take(), class, error, now));
+        if !was_flagged && needs_relogin(account) {
+            println!("[Health] ⚠️ 账号 {} 的 refresh_token 已连续被拒绝，需要重新登录", account.name);
+        }
+        broadcast(account);
+    }
+}
+
+/// 已获得有效令牌: 清除健康状态
+pub fn clear(account: &mut Account) {
+    if account.refresh_health.take().is_some() {
+        println!("[Health] 账号 {} 刷新已恢复", account.name);
+        broadcast(account);
+    }
+}
+
+/// 将健康状态变化转发给前端 (account-health-changed 事件)
+pub fn forward_events(app_handle: AppHandle) {
+    let mut rx = subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(change) => {
+                    let _ = app_handle.emit("account-health-changed", change);
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_refresh_errors() {
+        assert_eq!(classify("刷新令牌被拒绝 (HTTP 400): {\"error\":\"invalid_grant\"}"), RefreshErrorClass::Revoked);
+        assert_eq!(classify("刷新令牌被拒绝 (HTTP 401): "), RefreshErrorClass::Revoked);
+        assert_eq!(classify("刷新令牌被拒绝 (HTTP 503): "), RefreshErrorClass::Server);
+        assert_eq!(classify("刷新令牌失败: 网络请求失败: connection refused"), RefreshErrorClass::Network);
+        assert_eq!(classify("刷新令牌失败: CIRCUIT_OPEN:auth.openai.com 连续请求失败"), RefreshErrorClass::Network);
+        assert_eq!(classify("解析刷新响应失败: EOF"), RefreshErrorClass::Server);
+    }
+
+    #[test]
+    fn test_needs_relogin_after_repeated_revocations() {
+        let first = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let later = first + chrono::Duration::minutes(30);
+
+        let health = apply_failure(None, RefreshErrorClass::Network, "offline", first);
+        let health = apply_failure(Some(health), RefreshErrorClass::Revoked, "invalid_grant", later);
+        assert_eq!((health.consecutive_failures, health.revoked_failures, health.needs_relogin), (2, 1, false));
+        assert_eq!((health.first_failed_at, health.last_failed_at), (first, later));
+
+        let health = apply_failure(Some(health), RefreshErrorClass::Revoked, "invalid_grant", later);
+        assert!(health.needs_relogin);
+        // 之后的网络错误不会撤销标记
+        let health = apply_failure(Some(health), RefreshErrorClass::Network, "offline", later);
+        assert!(health.needs_relogin);
+        assert_eq!(health.last_error_class, RefreshErrorClass::Network);
+    }
+}
@@ -0,0 +1,335 @@
+//! Codex Switcher - 请求重试与熔断
+//!
+//! 对 OpenAI 的请求统一做指数退避重试 (带抖动，429 时遵循 Retry-After)，
+//! 并按主机维护熔断器，连续失败后暂停请求一段时间
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// 重试策略
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// 最大尝试次数 (含首次)
+    pub max_attempts: u32,
+    /// 退避基数
+    pub base_delay: Duration,
+    /// 单次等待上限
+    pub max_delay: Duration,
+    /// 请求可安全重放。为 false 时只重试确定未送达服务端的连接错误，以及服务端
+    /// 以 429/503 + Retry-After 明确要求重试的响应
+    pub replayable: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            replayable: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 只尝试一次 (用于不可重放的请求，如授权码交换)，仍参与熔断统计
+    pub fn once() -> Self {
+        Self { max_attempts: 1, ..Self::default() }
+    }
+
+    /// 不可重放的请求 (如 refresh_token 授权: 服务端已使用令牌但响应丢失时，重发的是已轮换掉的令牌)
+    pub fn single_use() -> Self {
+        Self { replayable: false, ..Self::default() }
+    }
+
+    /// 第 n 次失败后的等待时间 (full jitter: 0 ~ base * 2^n)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let cap = self.base_delay
+            .saturating_mul(1u32 << attempt.min(16))
+            .min(self.max_delay);
+        let millis = cap.as_millis() as u64;
+        if millis == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_millis(rand::rng().random_range(0..=millis))
+    }
+}
+
+/// 连续失败多少次后熔断
+const FAILURE_THRESHOLD: u32 = 5;
+/// 熔断持续时间
+const OPEN_DURATION: Duration = Duration::from_secs(60);
+
+/// 熔断器状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// 正常放行
+    Closed,
+    /// 熔断中，直接拒绝请求
+    Open,
+    /// 冷却结束，放行一次试探请求
+    HalfOpen,
+}
+
+/// 单个主机的熔断器
+#[derive(Debug, Clone)]
+struct Breaker {
+    state: BreakerState,
+    consecutive_failures: u32,
+    total_failures: u64,
+    opened_at: Option<Instant>,
+    last_error: Option<String>,
+    last_failure_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Breaker {
+    fn new() -> Self {
+        Self {
+            state: BreakerState::Closed,
+            consecutive_failures: 0,
+            total_failures: 0,
+            opened_at: None,
+            last_error: None,
+            last_failure_at: None,
+        }
+    }
+
+    /// 是否允许发出请求，返回 Err(剩余熔断时间)
+    fn try_acquire(&mut self, now: Instant) -> Result<(), Duration> {
+        match self.state {
+            BreakerState::Closed => Ok(()),
+            // 半开状态下只放行一次试探请求，其余请求在试探结束前直接拒绝；
+            // 试探请求迟迟不返回时，冷却时间过后再放行一次
+            BreakerState::Open | BreakerState::HalfOpen => {
+                let elapsed = self.opened_at.map(|t| now.duration_since(t)).unwrap_or(OPEN_DURATION);
+                if elapsed >= OPEN_DURATION {
+                    self.state = BreakerState::HalfOpen;
+                    self.opened_at = Some(now);
+                    Ok(())
+                } else {
+                    Err(OPEN_DURATION - elapsed)
+                }
+            }
+        }
+    }
+
+    fn on_success(&mut self) {
+        self.state = BreakerState::Closed;
+        self.consecutive_failures = 0;
+        self.opened_at = None;
+    }
+
+    fn on_failure(&mut self, error: String, now: Instant) {
+        self.consecutive_failures += 1;
+        self.total_failures += 1;
+        self.last_error = Some(error);
+        self.last_failure_at = Some(chrono::Utc::now());
+
+        if self.state == BreakerState::HalfOpen || self.consecutive_failures >= FAILURE_THRESHOLD {
+            self.state = BreakerState::Open;
+            self.opened_at = Some(now);
+        }
+    }
+}
+
+static BREAKERS: OnceLock<Mutex<HashMap<String, Breaker>>> = OnceLock::new();
+
+fn breakers() -> &'static Mutex<HashMap<String, Breaker>> {
+    BREAKERS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn with_breaker<T>(host: &str, f: impl FnOnce(&mut Breaker) -> T) -> T {
+    let mut map = breakers().lock().unwrap_or_else(|e| e.into_inner());
+    f(map.entry(host.to_string()).or_insert_with(Breaker::new))
+}
+
+/// 熔断器诊断信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BreakerStatus {
+    pub host: String,
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+    pub total_failures: u64,
+    /// 熔断剩余秒数
+    pub retry_in_secs: Option<u64>,
+    pub last_error: Option<String>,
+    pub last_failure_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// 获取所有主机的熔断器状态
+pub fn breaker_statuses() -> Vec<BreakerStatus> {
+    let map = breakers().lock().unwrap_or_else(|e| e.into_inner());
+    let now = Instant::now();
+    let mut list: Vec<_> = map.iter().map(|(host, b)| BreakerStatus {
+        host: host.clone(),
+        state: b.state,
+        consecutive_failures: b.consecutive_failures,
+        total_failures: b.total_failures,
+        retry_in_secs: match (b.state, b.opened_at) {
+            (BreakerState::Open, Some(t)) => Some(OPEN_DURATION.saturating_sub(now.duration_since(t)).as_secs()),
+            _ => None,
+        },
+        last_error: b.last_error.clone(),
+        last_failure_at: b.last_failure_at,
+    }).collect();
+    list.sort_by(|a, b| a.host.cmp(&b.host));
+    list
+}
+
+/// 按主机 (含端口) 区分熔断器
+fn host_key(url: &reqwest::Url) -> String {
+    match (url.host_str(), url.port()) {
+        (Some(h), Some(p)) => format!("{}:{}", h, p),
+        (Some(h), None) => h.to_string(),
+        _ => url.as_str().to_string(),
+    }
+}
+
+/// 解析 Retry-After (秒数或 HTTP 日期)
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let secs = (date.with_timezone(&chrono::Utc) - chrono::Utc::now()).num_seconds();
+    Some(Duration::from_secs(secs.max(0) as u64))
+}
+
+/// 是否属于可重试的状态码
+fn is_retryable(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// 按重试策略发送请求
+///
+/// `build` 每次调用都需构造一个新的请求。网络错误、5xx 和 429 会被重试并计入熔断，
+/// 其余响应 (包括 4xx) 原样返回，由调用方判断业务结果。不可重放的请求见 `RetryPolicy::replayable`
+pub async fn send_with_retry<F>(policy: RetryPolicy, build: F) -> Result<reqwest::Response, String>
+where
+    F: Fn() -> reqwest::RequestBuilder,
+{
+    let request = build().build().map_err(|e| format!("构造请求失败: {}", e))?;
+    let host = host_key(request.url());
+
+    let mut attempt = 0;
+    loop {
+        if let Err(remaining) = with_breaker(&host, |b| b.try_acquire(Instant::now())) {
+            return Err(format!(
+                "CIRCUIT_OPEN:{} 连续请求失败，已暂停访问，{} 秒后再试",
+                host,
+                remaining.as_secs().max(1)
+            ));
+        }
+
+        attempt += 1;
+        let result = build().send().await;
+        match &result {
+            Ok(response) => {
+                crate::connectivity::report_reachable();
+                crate::clock::observe_response(response.headers());
+            }
+            Err(e) if crate::connectivity::is_network_error(e) => crate::connectivity::report_unreachable(),
+            Err(_) => {}
+        }
+
+        let (error, wait) = match result {
+            Ok(response) if !is_retryable(response.status()) => {
+                with_breaker(&host, |b| b.on_success());
+                return Ok(response);
+            }
+            Ok(response) => {
+                let status = response.status();
+                let retry_after = response.headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(parse_retry_after);
+                with_breaker(&host, |b| b.on_failure(format!("HTTP {}", status), Instant::now()));
+
+                // 不可重放的请求只在服务端明确要求时重试
+                let asked_to_retry = retry_after.is_some()
+                    && (status == reqwest::StatusCode::TOO_MANY_REQUESTS || status == reqwest::StatusCode::SERVICE_UNAVAILABLE);
+                if attempt >= policy.max_attempts || (!policy.replayable && !asked_to_retry) {
+                    return Ok(response);
+                }
+                let wait = match retry_after {
+                    Some(d) if asked_to_retry => d.min(policy.max_delay),
+                    _ => policy.backoff(attempt - 1),
+                };
+                (format!("HTTP {}", status), wait)
+            }
+            Err(e) => {
+                let error = e.to_string();
+                with_breaker(&host, |b| b.on_failure(error.clone(), Instant::now()));
+
+                // 连接未建立时请求一定没有送达；超时、连接中断则可能已被服务端处理
+                if attempt >= policy.max_attempts || (!policy.replayable && !e.is_connect()) {
+                    return Err(format!("网络请求失败: {}", error));
+                }
+                (error, policy.backoff(attempt - 1))
+            }
+        };
+
+        println!(
+            "[Retry] {} 第 {} 次请求失败 ({})，{} ms 后重试",
+            host, attempt, error, wait.as_millis()
+        );
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// 网络诊断信息 (熔断器状态)
+#[tauri::command]
+pub fn get_network_diagnostics() -> Vec<BreakerStatus> {
+    breaker_statuses()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = RetryPolicy::default();
+        for attempt in 0..20 {
+            assert!(policy.backoff(attempt) <= policy.max_delay);
+        }
+        assert!(policy.backoff(0) <= policy.base_delay);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("7"), Some(Duration::from_secs(7)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn test_breaker_opens_and_half_opens() {
+        let mut b = Breaker::new();
+        let start = Instant::now();
+        for _ in 0..FAILURE_THRESHOLD {
+            assert!(b.try_acquire(start).is_ok());
+            b.on_failure("boom".to_string(), start);
+        }
+        assert_eq!(b.state, BreakerState::Open);
+        assert!(b.try_acquire(start).is_err());
+
+        // 冷却结束后放行一次试探，失败则重新熔断
+        let later = start + OPEN_DURATION;
+        assert!(b.try_acquire(later).is_ok());
+        assert_eq!(b.state, BreakerState::HalfOpen);
+        b.on_failure("still down".to_string(), later);
+        assert_eq!(b.state, BreakerState::Open);
+
+        let much_later = later + OPEN_DURATION;
+        assert!(b.try_acquire(much_later).is_ok());
+        b.on_success();
+        assert_eq!(b.state, BreakerState::Closed);
+        assert_eq!(b.consecutive_failures, 0);
+    }
+}
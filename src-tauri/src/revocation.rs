@@ -0,0 +1,166 @@
+//! Codex Switcher - 删除账号时撤销令牌
+//!
+//! 删除前可选地调用 OAuth 撤销端点使 refresh_token / access_token 在服务端失效，
+//! 结果记录在存储中。离线或撤销失败都不影响本地删除
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+use tauri::State;
+
+use crate::account::{AccountStore, RevocationRecord, RevokeOutcome};
+use crate::{connectivity, oauth, AppState};
+
+/// 撤销记录最多保留条数
+const MAX_RECORDS: usize = 100;
+
+/// 撤销单个令牌，仍被保留的账号使用的令牌不撤销
+async fn revoke(token: Option<String>, hint: &str, shared: &HashSet<String>) -> RevokeOutcome {
+    let token = match token.filter(|t| !t.is_empty()) {
+        Some(t) => t,
+        None => return RevokeOutcome::NoToken,
+    };
+    if shared.contains(&token) {
+        return RevokeOutcome::Shared;
+    }
+    if !connectivity::is_online() {
+        return RevokeOutcome::Offline;
+    }
+    match oauth::revoke_token(&token, hint).await {
+        Ok(()) => RevokeOutcome::Revoked,
+        // 请求层在连接失败时会切换到离线状态
+        Err(_) if !connectivity::is_online() => RevokeOutcome::Offline,
+        Err(e) => RevokeOutcome::Failed(e),
+    }
+}
+
+/// 账号的 refresh_token 与 access_token
+fn account_tokens(account: &crate::account::Account) -> (Option<String>, Option<String>) {
+    let tokens = account.auth_json.get("tokens");
+    let field = |key: &str| {
+        tokens
+            .and_then(|t| t.get(key))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+    };
+    (account.refresh_token.clone().or_else(|| field("refresh_token")), field("access_token"))
+}
+
+/// 删除账号，revoke 为 true 时先撤销其令牌
+///
+/// 所有账号均存在才会开始处理；返回每个账号的撤销记录 (未撤销时为空)。
+/// 工作区条目与来源账号共用同一组令牌，仍被保留的账号或 auth.json 使用的令牌记为 Shared 而不撤销。
+/// 删除当前账号后，auth.json 改写为新的当前账号
+pub async fn delete_accounts_inner(
+    store: &Mutex<AccountStore>,
+    ids: &[String],
+    revoke_tokens: bool,
+) -> Result<Vec<RevocationRecord>, String> {
+    // 1. 取出令牌后立即释放锁，避免跨 await 持有
+    let (targets, shared) = {
+        let store = store.lock().map_err(|e| e.to_string())?;
+        let targets = ids.iter()
+            .map(|id| {
+                let account = store.accounts.get(id)
+                    .ok_or_else(|| format!("账号不存在: {}", id))?;
+                let (refresh_token, access_token) = account_tokens(account);
+                Ok((id.clone(), account.name.clone(), refresh_token, access_token))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let mut shared: HashSet<String> = store.accounts.values()
+            .filter(|a| !ids.contains(&a.id))
+            .flat_map(|a| {
+                let (refresh_token, access_token) = account_tokens(a);
+                [refresh_token, access_token]
+            })
+            .flatten()
+            .collect();
+        // Codex CLI 正在使用的令牌: 撤销后 CLI 会立即掉线
+        if let Ok(auth) = AccountStore::read_codex_auth() {
+            let tokens = auth.get("tokens");
+            shared.extend(
+                ["refresh_token", "access_token"].iter()
+                    .filter_map(|key| tokens.and_then(|t| t.get(key)).and_then(|v| v.as_str()))
+                    .map(str::to_string),
+            );
+        }
+        (targets, shared)
+    };
+
+    // 2. 撤销令牌 (refresh_token 优先，撤销它通常会连带使 access_token 失效)
+    let mut records = Vec::new();
+    if revoke_tokens {
+        for (id, name, refresh_token, access_token) in &targets {
+            let record = RevocationRecord {
+                account_id: id.clone(),
+                account_name: name.clone(),
+                refresh_token: revoke(refresh_token.clone(), "refresh_token", &shared).await,
+                access_token: revoke(access_token.clone(), "access_token", &shared).await,
+                deleted_at: chrono::Utc::now(),
+            };
+            println!(
+                "[Revoke] 账号 {}: refresh_token {:?}, access_token {:?}",
+                name, record.refresh_token, record.access_token
+            );
+            records.push(record);
+        }
+    }
+
+    // 3. 无论撤销结果如何都在本地删除
+    let mut store = store.lock().map_err(|e| e.to_string())?;
+    let previous_current = store.current.clone();
+    for (id, _, _, _) in &targets {
+        // 撤销期间可能已被其他操作删除
+        if store.accounts.contains_key(id) {
+            store.delete_account(id)?;
+        }
+    }
+    if store.current != previous_current {
+        if let Some(current) = store.current.as_ref().and_then(|c| store.accounts.get(c)) {
+            println!("[Revoke] 当前账号已删除，切换到 {}", current.name);
+            AccountStore::write_codex_auth(&current.auth_json)?;
+        }
+    }
+    store.revocations.extend(records.iter().cloned());
+    let overflow = store.revocations.len().saturating_sub(MAX_RECORDS);
+    store.revocations.drain(..overflow);
+    store.save()?;
+
+    Ok(records)
+}
+
+/// 删除账号 (revoke 未指定时使用设置中的默认值)
+#[tauri::command]
+pub async fn delete_account(
+    state: State<'_, AppState>,
+    id: String,
+    revoke: Option<bool>,
+) -> Result<Option<RevocationRecord>, String> {
+    let revoke = resolve_revoke(&state.store, revoke)?;
+    let mut records = delete_accounts_inner(&state.store, &[id], revoke).await?;
+    Ok(records.pop())
+}
+
+/// 批量撤销并删除账号
+#[tauri::command]
+pub async fn delete_accounts(
+    state: State<'_, AppState>,
+    ids: Vec<String>,
+    revoke: Option<bool>,
+) -> Result<Vec<RevocationRecord>, String> {
+    let revoke = resolve_revoke(&state.store, revoke)?;
+    delete_accounts_inner(&state.store, &ids, revoke).await
+}
+
+/// 获取撤销记录 (最近的在前)
+#[tauri::command]
+pub fn get_revocation_log(state: State<'_, AppState>) -> Result<Vec<RevocationRecord>, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    Ok(store.revocations.iter().rev().cloned().collect())
+}
+
+fn resolve_revoke(store: &Mutex<AccountStore>, revoke: Option<bool>) -> Result<bool, String> {
+    match revoke {
+        Some(r) => Ok(r),
+        None => Ok(store.lock().map_err(|e| e.to_string())?.settings.revoke_on_delete),
+    }
+}
@@ -0,0 +1,275 @@
+//! Codex Switcher - 定时切换
+//!
+//! 规则把星期与本地时间段映射到账号 (如工作日 09:00-18:00 使用 Team 账号)。
+//! 后台任务在进入时段时经正常的切换流程切换一次；时段内手动切换不会被改回。
+//! 多条规则同时命中时以列表中靠前的为准
+
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::{Emitter, State};
+
+use crate::account::AccountStore;
+use crate::refresh_health;
+use crate::scheduler::{JobContext, JobReport};
+use crate::AppState;
+
+/// 定时切换规则
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwitchRule {
+    pub id: String,
+    pub name: String,
+    pub account_id: String,
+    /// 生效的星期 (1 = 周一 … 7 = 周日)，为空表示每天
+    #[serde(default)]
+    pub weekdays: Vec<u8>,
+    /// 本地时间 "HH:MM"；结束早于开始表示跨越午夜，两者相同表示全天
+    pub start: String,
+    pub end: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+fn default_true() -> bool { true }
+
+/// 某一时刻命中的规则
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActiveWindow {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub account_id: String,
+    /// 本次时段的开始时间 (本地)
+    pub started_at: NaiveDateTime,
+}
+
+impl ActiveWindow {
+    /// 时段的唯一标识，用于判断是否已切换过
+    fn key(&self) -> String {
+        format!("{}@{}", self.rule_id, self.started_at.format("%Y-%m-%dT%H:%M"))
+    }
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .map_err(|_| format!("时间格式应为 HH:MM: {}", value))
+}
+
+fn runs_on(rule: &SwitchRule, date: NaiveDate) -> bool {
+    rule.weekdays.is_empty() || rule.weekdays.contains(&(date.weekday().number_from_monday() as u8))
+}
+
+/// 规则在指定时刻是否生效，返回本次时段的开始时间
+fn window_start(rule: &SwitchRule, at: NaiveDateTime) -> Option<NaiveDateTime> {
+    let start = parse_time(&rule.start).ok()?;
+    let end = parse_time(&rule.end).ok()?;
+    let (date, time) = (at.date(), at.time());
+    let yesterday = date.pred_opt()?;
+
+    if start < end {
+        (runs_on(rule, date) && time >= start && time < end).then(|| date.and_time(start))
+    } else if runs_on(rule, date) && time >= start {
+        Some(date.and_time(start))
+    } else if runs_on(rule, yesterday) && time < end {
+        // 前一天开始、跨越午夜的时段 (或全天规则的前一天)
+        Some(yesterday.and_time(start))
+    } else {
+        None
+    }
+}
+
+/// 指定时刻生效的规则 (按列表顺序取第一条)
+pub fn resolve(rules: &[SwitchRule], at: NaiveDateTime) -> Option<ActiveWindow> {
+    rules.iter()
+        .filter(|rule| rule.enabled)
+        .find_map(|rule| {
+            window_start(rule, at).map(|started_at| ActiveWindow {
+                rule_id: rule.id.clone(),
+                rule_name: rule.name.clone(),
+                account_id: rule.account_id.clone(),
+                started_at,
+            })
+        })
+}
+
+/// 校验规则
+fn validate(store: &AccountStore, rules: &[SwitchRule]) -> Result<(), String> {
+    for rule in rules {
+        parse_time(&rule.start)?;
+        parse_time(&rule.end)?;
+        if let Some(day) = rule.weekdays.iter().find(|d| !(1..=7).contains(*d)) {
+            return Err(format!("规则「{}」的星期无效: {}", rule.name, day));
+        }
+        if !store.accounts.contains_key(&rule.account_id) {
+            return Err(format!("规则「{}」指向的账号不存在", rule.name));
+        }
+    }
+    Ok(())
+}
+
+/// 保存规则 (未指定 ID 的规则自动生成)
+pub fn save_rules_inner(store: &Mutex<AccountStore>, mut rules: Vec<SwitchRule>) -> Result<Vec<SwitchRule>, String> {
+    let mut store = store.lock().map_err(|e| e.to_string())?;
+    validate(&store, &rules)?;
+    for rule in &mut rules {
+        if rule.id.is_empty() {
+            rule.id = uuid::Uuid::new_v4().to_string();
+        }
+    }
+    store.switch_schedules = rules.clone();
+    store.save()?;
+    Ok(rules)
+}
+
+/// 定时切换结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum ScheduleOutcome {
+    /// 当前不在任何时段内
+    Idle,
+    /// 本时段已处理过
+    AlreadyApplied,
+    /// 时段开始时已在使用目标账号
+    AlreadyCurrent { account_id: String },
+    /// 目标账号需要重新登录，暂不切换 (重新登录后在本时段内仍会切换)
+    NeedsRelogin { account_id: String },
+    /// 目标账号已不存在，跳过该规则
+    MissingAccount { account_id: String, rule_id: String },
+    Switched { account_id: String, rule_id: String },
+}
+
+/// 按规则在时段开始时切换账号
+pub async fn apply_inner(store: &Mutex<AccountStore>, at: NaiveDateTime) -> Result<ScheduleOutcome, String> {
+    let (window, already_current) = {
+        let store = store.lock().map_err(|e| e.to_string())?;
+        let Some(window) = resolve(&store.switch_schedules, at) else {
+            return Ok(ScheduleOutcome::Idle);
+        };
+        if store.schedule_last_applied.as_deref() == Some(window.key().as_str()) {
+            return Ok(ScheduleOutcome::AlreadyApplied);
+        }
+        let Some(target) = store.accounts.get(&window.account_id) else {
+            println!("[Schedule] 规则「{}」指向的账号不存在，跳过", window.rule_name);
+            return Ok(ScheduleOutcome::MissingAccount { account_id: window.account_id, rule_id: window.rule_id });
+        };
+        if refresh_health::needs_relogin(target) {
+            return Ok(ScheduleOutcome::NeedsRelogin { account_id: window.account_id });
+        }
+        let already_current = store.current.as_deref() == Some(window.account_id.as_str());
+        (window, already_current)
+    };
+
+    if !already_current {
+        println!("[Schedule] 进入时段「{}」，切换到账号 {}", window.rule_name, window.account_id);
+        crate::switch_account_inner(store, &window.account_id).await?;
+    }
+
+    let mut s = store.lock().map_err(|e| e.to_string())?;
+    s.schedule_last_applied = Some(window.key());
+    s.save()?;
+    Ok(if already_current {
+        ScheduleOutcome::AlreadyCurrent { account_id: window.account_id }
+    } else {
+        ScheduleOutcome::Switched { account_id: window.account_id, rule_id: window.rule_id }
+    })
+}
+
+/// 后台任务: 检查是否进入新的时段
+pub async fn run(ctx: JobContext) -> Result<JobReport, String> {
+    let outcome = apply_inner(&ctx.store, Local::now().naive_local()).await?;
+    if let ScheduleOutcome::Switched { .. } = &outcome {
+        // 与手动切换一致，由前端按设置决定是否重载 IDE
+        let _ = ctx.app_handle.emit("account-switched", &outcome);
+    }
+    Ok(JobReport {
+        summary: match &outcome {
+            ScheduleOutcome::Idle => "当前不在任何时段内".to_string(),
+            ScheduleOutcome::AlreadyApplied => "本时段已处理".to_string(),
+            ScheduleOutcome::AlreadyCurrent { .. } => "已在使用目标账号".to_string(),
+            ScheduleOutcome::NeedsRelogin { account_id } => format!("账号 {} 需要重新登录，暂不切换", account_id),
+            ScheduleOutcome::MissingAccount { account_id, .. } => format!("规则指向的账号 {} 不存在，已跳过", account_id),
+            ScheduleOutcome::Switched { account_id, .. } => format!("已切换到账号 {}", account_id),
+        },
+        details: serde_json::to_value(&outcome).unwrap_or_default(),
+    })
+}
+
+/// 获取定时切换规则
+#[tauri::command]
+pub fn get_switch_schedules(state: State<'_, AppState>) -> Result<Vec<SwitchRule>, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    Ok(store.switch_schedules.clone())
+}
+
+/// 保存定时切换规则
+#[tauri::command]
+pub fn save_switch_schedules(state: State<'_, AppState>, rules: Vec<SwitchRule>) -> Result<Vec<SwitchRule>, String> {
+    let rules = save_rules_inner(&state.store, rules)?;
+    // 规则变化后立即检查一次
+    crate::scheduler::trigger("schedule_switch");
+    Ok(rules)
+}
+
+/// 预览指定时刻 (本地时间 "YYYY-MM-DDTHH:MM"，为空时为当前) 生效的规则；
+/// 传入 rules 时预览未保存的规则
+#[tauri::command]
+pub fn preview_switch_schedule(
+    state: State<'_, AppState>,
+    at: Option<String>,
+    rules: Option<Vec<SwitchRule>>,
+) -> Result<Option<ActiveWindow>, String> {
+    let at = match at {
+        Some(value) => NaiveDateTime::parse_from_str(value.trim(), "%Y-%m-%dT%H:%M")
+            .map_err(|_| format!("时间格式应为 YYYY-MM-DDTHH:MM: {}", value))?,
+        None => Local::now().naive_local(),
+    };
+    let rules = match rules {
+        Some(rules) => rules,
+        None => state.store.lock().map_err(|e| e.to_string())?.switch_schedules.clone(),
+    };
+    Ok(resolve(&rules, at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: &str, weekdays: &[u8], start: &str, end: &str) -> SwitchRule {
+        SwitchRule {
+            id: id.to_string(),
+            name: id.to_string(),
+            account_id: format!("acct-{}", id),
+            weekdays: weekdays.to_vec(),
+            start: start.to_string(),
+            end: end.to_string(),
+            enabled: true,
+        }
+    }
+
+    fn at(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_resolve_weekdays_overnight_and_priority() {
+        // 2026-03-02 为周一
+        let rules = vec![
+            rule("work", &[1, 2, 3, 4, 5], "09:00", "18:00"),
+            rule("evening", &[], "18:00", "02:00"),
+            rule("fallback", &[], "00:00", "00:00"),
+        ];
+        let active = |value| resolve(&rules, at(value)).map(|w| (w.rule_id, w.started_at));
+
+        assert_eq!(active("2026-03-02T10:30"), Some(("work".to_string(), at("2026-03-02T09:00"))));
+        assert_eq!(active("2026-03-02T18:00"), Some(("evening".to_string(), at("2026-03-02T18:00"))));
+        // 跨越午夜: 归属前一天开始的时段
+        assert_eq!(active("2026-03-03T01:59"), Some(("evening".to_string(), at("2026-03-02T18:00"))));
+        // 其余时间落到全天规则
+        assert_eq!(active("2026-03-03T02:00"), Some(("fallback".to_string(), at("2026-03-03T00:00"))));
+        // 周六不命中工作日规则
+        assert_eq!(active("2026-03-07T10:30"), Some(("fallback".to_string(), at("2026-03-07T00:00"))));
+
+        let mut disabled = rules.clone();
+        disabled[2].enabled = false;
+        assert_eq!(resolve(&disabled, at("2026-03-07T10:30")), None);
+    }
+}
@@ -1,134 +1,428 @@
-//! 后台调度器 - Token 自动刷新
+//! 后台调度器 - 定时任务运行框架
 //!
-//! 借鉴 CodexBar 的全局定时轮询 + Antigravity 的静默刷新
-
-use crate::account::AccountStore;
-use std::sync::{Arc, Mutex};
-use tokio::time::{interval, Duration};
-use tauri::Emitter;
-
-/// 启动后台 Token 刷新调度器
-pub fn start(store: Arc<Mutex<AccountStore>>, app_handle: tauri::AppHandle) {
-    // 使用 Tauri 的 async runtime 而不是直接 tokio::spawn
-    // 因为在 setup() 中调用时 Tokio runtime 可能尚未完全初始化
-    tauri::async_runtime::spawn(async move {
-        // 默认 30 分钟轮询一次
-        let mut ticker = interval(Duration::from_secs(30 * 60));
-        
-        println!("✅ 后台调度器已启动 (间隔: 30 分钟)");
-        
-        loop {
-            ticker.tick().await;
-            
-            println!("[Scheduler] 开始检查 Token 有效期...");
-            
-            // 获取所有账号
-            let accounts = {
-                let store = store.lock().unwrap();
-                store.accounts.values().cloned().collect::<Vec<_>>()
-            };
-            
-            let mut refreshed_count = 0;
-            
-            for account in accounts {
-                // 检查 Token 是否即将过期
-                if let Some(ref refresh_token) = account.refresh_token {
-                    if is_token_expiring_soon(&account.auth_json) {
-                        println!("[Scheduler] 账号 {} Token 即将过期，正在刷新...", account.name);
-                        
-                        // 调用刷新逻辑
-                        match refresh_token_silently(refresh_token, &account.auth_json).await {
-                            Ok(new_auth) => {
-                                // 更新 store 中的 auth_json
-                                let mut store = store.lock().unwrap();
-                                if let Some(acc) = store.accounts.get_mut(&account.id) {
-                                    acc.auth_json = new_auth;
-                                    refreshed_count += 1;
-                                    println!("[Scheduler] ✅ 账号 {} Token 刷新成功", account.name);
-                                }
-                                let _ = store.save();
-                            }
-                            Err(e) => {
-                                println!("[Scheduler] ❌ 账号 {} Token 刷新失败: {}", account.name, e);
-                            }
-                        }
-                    }
-                }
-            }
-            
-            if refreshed_count > 0 {
-                println!("[Scheduler] 本轮刷新了 {} 个账号的 Token", refreshed_count);
-                
-                // 发送事件通知前端更新账号列表
-                let _ = app_handle.emit("accounts-updated", ());
-            } else {
-                println!("[Scheduler] 所有 Token 状态良好，无需刷新");
+//! 借鉴 CodexBar 的全局定时轮询。每个任务独立计时 (间隔 + 随机抖动)、可单独启用，
+//! 同一任务不会重叠执行；最近一次运行记录保存在 jobs.json，重启后按记录继续计时。
+//! 新的周期性工作在 `jobs::builtin()` 中登记即可，修改设置后各任务立即重新计时
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::Notify;
+use tokio::time::Duration;
+
+use crate::account::{AccountStore, AppSettings};
+use crate::store::StoreService;
+use crate::AppState;
+
+/// 间隔下限，避免误配置导致频繁请求
+const MIN_INTERVAL_MINUTES: u32 = 1;
+
+/// 单个任务的配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobSettings {
+    pub enabled: bool,
+    pub interval_minutes: u32,
+    /// 每次计时附加 0..=jitter_secs 秒的随机延迟，避免多个客户端或任务同时触发
+    #[serde(default)]
+    pub jitter_secs: u32,
+}
+
+/// 任务执行结果
+pub struct JobReport {
+    pub summary: String,
+    /// 任务自定义的详细结果 (供前端展示)
+    pub details: serde_json::Value,
+}
+
+pub type JobFuture = Pin<Box<dyn Future<Output = Result<JobReport, String>> + Send>>;
+
+/// 任务执行上下文
+#[derive(Clone)]
+pub struct JobContext {
+    pub store: StoreService,
+    pub app_handle: AppHandle,
+    /// 本次执行时生效的任务配置
+    pub settings: JobSettings,
+}
+
+/// 任务定义
+pub struct Job {
+    /// 任务名 (设置与运行记录中的键)
+    pub name: &'static str,
+    pub label: &'static str,
+    pub defaults: JobSettings,
+    /// 自定义配置来源，为空时读取 `settings.jobs` 中的同名条目
+    pub configure: Option<fn(&AppSettings) -> JobSettings>,
+    /// 系统唤醒或时钟跳变后是否立即执行 (否则仅按墙上时间重新计时)
+    pub catch_up: bool,
+    pub run: fn(JobContext) -> JobFuture,
+}
+
+impl Job {
+    /// 当前生效的配置
+    pub fn settings(&self, settings: &AppSettings) -> JobSettings {
+        let mut config = match self.configure {
+            Some(configure) => configure(settings),
+            None => settings.jobs.get(self.name).cloned().unwrap_or_else(|| self.defaults.clone()),
+        };
+        config.interval_minutes = config.interval_minutes.max(MIN_INTERVAL_MINUTES);
+        config
+    }
+}
+
+/// 持久化的运行记录
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_finished_at: Option<DateTime<Utc>>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub last_summary: Option<String>,
+    pub last_duration_ms: u64,
+    /// 最近一次是否为手动触发
+    pub last_manual: bool,
+    pub run_count: u64,
+}
+
+/// 任务状态
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub name: String,
+    pub label: String,
+    #[serde(flatten)]
+    pub settings: JobSettings,
+    /// 是否正在执行
+    pub running: bool,
+    /// 下次计划执行时间 (未启用时为空)
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_run: Option<JobRecord>,
+    /// 最近一次执行的详细结果 (本次启动以来)
+    pub details: serde_json::Value,
+}
+
+/// 调度器状态
+#[derive(Debug, Clone, Serialize)]
+pub struct SchedulerStatus {
+    pub jobs: Vec<JobStatus>,
+}
+
+struct Slot {
+    job: Job,
+    /// 设置变更或手动执行后唤醒计时循环
+    wake: Notify,
+    /// 防止同一任务重叠执行
+    run_lock: tokio::sync::Mutex<()>,
+    state: Mutex<SlotState>,
+}
+
+#[derive(Default)]
+struct SlotState {
+    settings: Option<JobSettings>,
+    running: bool,
+    next_run_at: Option<DateTime<Utc>>,
+    details: serde_json::Value,
+    /// 下一次计时立即执行
+    catch_up: bool,
+}
+
+impl Slot {
+    fn state(&self) -> MutexGuard<'_, SlotState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+static SLOTS: OnceLock<Vec<Slot>> = OnceLock::new();
+static RECORDS: OnceLock<Mutex<BTreeMap<String, JobRecord>>> = OnceLock::new();
+
+fn slots() -> &'static [Slot] {
+    SLOTS.get().map(|s| s.as_slice()).unwrap_or_default()
+}
+
+/// 运行记录文件 (与 accounts.json 同目录)
+fn records_path() -> PathBuf {
+    AccountStore::config_path().with_file_name("jobs.json")
+}
+
+fn records() -> MutexGuard<'static, BTreeMap<String, JobRecord>> {
+    RECORDS
+        .get_or_init(|| {
+            let records = std::fs::read_to_string(records_path())
+                .ok()
+                .and_then(|content| serde_json::from_str(&content).ok())
+                .unwrap_or_default();
+            Mutex::new(records)
+        })
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+fn save_records(records: &BTreeMap<String, JobRecord>) -> Result<(), String> {
+    let path = records_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(records)
+        .map_err(|e| format!("序列化失败: {}", e))?;
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, content)
+        .map_err(|e| format!("写入临时文件失败: {}", e))?;
+    std::fs::rename(&tmp_path, &path)
+        .map_err(|e| format!("写入文件失败: {}", e))
+}
+
+/// 下次执行时间: 上次开始时间 + 间隔 + 抖动；从未执行过时只等待抖动
+///
+/// 系统时间被回拨后上次开始时间可能晚于当前时间，按当前时间计算，避免任务长时间停摆
+pub fn next_run_at(
+    last_started_at: Option<DateTime<Utc>>,
+    interval_minutes: u32,
+    jitter_secs: u32,
+    now: DateTime<Utc>,
+) -> DateTime<Utc> {
+    let jitter = chrono::Duration::seconds(jitter_secs as i64);
+    match last_started_at {
+        Some(last) => last.min(now) + chrono::Duration::minutes(interval_minutes as i64) + jitter,
+        None => now + jitter,
+    }
+}
+
+fn job_status(slot: &Slot, records: &BTreeMap<String, JobRecord>) -> JobStatus {
+    let state = slot.state();
+    JobStatus {
+        name: slot.job.name.to_string(),
+        label: slot.job.label.to_string(),
+        settings: state.settings.clone().unwrap_or_else(|| slot.job.defaults.clone()),
+        running: state.running,
+        next_run_at: state.next_run_at,
+        last_run: records.get(slot.job.name).cloned(),
+        details: state.details.clone(),
+    }
+}
+
+pub fn status() -> SchedulerStatus {
+    let records = records().clone();
+    SchedulerStatus {
+        jobs: slots().iter().map(|slot| job_status(slot, &records)).collect(),
+    }
+}
+
+/// 设置已变更: 所有任务按新配置重新计时
+pub fn reconfigure() {
+    for slot in slots() {
+        slot.wake.notify_one();
+    }
+}
+
+/// 系统唤醒或时钟跳变: 需要补做的任务立即执行，其余任务按墙上时间重新计时
+///
+/// 计时使用的 tokio 定时器基于单调时钟，睡眠期间不走时，因此需要由时钟监测主动唤醒
+pub fn catch_up() {
+    for slot in slots() {
+        if slot.job.catch_up {
+            slot.state().catch_up = true;
+        }
+        slot.wake.notify_one();
+    }
+}
+
+/// 立即执行指定任务 (按任务自身的启用设置)
+pub fn trigger(name: &str) {
+    if let Some(slot) = slots().iter().find(|slot| slot.job.name == name) {
+        slot.state().catch_up = true;
+        slot.wake.notify_one();
+    }
+}
+
+/// 启动调度器，每个任务一个计时循环
+pub fn start(store: StoreService, app_handle: AppHandle, jobs: Vec<Job>) {
+    let registered = jobs.into_iter()
+        .map(|job| Slot {
+            job,
+            wake: Notify::new(),
+            run_lock: tokio::sync::Mutex::new(()),
+            state: Mutex::new(SlotState::default()),
+        })
+        .collect();
+    if SLOTS.set(registered).is_err() {
+        eprintln!("[Scheduler] 调度器已启动，忽略重复调用");
+        return;
+    }
+
+    for slot in slots() {
+        let ctx = JobContext {
+            store: store.clone(),
+            app_handle: app_handle.clone(),
+            settings: slot.job.defaults.clone(),
+        };
+        // 使用 Tauri 的 async runtime 而不是直接 tokio::spawn
+        // 因为在 setup() 中调用时 Tokio runtime 可能尚未完全初始化
+        tauri::async_runtime::spawn(drive(slot, ctx));
+    }
+    println!("✅ 后台调度器已启动 ({} 个任务)", slots().len());
+}
+
+/// 单个任务的计时循环
+async fn drive(slot: &'static Slot, ctx: JobContext) {
+    loop {
+        // 先登记等待，避免计算期间到来的唤醒丢失
+        let wake = slot.wake.notified();
+        tokio::pin!(wake);
+        wake.as_mut().enable();
+
+        let settings = {
+            let store = ctx.store.lock().unwrap_or_else(|e| e.into_inner());
+            slot.job.settings(&store.settings)
+        };
+        let catch_up = std::mem::take(&mut slot.state().catch_up);
+        let next = settings.enabled.then(|| {
+            if catch_up {
+                return Utc::now();
             }
+            let jitter = rand::rng().random_range(0..=settings.jitter_secs);
+            let last_started_at = records().get(slot.job.name).and_then(|r| r.last_started_at);
+            next_run_at(last_started_at, settings.interval_minutes, jitter, Utc::now())
+        });
+        {
+            let mut state = slot.state();
+            state.settings = Some(settings);
+            state.next_run_at = next;
         }
-    });
-}
-
-/// 检查 Token 是否即将过期（剩余 < 10 分钟）
-fn is_token_expiring_soon(auth_json: &serde_json::Value) -> bool {
-    // 优先从 tokens.expires_at 获取 (可能是 RFC3339 字符串)
-    let expires_at_val = auth_json.get("tokens")
-        .and_then(|t| t.get("expires_at"))
-        .or_else(|| auth_json.get("expires_at"));
-
-    if let Some(val) = expires_at_val {
-        let timestamp = if let Some(ts) = val.as_i64() {
-            ts
-        } else if let Some(iso_str) = val.as_str() {
-            // 解析 RFC3339 字符串
-            chrono::DateTime::parse_from_rfc3339(iso_str)
-                .map(|dt| dt.timestamp())
-                .unwrap_or(0)
-        } else {
-            0
+
+        let Some(next) = next else {
+            wake.await;
+            continue;
         };
 
-        if timestamp > 0 {
-            let now = chrono::Utc::now().timestamp();
-            let remaining = timestamp - now;
-            return remaining < 600; // 10 分钟
+        let delay = (next - Utc::now()).to_std().unwrap_or(Duration::ZERO);
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => execute(slot, &ctx, false).await,
+            _ = wake => {}
         }
     }
-    false
-}
-
-/// 静默刷新 Token
-async fn refresh_token_silently(refresh_token: &str, old_auth: &serde_json::Value) -> Result<serde_json::Value, String> {
-    // 复用 OAuth 模块的刷新逻辑
-    let token_response = crate::oauth::refresh_access_token(refresh_token).await?;
-    
-    // 计算新的过期时间
-    let expires_in = token_response.expires_in.unwrap_or(3600);
-    let expires_at_iso = (chrono::Utc::now() + chrono::Duration::seconds(expires_in as i64)).to_rfc3339();
-    
-    // 构建新的 auth_json，保留原有结构
-    let mut new_auth = old_auth.clone();
-    if let Some(obj) = new_auth.as_object_mut() {
-        obj.insert("last_refresh".to_string(), serde_json::json!(chrono::Utc::now().to_rfc3339()));
-        
-        if let Some(tokens_obj) = obj.get_mut("tokens").and_then(|v| v.as_object_mut()) {
-            tokens_obj.insert("access_token".to_string(), serde_json::json!(token_response.access_token));
-            if let Some(rt) = token_response.refresh_token {
-                tokens_obj.insert("refresh_token".to_string(), serde_json::json!(rt));
+}
+
+fn emit_status(app_handle: &AppHandle) {
+    let _ = app_handle.emit("scheduler-status", status());
+}
+
+/// 执行一次任务；已在执行时计划触发直接跳过，手动触发等待其完成
+async fn execute(slot: &Slot, ctx: &JobContext, manual: bool) {
+    let _guard = match slot.run_lock.try_lock() {
+        Ok(guard) => guard,
+        Err(_) => {
+            if manual {
+                let _ = slot.run_lock.lock().await;
+            }
+            return;
+        }
+    };
+
+    let started_at = Utc::now();
+    slot.state().running = true;
+    emit_status(&ctx.app_handle);
+    println!("[Scheduler] 开始执行任务: {}", slot.job.label);
+
+    let mut ctx = ctx.clone();
+    ctx.settings = {
+        let store = ctx.store.lock().unwrap_or_else(|e| e.into_inner());
+        slot.job.settings(&store.settings)
+    };
+    let result = (slot.job.run)(ctx.clone()).await;
+
+    let finished_at = Utc::now();
+    {
+        let mut records = records();
+        let record = records.entry(slot.job.name.to_string()).or_default();
+        record.last_started_at = Some(started_at);
+        record.last_finished_at = Some(finished_at);
+        record.last_duration_ms = (finished_at - started_at).num_milliseconds().max(0) as u64;
+        record.last_manual = manual;
+        record.run_count += 1;
+        match &result {
+            Ok(report) => {
+                println!("[Scheduler] ✅ {}: {}", slot.job.label, report.summary);
+                record.last_success_at = Some(finished_at);
+                record.last_error = None;
+                record.last_summary = Some(report.summary.clone());
             }
-            if let Some(it) = token_response.id_token {
-                tokens_obj.insert("id_token".to_string(), serde_json::json!(it));
+            Err(e) => {
+                println!("[Scheduler] ❌ {} 失败: {}", slot.job.label, e);
+                record.last_error = Some(e.clone());
             }
-            tokens_obj.insert("expires_at".to_string(), serde_json::json!(expires_at_iso));
-        } else {
-            // 如果旧结构受损，尝试重建基础结构
-            obj.insert("tokens".to_string(), serde_json::json!({
-                "access_token": token_response.access_token,
-                "refresh_token": token_response.refresh_token,
-                "id_token": token_response.id_token,
-                "expires_at": expires_at_iso
-            }));
         }
+        if let Err(e) = save_records(&records) {
+            eprintln!("[Scheduler] 保存运行记录失败: {}", e);
+        }
+    }
+    {
+        let mut state = slot.state();
+        state.running = false;
+        if let Ok(report) = result {
+            state.details = report.details;
+        }
+    }
+    emit_status(&ctx.app_handle);
+}
+
+/// 获取调度器状态
+#[tauri::command]
+pub fn get_scheduler_status() -> SchedulerStatus {
+    status()
+}
+
+/// 立即执行指定任务 (关闭的任务同样可以手动执行)；未指定时执行所有已启用的任务
+#[tauri::command]
+pub async fn run_scheduler_now(
+    app_handle: AppHandle,
+    state: State<'_, AppState>,
+    job: Option<String>,
+) -> Result<SchedulerStatus, String> {
+    let targets: Vec<&Slot> = match job {
+        Some(name) => vec![slots().iter()
+            .find(|slot| slot.job.name == name)
+            .ok_or_else(|| format!("未知任务: {}", name))?],
+        None => {
+            let settings = state.store.lock().map_err(|e| e.to_string())?.settings.clone();
+            slots().iter().filter(|slot| slot.job.settings(&settings).enabled).collect()
+        }
+    };
+
+    for slot in targets {
+        let ctx = JobContext {
+            store: state.store.clone(),
+            app_handle: app_handle.clone(),
+            settings: slot.job.defaults.clone(),
+        };
+        execute(slot, &ctx, true).await;
+        // 以本次执行时间为基准重新计时
+        slot.wake.notify_one();
+    }
+    Ok(status())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_run_at_adds_interval_and_jitter() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let last = now - chrono::Duration::minutes(10);
+
+        assert_eq!(next_run_at(None, 30, 0, now), now);
+        assert_eq!(next_run_at(None, 30, 15, now), now + chrono::Duration::seconds(15));
+        assert_eq!(next_run_at(Some(last), 30, 0, now), now + chrono::Duration::minutes(20));
+        assert_eq!(next_run_at(Some(last), 30, 60, now), now + chrono::Duration::minutes(21));
+        // 已经逾期: 返回过去的时间，由调用方立即执行
+        assert!(next_run_at(Some(now - chrono::Duration::hours(2)), 30, 0, now) < now);
+        // 时间回拨后上次执行时间在未来: 以当前时间为基准
+        assert_eq!(next_run_at(Some(now + chrono::Duration::days(1)), 30, 0, now), now + chrono::Duration::minutes(30));
     }
-    
-    Ok(new_auth)
 }
@@ -0,0 +1,108 @@
+//! Codex Switcher - 共享账号存储服务
+//!
+//! Tauri 命令、后台调度器以及今后的 CLI / 守护进程共用同一份内存数据，
+//! 写盘经 `AccountStore::save` 串行执行，每次写入后广播变更事件
+
+use serde::Serialize;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::broadcast;
+
+use crate::account::AccountStore;
+
+/// 广播通道容量 (订阅者落后时只会丢弃旧事件)
+const CHANNEL_CAPACITY: usize = 64;
+
+/// 存储变更事件
+#[derive(Debug, Clone, Serialize)]
+pub struct StoreChange {
+    /// 单调递增的写入序号
+    pub revision: u64,
+    pub changed_at: chrono::DateTime<chrono::Utc>,
+}
+
+static REVISION: AtomicU64 = AtomicU64::new(0);
+static CHANNEL: OnceLock<broadcast::Sender<StoreChange>> = OnceLock::new();
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+fn channel() -> &'static broadcast::Sender<StoreChange> {
+    CHANNEL.get_or_init(|| broadcast::channel(CHANNEL_CAPACITY).0)
+}
+
+/// 订阅存储变更
+pub fn subscribe() -> broadcast::Receiver<StoreChange> {
+    channel().subscribe()
+}
+
+/// 写盘锁: 保证同一进程内的写入不会交错
+pub(crate) fn write_lock() -> MutexGuard<'static, ()> {
+    WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 写盘完成后广播变更
+pub(crate) fn notify_saved() {
+    let change = StoreChange {
+        revision: REVISION.fetch_add(1, Ordering::SeqCst) + 1,
+        changed_at: chrono::Utc::now(),
+    };
+    // 没有订阅者时发送失败，忽略即可
+    let _ = channel().send(change);
+}
+
+/// 账号存储服务，克隆后仍指向同一份数据
+#[derive(Clone, Default)]
+pub struct StoreService {
+    inner: Arc<Mutex<AccountStore>>,
+}
+
+impl StoreService {
+    /// 从磁盘加载
+    pub fn load() -> Self {
+        Self::from_store(AccountStore::load())
+    }
+
+    pub fn from_store(store: AccountStore) -> Self {
+        Self { inner: Arc::new(Mutex::new(store)) }
+    }
+
+    /// 当前数据的副本
+    pub fn snapshot(&self) -> Result<AccountStore, String> {
+        Ok(self.inner.lock().map_err(|e| e.to_string())?.clone())
+    }
+
+    /// 修改并保存
+    pub fn update<R>(&self, f: impl FnOnce(&mut AccountStore) -> Result<R, String>) -> Result<R, String> {
+        let mut store = self.inner.lock().map_err(|e| e.to_string())?;
+        let result = f(&mut store)?;
+        store.save()?;
+        Ok(result)
+    }
+}
+
+impl Deref for StoreService {
+    type Target = Mutex<AccountStore>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+/// 将存储变更转发给前端 (store-changed 事件，连续写入合并为一次)
+pub fn forward_events(app_handle: AppHandle) {
+    let mut rx = subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let mut latest = match rx.recv().await {
+                Ok(change) => change,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            while let Ok(change) = rx.try_recv() {
+                latest = change;
+            }
+            let _ = app_handle.emit("store-changed", latest);
+        }
+    });
+}
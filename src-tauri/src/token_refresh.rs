@@ -0,0 +1,213 @@
+//! Codex Switcher - 令牌刷新协调
+//!
+//! refresh_token 每次使用都会轮换，同一个令牌被两处同时使用时后到的一方会拿到
+//! invalid_grant，甚至导致整条令牌链被撤销。切换、查询用量、后台调度和联网补刷
+//! 都经由这里刷新: 同一账号 (及共用 refresh_token 的工作区条目) 同时只有一个刷新
+//! 在进行，其余调用者等待并直接使用其结果；新令牌在返回前先写入存储
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+
+use crate::account::AccountStore;
+use crate::oauth::{self, TokenResponse};
+use crate::refresh_health;
+
+/// 最近一次刷新失败
+struct Failure {
+    refresh_token: String,
+    error: String,
+    at: Instant,
+}
+
+type Gate = Arc<tokio::sync::Mutex<Option<Failure>>>;
+
+static GATES: OnceLock<Mutex<HashMap<String, Gate>>> = OnceLock::new();
+
+fn gates() -> std::sync::MutexGuard<'static, HashMap<String, Gate>> {
+    GATES
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// 共用同一 refresh_token 的账号使用同一把锁 (以其中最小的账号 ID 为键)
+fn flight_key(store: &AccountStore, id: &str, refresh_token: &str) -> String {
+    store.accounts.values()
+        .filter(|a| a.refresh_token.as_deref() == Some(refresh_token))
+        .map(|a| a.id.as_str())
+        .chain(std::iter::once(id))
+        .min()
+        .unwrap_or(id)
+        .to_string()
+}
+
+/// 以存储中的令牌构造刷新结果 (供等待者使用)
+fn stored_tokens(store: &AccountStore, id: &str) -> Result<TokenResponse, String> {
+    let account = store.accounts.get(id)
+        .ok_or_else(|| format!("账号 {} 不存在", id))?;
+    let tokens = account.auth_json.get("tokens");
+    let field = |key: &str| {
+        tokens
+            .and_then(|t| t.get(key))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+    };
+    let expires_in = field("expires_at")
+        .and_then(|s| chrono::DateTime::parse_from_rfc3339(&s).ok())
+        // expires_at 以本机时间写入 (见 oauth::apply_tokens)
+        .map(|t| (t.timestamp() - chrono::Utc::now().timestamp()).max(0) as u64);
+
+    Ok(TokenResponse {
+        access_token: field("access_token").ok_or("账号数据缺少 access_token")?,
+        refresh_token: account.refresh_token.clone(),
+        id_token: field("id_token"),
+        expires_in,
+    })
+}
+
+/// 写入新令牌: 账号本身及共用旧 refresh_token 的其他工作区条目
+///
+/// apply_tokens 不修改 account_id，各条目仍保留自己的工作区。当前账号的令牌同时写入
+/// auth.json，否则 Codex CLI 会继续使用已被轮换作废的 refresh_token
+fn persist(store: &mut AccountStore, id: &str, old_refresh_token: &str, token_res: &TokenResponse) {
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut current_updated = false;
+    for account in store.accounts.values_mut() {
+        if account.id != id && account.refresh_token.as_deref() != Some(old_refresh_token) {
+            continue;
+        }
+        current_updated |= store.current.as_deref() == Some(account.id.as_str());
+        oauth::apply_tokens(&mut account.auth_json, token_res);
+        if let Some(obj) = account.auth_json.as_object_mut() {
+            obj.insert("last_refresh".to_string(), serde_json::json!(now));
+        }
+        if let Some(rt) = &token_res.refresh_token {
+            account.set_refresh_token(rt.clone());
+        }
+        refresh_health::clear(account);
+    }
+
+    let current = store.current.as_deref().and_then(|c| store.accounts.get(c));
+    if let Some(current) = current.filter(|_| current_updated) {
+        if let Err(e) = AccountStore::write_codex_auth(&current.auth_json) {
+            println!("[Refresh] 写入 auth.json 失败: {}", e);
+        }
+    }
+}
+
+/// 采用外部 (Codex CLI) 已轮换的令牌: 账号本身及共用旧 refresh_token 的工作区条目一并更新
+///
+/// 只替换令牌字段，各条目保留自己的 account_id
+pub(crate) fn adopt_external(store: &mut AccountStore, id: &str, auth_json: &serde_json::Value) -> Result<(), String> {
+    let old_refresh_token = store.accounts.get(id)
+        .ok_or_else(|| format!("账号 {} 不存在", id))?
+        .refresh_token.clone();
+    let tokens = auth_json.get("tokens").ok_or("auth.json 缺少 tokens 字段")?;
+    let refresh_token = tokens.get("refresh_token").and_then(|v| v.as_str());
+
+    for account in store.accounts.values_mut() {
+        let sibling = old_refresh_token.is_some() && account.refresh_token == old_refresh_token;
+        if account.id != id && !sibling {
+            continue;
+        }
+        if let Some(target) = account.auth_json.get_mut("tokens").and_then(|t| t.as_object_mut()) {
+            for key in ["access_token", "refresh_token", "id_token", "expires_at"] {
+                if let Some(value) = tokens.get(key) {
+                    target.insert(key.to_string(), value.clone());
+                }
+            }
+        }
+        if let (Some(obj), Some(last_refresh)) = (account.auth_json.as_object_mut(), auth_json.get("last_refresh")) {
+            obj.insert("last_refresh".to_string(), last_refresh.clone());
+        }
+        if let Some(rt) = refresh_token {
+            account.set_refresh_token(rt.to_string());
+        }
+        refresh_health::clear(account);
+    }
+    Ok(())
+}
+
+/// 持有账号的刷新锁执行刷新
+async fn refresh_exclusive(store: &Mutex<AccountStore>, id: &str, seen: &str, gate: &Gate) -> Result<TokenResponse, String> {
+    let waiting_since = Instant::now();
+    let mut last_failure = gate.lock().await;
+
+    // 1. 调用方读取之后已有其他调用者完成刷新: 直接使用存储中的新令牌
+    {
+        let store = store.lock().map_err(|e| e.to_string())?;
+        let current = store.accounts.get(id)
+            .ok_or_else(|| format!("账号 {} 不存在", id))?
+            .refresh_token.as_deref()
+            .ok_or("该账号没有 refresh_token")?;
+        if current != seen {
+            return stored_tokens(&store, id);
+        }
+    }
+    // 等待期间同一令牌的刷新刚刚失败: 共用其结果，不再用同一令牌重复请求
+    if let Some(failure) = last_failure.as_ref() {
+        if failure.refresh_token == seen && failure.at >= waiting_since {
+            return Err(failure.error.clone());
+        }
+    }
+
+    // 2. 刷新并在释放锁之前写入存储
+    match oauth::refresh_access_token(seen).await {
+        Ok(token_res) => {
+            *last_failure = None;
+            let mut store = store.lock().map_err(|e| e.to_string())?;
+            persist(&mut store, id, seen, &token_res);
+            store.save()?;
+            Ok(token_res)
+        }
+        Err(e) => {
+            // 等待者共用本次结果，失败只在实际请求处记录一次
+            if let Ok(mut store) = store.lock() {
+                refresh_health::record_failure(&mut store, id, seen, &e);
+                if let Err(save_err) = store.save() {
+                    println!("[Health] 保存刷新健康状态失败: {}", save_err);
+                }
+            }
+            *last_failure = Some(Failure {
+                refresh_token: seen.to_string(),
+                error: e.clone(),
+                at: Instant::now(),
+            });
+            Err(e)
+        }
+    }
+}
+
+/// 刷新账号令牌 (单飞): 新令牌已写入存储后才返回
+///
+/// `seen` 为调用方判断需要刷新时读到的 refresh_token；若此后已有其他调用者完成了
+/// 刷新，直接返回存储中的新令牌而不再轮换
+pub async fn refresh(store: &Mutex<AccountStore>, id: &str, seen: &str) -> Result<TokenResponse, String> {
+    let key = {
+        let store = store.lock().map_err(|e| e.to_string())?;
+        flight_key(&store, id, seen)
+    };
+
+    let gate = gates().entry(key.clone()).or_default().clone();
+    let result = refresh_exclusive(store, id, seen, &gate).await;
+
+    // 无人等待时回收
+    let mut gates = gates();
+    if Arc::strong_count(&gate) == 2 {
+        gates.remove(&key);
+    }
+    result
+}
+
+/// 以账号当前的 refresh_token 刷新
+pub async fn refresh_current(store: &Mutex<AccountStore>, id: &str) -> Result<TokenResponse, String> {
+    let seen = {
+        let store = store.lock().map_err(|e| e.to_string())?;
+        store.accounts.get(id)
+            .ok_or_else(|| format!("账号 {} 不存在", id))?
+            .refresh_token.clone()
+            .ok_or("该账号没有 refresh_token")?
+    };
+    refresh(store, id, &seen).await
+}
@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::fs;
 use std::path::PathBuf;
+use crate::resilience::{send_with_retry, RetryPolicy};
 
 /// 前端展示的用量数据
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,6 +35,87 @@ pub struct UsageDisplay {
     pub has_credits: bool,
     /// Token 是否对 CLI 有效 (api.openai.com)
     pub is_valid_for_cli: bool,
+    /// 所有限流窗口（包含后端返回的额外限额桶）
+    #[serde(default)]
+    pub windows: Vec<RateWindow>,
+    /// 原始响应 JSON
+    #[serde(default)]
+    pub raw: Value,
+    /// 是否为离线时返回的缓存数据
+    #[serde(default)]
+    pub offline: bool,
+    /// 缓存数据的更新时间 (实时数据为 None)
+    #[serde(default)]
+    pub cached_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// 单个限流窗口
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateWindow {
+    /// 所属限额桶: 主限额为 "codex"，其余取后端返回的名称
+    pub bucket: String,
+    /// 窗口名称 (如 "5h"、"weekly"，无法识别时为原始字段名)
+    pub name: String,
+    /// 窗口长度（秒），来自 limit_window_seconds
+    pub window_seconds: Option<i64>,
+    /// 使用百分比
+    pub used_percent: i32,
+    /// 剩余百分比
+    pub left_percent: i32,
+    /// 重置时间描述
+    pub reset: String,
+    /// 重置时间戳
+    pub reset_at: Option<i64>,
+}
+
+impl From<&UsageDisplay> for crate::account::CachedQuota {
+    fn from(usage: &UsageDisplay) -> Self {
+        Self {
+            five_hour_left: usage.five_hour_left as f64,
+            five_hour_reset: usage.five_hour_reset.clone(),
+            five_hour_reset_at: usage.five_hour_reset_at,
+            weekly_left: usage.weekly_left as f64,
+            weekly_reset: usage.weekly_reset.clone(),
+            weekly_reset_at: usage.weekly_reset_at,
+            plan_type: usage.plan_type.clone(),
+            is_valid_for_cli: usage.is_valid_for_cli,
+            windows: usage.windows.clone(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+}
+
+impl From<&crate::account::CachedQuota> for UsageDisplay {
+    /// 由缓存构造展示数据，重置时间描述按当前时间重新计算
+    fn from(cached: &crate::account::CachedQuota) -> Self {
+        let reset_text = |reset_at: Option<i64>, fallback: &str| match reset_at {
+            Some(ts) if ts > 0 => UsageFetcher::format_reset(ts),
+            _ => fallback.to_string(),
+        };
+        let windows = cached.windows.iter().map(|w| RateWindow {
+            reset: reset_text(w.reset_at, &w.reset),
+            ..w.clone()
+        }).collect();
+
+        Self {
+            plan_type: cached.plan_type.clone(),
+            five_hour_used: 100 - cached.five_hour_left as i32,
+            five_hour_left: cached.five_hour_left as i32,
+            five_hour_reset: reset_text(cached.five_hour_reset_at, &cached.five_hour_reset),
+            five_hour_reset_at: cached.five_hour_reset_at,
+            weekly_used: 100 - cached.weekly_left as i32,
+            weekly_left: cached.weekly_left as i32,
+            weekly_reset: reset_text(cached.weekly_reset_at, &cached.weekly_reset),
+            weekly_reset_at: cached.weekly_reset_at,
+            credits_balance: None,
+            has_credits: false,
+            is_valid_for_cli: cached.is_valid_for_cli,
+            windows,
+            raw: Value::Null,
+            offline: true,
+            cached_at: Some(cached.updated_at),
+        }
+    }
 }
 
 /// Auth.json tokens 结构
@@ -55,10 +137,7 @@ pub struct UsageFetcher;
 impl UsageFetcher {
     /// 获取 Codex auth.json 路径
     fn auth_path() -> PathBuf {
-        dirs::home_dir()
-            .expect("无法获取用户目录")
-            .join(".codex")
-            .join("auth.json")
+        crate::account::AccountStore::codex_auth_path()
     }
 
     /// 读取认证信息
@@ -84,42 +163,47 @@ impl UsageFetcher {
     }
 
     /// 从 API 获取用量 (直接使用提供的 Token，不读取 auth.json)
-    pub async fn fetch_usage_direct(
+    ///
+    /// 返回 401/403 时调用 `refresh` 获取新令牌后重试 (由调用方负责协调与保存)
+    pub async fn fetch_usage_direct<F, Fut>(
         access_token: String,
         account_id: Option<String>,
-        refresh_token: Option<String>,
-    ) -> Result<(UsageDisplay, Option<crate::oauth::TokenResponse>), String> {
+        refresh: Option<F>,
+    ) -> Result<(UsageDisplay, Option<crate::oauth::TokenResponse>), String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<crate::oauth::TokenResponse, String>>,
+    {
         let mut current_token = access_token;
         let mut new_tokens: Option<crate::oauth::TokenResponse> = None;
 
-        let client = reqwest::Client::new();
+        let client = crate::http_client::client();
+        let usage_url = crate::endpoints::current().usage_url;
         let build_request = |at: &str, aid: &Option<String>| {
             let mut req = client
-                .get("https://chatgpt.com/backend-api/wham/usage")
+                .get(&usage_url)
                 .header("Authorization", format!("Bearer {}", at))
                 .header("User-Agent", "CodexSwitcher/1.0")
-                .header("Accept", "application/json")
-                .timeout(std::time::Duration::from_secs(30));
+                .header("Accept", "application/json");
             if let Some(id) = aid {
                 req = req.header("ChatGPT-Account-Id", id);
             }
             req
         };
 
-        let mut response = build_request(&current_token, &account_id).send().await
-            .map_err(|e| format!("网络请求失败: {}", e))?;
+        let mut response = send_with_retry(RetryPolicy::default(), || build_request(&current_token, &account_id)).await?;
 
         let mut status = response.status();
         
-        // 如果 401/403 且有 refresh_token，尝试刷新
-        if (status == 401 || status == 403) && refresh_token.is_some() {
-            if let Some(ref rt) = refresh_token {
-                if let Ok(token_res) = crate::oauth::refresh_access_token(rt).await {
+        // 如果 401/403 且可以刷新，尝试刷新
+        if status == 401 || status == 403 {
+            if let Some(refresh) = refresh {
+                if let Ok(token_res) = refresh().await {
                     current_token = token_res.access_token.clone();
                     new_tokens = Some(token_res);
                     
                     // 重试请求
-                    response = build_request(&current_token, &account_id).send().await
+                    response = send_with_retry(RetryPolicy::default(), || build_request(&current_token, &account_id)).await
                         .map_err(|e| format!("刷新后重试失败: {}", e))?;
                     status = response.status();
                 }
@@ -128,7 +212,7 @@ impl UsageFetcher {
 
         if status == 401 || status == 403 {
             // 如果刷新后仍然 401/403，标记为无效
-            return Err("TOKEN_INVALID:授权已失效，请删除该账号后重新登录".to_string());
+            return Err("TOKEN_INVALID:授权已失效，请对该账号执行「重新登录」".to_string());
         }
 
         let text = response.text().await
@@ -142,63 +226,6 @@ impl UsageFetcher {
         Ok((display, new_tokens))
     }
 
-    /// 从 API 获取用量 (从 auth.json 读取 Token)
-    pub async fn fetch_usage(refresh_token: Option<String>) -> Result<(UsageDisplay, Option<crate::oauth::TokenResponse>), String> {
-        let (mut access_token, account_id) = Self::read_auth()?;
-        let mut new_tokens: Option<crate::oauth::TokenResponse> = None;
-
-        let client = reqwest::Client::new();
-        let build_request = |at: &str, aid: &Option<String>| {
-            let mut req = client
-                .get("https://chatgpt.com/backend-api/wham/usage")
-                .header("Authorization", format!("Bearer {}", at))
-                .header("User-Agent", "CodexSwitcher/1.0")
-                .header("Accept", "application/json")
-                .timeout(std::time::Duration::from_secs(30));
-            if let Some(id) = aid {
-                req = req.header("ChatGPT-Account-Id", id);
-            }
-            req
-        };
-
-        let mut response = build_request(&access_token, &account_id).send().await
-            .map_err(|e| format!("网络请求失败: {}", e))?;
-
-        let mut status = response.status();
-        
-        // 如果 401 且有 refresh_token，尝试刷新
-        if (status == 401 || status == 403) && refresh_token.is_some() {
-            if let Some(ref rt) = refresh_token {
-                if let Ok(token_res) = crate::oauth::refresh_access_token(rt).await {
-                    access_token = token_res.access_token.clone();
-                    new_tokens = Some(token_res);
-                    
-                    // 重试请求
-                    response = build_request(&access_token, &account_id).send().await
-                        .map_err(|e| format!("刷新后重试失败: {}", e))?;
-                    status = response.status();
-                }
-            }
-        }
-
-        if status == 401 || status == 403 {
-            return Err("认证失败，请重新登录 Codex".to_string());
-        }
-
-        if !status.is_success() {
-            return Err(format!("API 返回错误: {}", status));
-        }
-
-        let text = response.text().await
-            .map_err(|e| format!("读取响应失败: {}", e))?;
-
-        let json: Value = serde_json::from_str(&text)
-            .map_err(|e| format!("解析 JSON 失败: {}", e))?;
-
-        let display = Self::parse_usage_response(&json)?;
-        Ok((display, new_tokens))
-    }
-
     /// 从 Value 解析用量数据
     fn parse_usage_response(json: &Value) -> Result<UsageDisplay, String> {
         let plan_type = json.get("plan_type")
@@ -236,6 +263,8 @@ impl UsageFetcher {
             .and_then(|v| v.as_bool())
             .unwrap_or(true);
 
+        let windows = Self::parse_all_windows(json);
+
         Ok(UsageDisplay {
             plan_type,
             five_hour_used,
@@ -249,9 +278,102 @@ impl UsageFetcher {
             credits_balance,
             has_credits: has_credits || unlimited,
             is_valid_for_cli: true, // 能走到这里说明 API 请求成功，Token 是有效的
+            windows,
+            raw: json.clone(),
+            offline: false,
+            cached_at: None,
         })
     }
 
+    /// 收集响应中的所有限流窗口
+    ///
+    /// 依次处理 `rate_limit`（主限额）、顶层其他 `*_rate_limit` 对象
+    /// 以及 `additional_rate_limits` 数组，后端新增的限额无需改代码即可展示
+    fn parse_all_windows(json: &Value) -> Vec<RateWindow> {
+        let mut windows = Vec::new();
+
+        if let Some(rate_limit) = json.get("rate_limit") {
+            Self::collect_bucket("codex", rate_limit, &mut windows);
+        }
+
+        if let Some(obj) = json.as_object() {
+            for (key, value) in obj {
+                if key != "rate_limit" && key.ends_with("rate_limit") && value.is_object() {
+                    let bucket = key.trim_end_matches("rate_limit").trim_end_matches('_');
+                    Self::collect_bucket(bucket, value, &mut windows);
+                }
+            }
+        }
+
+        if let Some(extra) = json.get("additional_rate_limits").and_then(|v| v.as_array()) {
+            for (i, item) in extra.iter().enumerate() {
+                let bucket = item.get("limit_name")
+                    .or_else(|| item.get("metered_feature"))
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| format!("extra_{}", i));
+                let rate_limit = item.get("rate_limit").unwrap_or(item);
+                Self::collect_bucket(&bucket, rate_limit, &mut windows);
+            }
+        }
+
+        windows
+    }
+
+    /// 解析一个限额桶中的全部窗口 (primary_window 优先，其余按字段名排序)
+    fn collect_bucket(bucket: &str, rate_limit: &Value, out: &mut Vec<RateWindow>) {
+        let obj = match rate_limit.as_object() {
+            Some(o) => o,
+            None => return,
+        };
+
+        let mut keys: Vec<&String> = obj.keys()
+            .filter(|k| obj[k.as_str()].get("used_percent").is_some())
+            .collect();
+        keys.sort_by_key(|k| match k.as_str() {
+            "primary_window" => (0, k.to_string()),
+            "secondary_window" => (1, k.to_string()),
+            _ => (2, k.to_string()),
+        });
+
+        for key in keys {
+            let window = &obj[key.as_str()];
+            let window_seconds = window.get("limit_window_seconds")
+                .or_else(|| window.get("window_seconds"))
+                .and_then(Self::parse_number)
+                .map(|v| v as i64)
+                .filter(|v| *v > 0);
+            let used_percent = window.get("used_percent")
+                .and_then(Self::parse_number)
+                .map(|v| v.round() as i32)
+                .unwrap_or(0);
+            let (_, reset, reset_at) = Self::parse_window(Some(window));
+
+            out.push(RateWindow {
+                bucket: bucket.to_string(),
+                name: Self::window_name(key, window_seconds),
+                window_seconds,
+                used_percent,
+                left_percent: 100 - used_percent,
+                reset,
+                reset_at,
+            });
+        }
+    }
+
+    /// 根据窗口长度生成名称
+    fn window_name(key: &str, window_seconds: Option<i64>) -> String {
+        match window_seconds {
+            Some(604_800) => "weekly".to_string(),
+            Some(86_400) => "daily".to_string(),
+            Some(s) if s % 86_400 == 0 => format!("{}d", s / 86_400),
+            Some(s) if s % 3600 == 0 => format!("{}h", s / 3600),
+            Some(s) if s % 60 == 0 => format!("{}m", s / 60),
+            Some(s) => format!("{}s", s),
+            None => key.trim_end_matches("_window").to_string(),
+        }
+    }
+
     /// 解析窗口数据
     fn parse_window(window: Option<&Value>) -> (i32, String, Option<i64>) {
         let window = match window {
@@ -281,8 +403,8 @@ impl UsageFetcher {
                 .and_then(|v| Self::parse_int(v))
                 .unwrap_or(0);
             if reset_after > 0 {
-                use chrono::Utc;
-                final_reset_ts = Some(Utc::now().timestamp() + reset_after as i64);
+                // 与服务端给出的 reset_at 统一为服务端时间
+                final_reset_ts = Some(crate::clock::now().timestamp() + reset_after as i64);
                 Self::format_duration(reset_after as i64)
             } else {
                 "未知".to_string()
@@ -311,7 +433,7 @@ impl UsageFetcher {
     }
 
     /// 格式化重置时间（时间戳）
-    fn format_reset(reset_at: i64) -> String {
+    pub(crate) fn format_reset(reset_at: i64) -> String {
         use chrono::{TimeZone, Utc};
 
         if reset_at == 0 {
@@ -321,7 +443,8 @@ impl UsageFetcher {
         let reset_time = Utc.timestamp_opt(reset_at, 0)
             .single()
             .unwrap_or_else(Utc::now);
-        let now = Utc::now();
+        // reset_at 为服务端时间
+        let now = crate::clock::now();
 
         let duration = reset_time.signed_duration_since(now);
         Self::format_chrono_duration(duration)
@@ -361,3 +484,58 @@ impl UsageFetcher {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_windows_with_extra_buckets() {
+        let json = serde_json::json!({
+            "plan_type": "plus",
+            "rate_limit": {
+                "allowed": true,
+                "primary_window": { "used_percent": 20, "limit_window_seconds": 18000, "reset_after_seconds": 600 },
+                "secondary_window": { "used_percent": 45.6, "limit_window_seconds": 604800, "reset_after_seconds": 86400 }
+            },
+            "code_review_rate_limit": {
+                "primary_window": { "used_percent": 5, "limit_window_seconds": 604800, "reset_after_seconds": 3600 }
+            },
+            "additional_rate_limits": [
+                { "limit_name": "gpt-5-codex-mini", "rate_limit": {
+                    "primary_window": { "used_percent": 70, "limit_window_seconds": 3600, "reset_after_seconds": 60 }
+                } }
+            ]
+        });
+
+        let usage = UsageFetcher::parse_usage_response(&json).unwrap();
+        assert_eq!(usage.five_hour_left, 80);
+        assert_eq!(usage.windows.len(), 4);
+
+        let names: Vec<(&str, &str)> = usage.windows.iter()
+            .map(|w| (w.bucket.as_str(), w.name.as_str()))
+            .collect();
+        assert_eq!(names, vec![
+            ("codex", "5h"),
+            ("codex", "weekly"),
+            ("code_review", "weekly"),
+            ("gpt-5-codex-mini", "1h"),
+        ]);
+        assert_eq!(usage.windows[1].used_percent, 46);
+        assert_eq!(usage.windows[3].window_seconds, Some(3600));
+        assert_eq!(usage.raw, json);
+    }
+
+    #[test]
+    fn test_parse_window_without_seconds_uses_key() {
+        let json = serde_json::json!({
+            "rate_limit": { "burst_window": { "used_percent": "10" } }
+        });
+
+        let usage = UsageFetcher::parse_usage_response(&json).unwrap();
+        assert_eq!(usage.windows.len(), 1);
+        assert_eq!(usage.windows[0].name, "burst");
+        assert_eq!(usage.windows[0].window_seconds, None);
+        assert_eq!(usage.windows[0].left_percent, 90);
+    }
+}
@@ -0,0 +1,219 @@
+//! Codex Switcher - 工作区选择
+//!
+//! 同一 OpenAI 用户可能同时属于个人套餐和 Team 工作区。登录或导入后从 ChatGPT 账号列表中
+//! 列出可用工作区，每个工作区单独授权一次 (授权地址带 allowed_workspace_id)，得到绑定该
+//! 工作区的令牌后保存为一个账号条目。auth.json 的 account_id (即请求头 ChatGPT-Account-Id)
+//! 始终取自令牌本身的 chatgpt_account_id，不在本地改写
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::State;
+
+use crate::account::{Account, AccountStore};
+use crate::resilience::{send_with_retry, RetryPolicy};
+use crate::{claims, oauth, AppState};
+
+/// 可选的工作区
+#[derive(Debug, Clone, Serialize)]
+pub struct Workspace {
+    /// ChatGPT 账号 ID (令牌的 chatgpt_account_id)
+    pub id: String,
+    pub title: Option<String>,
+    pub role: Option<String>,
+    pub plan_type: Option<String>,
+    /// 来源账号的令牌所绑定的工作区
+    pub is_default: bool,
+    /// 已保存该工作区的本地账号 ID
+    pub saved_account_id: Option<String>,
+}
+
+/// 为指定工作区发起的登录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceTarget {
+    pub id: String,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+fn account_email(account: &Account) -> Option<String> {
+    claims::inspect(account, chrono::Utc::now()).email
+}
+
+fn workspace_of(account: &Account) -> Option<&str> {
+    account.auth_json
+        .get("tokens")
+        .and_then(|t| t.get("account_id"))
+        .and_then(|v| v.as_str())
+}
+
+/// 同一用户在指定工作区下已保存的账号
+pub fn find_saved(store: &AccountStore, email: Option<&str>, workspace_id: &str) -> Option<String> {
+    store.accounts.values()
+        .find(|a| {
+            workspace_of(a) == Some(workspace_id)
+                && match (email, account_email(a)) {
+                    (Some(e), Some(other)) => e.eq_ignore_ascii_case(&other),
+                    _ => false,
+                }
+        })
+        .map(|a| a.id.clone())
+}
+
+/// 解析 ChatGPT 账号列表 (accounts 为以账号 ID 为键的对象，或数组)
+fn parse_accounts(body: &serde_json::Value) -> Vec<Workspace> {
+    let entries: Vec<(Option<&str>, &serde_json::Value)> = match body.get("accounts") {
+        Some(serde_json::Value::Object(map)) => map.iter().map(|(k, v)| (Some(k.as_str()), v)).collect(),
+        Some(serde_json::Value::Array(list)) => list.iter().map(|v| (None, v)).collect(),
+        _ => Vec::new(),
+    };
+    let text = |v: &serde_json::Value, key: &str| v.get(key).and_then(|x| x.as_str()).map(str::to_string);
+
+    let mut workspaces: Vec<Workspace> = entries.into_iter()
+        .filter_map(|(key, entry)| {
+            let account = entry.get("account").unwrap_or(entry);
+            let id = text(account, "account_id").or_else(|| key.map(str::to_string))?;
+            // "default" 是服务端的占位条目，不对应实际账号
+            if id == "default" {
+                return None;
+            }
+            Some(Workspace {
+                id,
+                title: text(account, "name"),
+                role: text(account, "account_user_role"),
+                plan_type: text(account, "plan_type"),
+                is_default: false,
+                saved_account_id: None,
+            })
+        })
+        .collect();
+
+    // 按服务端给出的顺序排列
+    if let Some(order) = body.get("account_ordering").and_then(|v| v.as_array()) {
+        let rank = |id: &str| order.iter().position(|o| o.as_str() == Some(id)).unwrap_or(usize::MAX);
+        workspaces.sort_by_key(|w| rank(&w.id));
+    }
+    workspaces
+}
+
+/// 用账号的 access_token 获取其可访问的 ChatGPT 账号 (工作区)
+async fn fetch_workspaces(access_token: &str, account_id: Option<&str>) -> Result<Vec<Workspace>, String> {
+    let client = crate::http_client::client();
+    let url = crate::endpoints::current().accounts_url;
+    let response = send_with_retry(RetryPolicy::default(), || {
+        let mut req = client
+            .get(&url)
+            .header("Authorization", format!("Bearer {}", access_token))
+            .header("User-Agent", "CodexSwitcher/1.0")
+            .header("Accept", "application/json");
+        if let Some(id) = account_id {
+            req = req.header("ChatGPT-Account-Id", id);
+        }
+        req
+    })
+    .await
+    .map_err(|e| format!("获取工作区列表失败: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("获取工作区列表失败 (HTTP {}): {}", status.as_u16(), body));
+    }
+    let body: serde_json::Value = response.json()
+        .await
+        .map_err(|e| format!("解析工作区列表失败: {}", e))?;
+    Ok(parse_accounts(&body))
+}
+
+/// 列出账号可用的工作区
+pub async fn list_workspaces(store: &Mutex<AccountStore>, id: &str) -> Result<Vec<Workspace>, String> {
+    let (access_token, current_id) = {
+        let store = store.lock().map_err(|e| e.to_string())?;
+        let account = store.accounts.get(id)
+            .ok_or_else(|| format!("账号 {} 不存在", id))?;
+        let access_token = account.auth_json
+            .get("tokens")
+            .and_then(|t| t.get("access_token"))
+            .and_then(|v| v.as_str())
+            .ok_or("账号数据缺少 access_token")?
+            .to_string();
+        (access_token, workspace_of(account).map(str::to_string))
+    };
+
+    let mut workspaces = fetch_workspaces(&access_token, current_id.as_deref()).await?;
+
+    let store = store.lock().map_err(|e| e.to_string())?;
+    let email = store.accounts.get(id).and_then(account_email);
+    for workspace in &mut workspaces {
+        workspace.is_default = current_id.as_deref() == Some(workspace.id.as_str());
+        workspace.saved_account_id = find_saved(&store, email.as_deref(), &workspace.id);
+    }
+    Ok(workspaces)
+}
+
+/// 保存为指定工作区授权得到的令牌
+///
+/// 令牌必须绑定所选工作区；同一用户已保存该工作区时更新原条目，否则新增条目
+pub async fn save_workspace_login(
+    store: &Mutex<AccountStore>,
+    workspace: &WorkspaceTarget,
+    token_res: oauth::TokenResponse,
+) -> Result<Account, String> {
+    let id_token = token_res.id_token.as_deref()
+        .ok_or("无法从授权响应中解析用户信息 (Missing ID Token)")?;
+    let user_info = oauth::verified_user_info(id_token, false).await?;
+    if user_info.account_id.as_deref() != Some(workspace.id.as_str()) {
+        return Err(format!(
+            "授权得到的工作区 ({}) 与所选工作区 ({}) 不一致，请在授权页选择对应的工作区",
+            user_info.account_id.as_deref().unwrap_or("无"),
+            workspace.id
+        ));
+    }
+
+    let saved = {
+        let store = store.lock().map_err(|e| e.to_string())?;
+        find_saved(&store, Some(&user_info.email), &workspace.id)
+    };
+    if let Some(saved) = saved {
+        return crate::relogin_account(store, &saved, token_res).await;
+    }
+
+    let account = crate::save_oauth_account(store, token_res).await?;
+    let label = workspace.title.clone().unwrap_or_else(|| workspace.id.clone());
+    let mut store = store.lock().map_err(|e| e.to_string())?;
+    store.update_account(
+        &account.id,
+        Some(format!("{} ({})", user_info.email, label)),
+        Some(format!("工作区: {}", label)),
+    )?;
+    store.save()?;
+    Ok(store.accounts[&account.id].clone())
+}
+
+/// 修正旧版本在本地改写过的 account_id，使其与令牌绑定的工作区一致，返回修正的条目数
+pub fn repair_account_ids(store: &mut AccountStore) -> usize {
+    let mut repaired = 0;
+    for account in store.accounts.values_mut() {
+        let bound = account.auth_json
+            .get("tokens")
+            .and_then(|t| t.get("id_token"))
+            .and_then(|v| v.as_str())
+            .and_then(oauth::parse_user_info)
+            .and_then(|u| u.account_id);
+        let Some(bound) = bound else { continue };
+        if workspace_of(account) == Some(bound.as_str()) {
+            continue;
+        }
+        if let Some(tokens) = account.auth_json.get_mut("tokens").and_then(|t| t.as_object_mut()) {
+            println!("[Workspace] 账号 {} 的 account_id 与令牌不一致，已恢复为 {}", account.name, bound);
+            tokens.insert("account_id".to_string(), serde_json::json!(bound));
+            repaired += 1;
+        }
+    }
+    repaired
+}
+
+/// 列出账号可用的工作区
+#[tauri::command]
+pub async fn list_account_workspaces(state: State<'_, AppState>, id: String) -> Result<Vec<Workspace>, String> {
+    list_workspaces(&state.store, &id).await
+}
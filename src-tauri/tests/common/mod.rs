@@ -0,0 +1,383 @@
+//! 集成测试共用的本地替身服务
+//!
+//! 模拟 OAuth 令牌端点 (带 refresh_token 轮换)、JWKS 和用量端点
+
+#![allow(dead_code)]
+
+use base64::{engine::general_purpose, Engine as _};
+use ring::rand::SystemRandom;
+use ring::signature::{RsaKeyPair, RsaPublicKeyComponents, RSA_PKCS1_SHA256};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// 环境变量和全局端点配置是进程级的，测试需串行执行
+pub static SERIAL: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// 构造一个未签名的 JWT (签名段为占位符)
+pub fn make_jwt(claims: serde_json::Value) -> String {
+    let header = general_purpose::URL_SAFE_NO_PAD.encode(br#"{"alg":"none","typ":"JWT"}"#);
+    let payload = general_purpose::URL_SAFE_NO_PAD.encode(claims.to_string());
+    format!("{}.{}.sig", header, payload)
+}
+
+/// 测试签名密钥的 kid
+pub const TEST_KID: &str = "test-key";
+
+fn signing_key() -> RsaKeyPair {
+    let pem = include_str!("../fixtures/jwt_test_key.pem");
+    let body: String = pem.lines().filter(|l| !l.starts_with("-----")).collect();
+    let der = general_purpose::STANDARD.decode(body).unwrap();
+    RsaKeyPair::from_pkcs8(&der).unwrap()
+}
+
+/// 测试密钥对应的 JWKS
+pub fn jwks() -> serde_json::Value {
+    let key = signing_key();
+    let public = RsaPublicKeyComponents::<Vec<u8>>::from(key.public());
+    serde_json::json!({
+        "keys": [{
+            "kid": TEST_KID,
+            "kty": "RSA",
+            "alg": "RS256",
+            "use": "sig",
+            "n": general_purpose::URL_SAFE_NO_PAD.encode(&public.n),
+            "e": general_purpose::URL_SAFE_NO_PAD.encode(&public.e),
+        }]
+    })
+}
+
+/// 用测试密钥签名 (RS256) 任意 claims
+pub fn sign_jwt(claims: serde_json::Value) -> String {
+    let key = signing_key();
+    let header = general_purpose::URL_SAFE_NO_PAD.encode(
+        serde_json::json!({ "alg": "RS256", "typ": "JWT", "kid": TEST_KID }).to_string(),
+    );
+    let payload = general_purpose::URL_SAFE_NO_PAD.encode(claims.to_string());
+    let message = format!("{}.{}", header, payload);
+    let mut signature = vec![0u8; key.public().modulus_len()];
+    key.sign(&RSA_PKCS1_SHA256, &SystemRandom::new(), message.as_bytes(), &mut signature).unwrap();
+    format!("{}.{}", message, general_purpose::URL_SAFE_NO_PAD.encode(signature))
+}
+
+/// 生成由 `issuer` 签发给测试客户端、1 小时后过期的 id_token
+pub fn signed_id_token(issuer: &str, email: &str, account_id: &str) -> String {
+    sign_jwt(serde_json::json!({
+        "iss": issuer,
+        "aud": ["app_test"],
+        "exp": chrono::Utc::now().timestamp() + 3600,
+        "iat": chrono::Utc::now().timestamp(),
+        "email": email,
+        "https://api.openai.com/auth": { "chatgpt_account_id": account_id }
+    }))
+}
+
+/// 生成 exp 在 `secs` 秒后的 access_token
+pub fn access_token(tag: &str, secs: i64) -> String {
+    make_jwt(serde_json::json!({
+        "sub": tag,
+        "exp": chrono::Utc::now().timestamp() + secs,
+    }))
+}
+
+/// 替身服务的可变状态
+#[derive(Default)]
+pub struct MockState {
+    /// 仍然有效的 refresh_token
+    pub refresh_tokens: HashSet<String>,
+    /// 用量端点接受的 access_token
+    pub access_tokens: HashSet<String>,
+    /// 用量端点返回的数据
+    pub usage_body: serde_json::Value,
+    /// 已签发令牌计数
+    pub issued: u32,
+    /// 收到的请求 (方法, 路径)
+    pub requests: Vec<(String, String)>,
+    /// 依次注入的失败响应 (状态码, Retry-After)
+    pub failures: VecDeque<(u16, Option<String>)>,
+    /// 设备码轮询依次返回的错误码，用完后签发令牌
+    pub device_script: VecDeque<String>,
+    /// 已撤销的令牌
+    pub revoked: Vec<String>,
+    /// 签发者 (即服务地址)
+    pub issuer: String,
+    /// 用量请求携带的 ChatGPT-Account-Id
+    pub usage_account_ids: Vec<String>,
+    /// 账号列表端点返回的数据
+    pub accounts_body: serde_json::Value,
+}
+
+/// 本地替身服务
+pub struct MockServer {
+    pub base_url: String,
+    pub state: Arc<Mutex<MockState>>,
+}
+
+impl MockServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(MockState {
+            usage_body: default_usage_body(),
+            issuer: base_url.clone(),
+            ..Default::default()
+        }));
+
+        let shared = state.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let shared = shared.clone();
+                tokio::spawn(async move {
+                    let _ = serve(socket, shared).await;
+                });
+            }
+        });
+
+        Self { base_url, state }
+    }
+
+    pub fn token_url(&self) -> String {
+        format!("{}/oauth/token", self.base_url)
+    }
+
+    pub fn usage_url(&self) -> String {
+        format!("{}/backend-api/wham/usage", self.base_url)
+    }
+
+    /// 让应用的所有请求指向本服务 (并重置为在线状态)
+    pub fn install(&self) {
+        codex_switcher_lib::connectivity::report_reachable();
+        codex_switcher_lib::endpoints::configure(codex_switcher_lib::endpoints::EndpointConfig {
+            client_id: "app_test".to_string(),
+            auth_url: format!("{}/oauth/authorize", self.base_url),
+            token_url: self.token_url(),
+            usage_url: self.usage_url(),
+            accounts_url: format!("{}/backend-api/accounts/check/v4-2023-04-27", self.base_url),
+            device_auth_url: format!("{}/api/accounts/deviceauth", self.base_url),
+            revoke_url: format!("{}/oauth/revoke", self.base_url),
+            jwks_url: format!("{}/.well-known/jwks.json", self.base_url),
+            issuer: self.base_url.clone(),
+        });
+    }
+
+    pub fn allow_refresh(&self, token: &str) {
+        self.state.lock().unwrap().refresh_tokens.insert(token.to_string());
+    }
+
+    pub fn allow_access(&self, token: &str) {
+        self.state.lock().unwrap().access_tokens.insert(token.to_string());
+    }
+
+    /// 接下来的请求先返回指定的失败响应
+    pub fn fail_next(&self, status: u16, retry_after: Option<&str>) {
+        self.state.lock().unwrap().failures.push_back((status, retry_after.map(|s| s.to_string())));
+    }
+
+    pub fn count(&self, path: &str) -> usize {
+        self.state.lock().unwrap().requests.iter().filter(|(_, p)| p == path).count()
+    }
+}
+
+pub fn default_usage_body() -> serde_json::Value {
+    serde_json::json!({
+        "plan_type": "plus",
+        "rate_limit": {
+            "allowed": true,
+            "primary_window": { "used_percent": 25, "limit_window_seconds": 18000, "reset_after_seconds": 3600 },
+            "secondary_window": { "used_percent": 60, "limit_window_seconds": 604800, "reset_after_seconds": 86400 }
+        },
+        "credits": { "has_credits": false, "unlimited": false, "balance": "0" }
+    })
+}
+
+/// 为测试准备独立的 CODEX_HOME 和 CODEX_SWITCHER_HOME
+pub fn temp_homes(name: &str) -> (PathBuf, PathBuf) {
+    let root = std::env::temp_dir().join(format!("codex-switcher-it-{}-{}", name, uuid::Uuid::new_v4()));
+    let codex_home = root.join("codex");
+    let switcher_home = root.join("switcher");
+    std::fs::create_dir_all(&codex_home).unwrap();
+    std::fs::create_dir_all(&switcher_home).unwrap();
+    std::env::set_var("CODEX_HOME", &codex_home);
+    std::env::set_var("CODEX_SWITCHER_HOME", &switcher_home);
+    (codex_home, switcher_home)
+}
+
+async fn serve(mut socket: TcpStream, state: Arc<Mutex<MockState>>) -> std::io::Result<()> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        let n = socket.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or("").split_whitespace();
+    let method = request_line.next().unwrap_or("").to_string();
+    let target = request_line.next().unwrap_or("/").to_string();
+    let path = target.split('?').next().unwrap_or("/").to_string();
+
+    let headers: HashMap<String, String> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string()))
+        .collect();
+
+    let content_length: usize = headers.get("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+    while buf.len() < header_end + content_length {
+        let n = socket.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let body = String::from_utf8_lossy(&buf[header_end..]).to_string();
+
+    let (status, payload, retry_after) = {
+        let mut state = state.lock().unwrap();
+        state.requests.push((method.clone(), path.clone()));
+        match state.failures.pop_front() {
+            Some((status, retry_after)) => (status, serde_json::json!({ "error": "injected" }), retry_after),
+            None => {
+                let (status, payload) = route(&mut state, &method, &path, &headers, &body);
+                (status, payload, None)
+            }
+        }
+    };
+
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        429 => "Too Many Requests",
+        503 => "Service Unavailable",
+        _ => "Error",
+    };
+    let extra = retry_after.map(|v| format!("Retry-After: {}\r\n", v)).unwrap_or_default();
+    let text = payload.to_string();
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, reason, extra, text.len(), text
+    );
+    socket.write_all(response.as_bytes()).await?;
+    socket.shutdown().await
+}
+
+fn route(
+    state: &mut MockState,
+    method: &str,
+    path: &str,
+    headers: &HashMap<String, String>,
+    body: &str,
+) -> (u16, serde_json::Value) {
+    match (method, path) {
+        ("POST", "/oauth/token") => {
+            let form: HashMap<String, String> = url::form_urlencoded::parse(body.as_bytes())
+                .into_owned()
+                .collect();
+            match form.get("grant_type").map(|s| s.as_str()) {
+                Some("refresh_token") => {
+                    let rt = form.get("refresh_token").cloned().unwrap_or_default();
+                    // 轮换: 旧 refresh_token 用过即失效
+                    if !state.refresh_tokens.remove(&rt) {
+                        return (400, serde_json::json!({ "error": "invalid_grant" }));
+                    }
+                    issue_tokens(state)
+                }
+                Some("authorization_code") => {
+                    // 设备码授权得到的授权码只能配合其 verifier 和 deviceauth 回调地址使用
+                    let device_code = form.get("code").map(|s| s.as_str()) == Some("device-auth-code");
+                    let device_exchange = form.get("code_verifier").map(|s| s.as_str()) == Some("device-verifier")
+                        && form.get("redirect_uri").is_some_and(|u| u.ends_with("/deviceauth/callback"));
+                    if device_code && !device_exchange {
+                        return (400, serde_json::json!({ "error": "invalid_grant" }));
+                    }
+                    issue_tokens(state)
+                }
+                _ => (400, serde_json::json!({ "error": "unsupported_grant_type" })),
+            }
+        }
+        ("POST", "/oauth/revoke") => {
+            let form: HashMap<String, String> = url::form_urlencoded::parse(body.as_bytes())
+                .into_owned()
+                .collect();
+            let token = form.get("token").cloned().unwrap_or_default();
+            state.refresh_tokens.remove(&token);
+            state.access_tokens.remove(&token);
+            state.revoked.push(token);
+            (200, serde_json::json!({}))
+        }
+        ("GET", "/.well-known/jwks.json") => (200, jwks()),
+        ("POST", "/api/accounts/deviceauth/usercode") => (200, serde_json::json!({
+            "device_auth_id": "dev-1",
+            "usercode": "ABCD-EFGH",
+            "interval": "1"
+        })),
+        ("POST", "/api/accounts/deviceauth/token") => {
+            let poll: serde_json::Value = serde_json::from_str(body).unwrap_or_default();
+            if poll["device_auth_id"] != "dev-1" || poll["user_code"] != "ABCD-EFGH" {
+                return (400, serde_json::json!({ "error": "invalid_request" }));
+            }
+            // 脚本条目: "pending" 对应 404，"slow_down" 对应 429，其余作为错误码返回
+            match state.device_script.pop_front().as_deref() {
+                Some("pending") => (404, serde_json::json!({ "error": "authorization_pending" })),
+                Some("slow_down") => (429, serde_json::json!({ "error": "slow_down" })),
+                Some(error) => (400, serde_json::json!({ "error": error })),
+                None => (200, serde_json::json!({
+                    "authorization_code": "device-auth-code",
+                    "code_challenge": "challenge",
+                    "code_verifier": "device-verifier"
+                })),
+            }
+        }
+        ("GET", "/backend-api/accounts/check/v4-2023-04-27") => {
+            let token = headers
+                .get("authorization")
+                .and_then(|v| v.strip_prefix("Bearer "))
+                .unwrap_or("");
+            if state.access_tokens.contains(token) {
+                (200, state.accounts_body.clone())
+            } else {
+                (401, serde_json::json!({ "detail": "Unauthorized" }))
+            }
+        }
+        ("GET", "/backend-api/wham/usage") => {
+            if let Some(account_id) = headers.get("chatgpt-account-id") {
+                state.usage_account_ids.push(account_id.clone());
+            }
+            let token = headers
+                .get("authorization")
+                .and_then(|v| v.strip_prefix("Bearer "))
+                .unwrap_or("");
+            if state.access_tokens.contains(token) {
+                (200, state.usage_body.clone())
+            } else {
+                (401, serde_json::json!({ "detail": "Unauthorized" }))
+            }
+        }
+        _ => (404, serde_json::json!({ "error": "not_found" })),
+    }
+}
+
+fn issue_tokens(state: &mut MockState) -> (u16, serde_json::Value) {
+    state.issued += 1;
+    let n = state.issued;
+    let access = access_token(&format!("at-{}", n), 3600);
+    let refresh = format!("rt-{}", n);
+    let id_token = signed_id_token(&state.issuer, "mock@example.com", "acct-mock");
+    state.access_tokens.insert(access.clone());
+    state.refresh_tokens.insert(refresh.clone());
+    (200, serde_json::json!({
+        "access_token": access,
+        "refresh_token": refresh,
+        "id_token": id_token,
+        "expires_in": 3600
+    }))
+}
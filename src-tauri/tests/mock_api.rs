@@ -0,0 +1,940 @@
+//! 针对本地替身服务的切换、刷新与用量流程测试
+
+mod common;
+
+use codex_switcher_lib::account::AccountStore;
+use codex_switcher_lib::connectivity;
+use codex_switcher_lib::jobs::{check_drift, DriftOutcome};
+use codex_switcher_lib::http_client::{build_client, test_connection, NetworkSettings};
+use codex_switcher_lib::account::{ImportRecord, ImportStatus, RevokeOutcome};
+use codex_switcher_lib::resilience::{breaker_statuses, BreakerState};
+use codex_switcher_lib::refresh_health::RefreshErrorClass;
+use codex_switcher_lib::revocation::delete_accounts_inner;
+use codex_switcher_lib::schedule::{apply_inner, save_rules_inner, ScheduleOutcome, SwitchRule};
+use codex_switcher_lib::store::{self, StoreService};
+use codex_switcher_lib::token_refresh;
+use codex_switcher_lib::workspace::{list_workspaces, repair_account_ids, save_workspace_login, WorkspaceTarget};
+use codex_switcher_lib::{get_quota_inner, import_accounts_inner, oauth, refresh_account_tokens, relogin_account, save_oauth_account, switch_account_inner, verify_auth_json, IdentityCheck};
+use common::{access_token, make_jwt, sign_jwt, signed_id_token, temp_homes, MockServer, SERIAL};
+use std::sync::Mutex;
+
+fn store_with_account(access: &str, refresh: &str) -> (Mutex<AccountStore>, String) {
+    let mut store = AccountStore::default();
+    let mut account = store.add_account(
+        "mock".to_string(),
+        serde_json::json!({
+            "tokens": {
+                "access_token": access,
+                "refresh_token": refresh,
+                "account_id": "acct-mock"
+            }
+        }),
+        None,
+    );
+    account.refresh_token = Some(refresh.to_string());
+    store.accounts.insert(account.id.clone(), account.clone());
+    (Mutex::new(store), account.id)
+}
+
+#[tokio::test]
+async fn refresh_rotates_and_rejects_reused_token() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    server.allow_refresh("rt-initial");
+
+    let first = oauth::refresh_access_token("rt-initial").await.unwrap();
+    assert_eq!(first.refresh_token.as_deref(), Some("rt-1"));
+
+    // 旧 refresh_token 已被轮换，再用一次会被拒绝
+    let err = oauth::refresh_access_token("rt-initial").await.unwrap_err();
+    assert!(err.contains("invalid_grant"), "{}", err);
+
+    let second = oauth::refresh_access_token("rt-1").await.unwrap();
+    assert_eq!(second.refresh_token.as_deref(), Some("rt-2"));
+}
+
+#[tokio::test]
+async fn switch_refreshes_expired_token_and_writes_auth_json() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    let (codex_home, switcher_home) = temp_homes("switch");
+    server.allow_refresh("rt-old");
+
+    let (store, id) = store_with_account(&access_token("expired", -60), "rt-old");
+    switch_account_inner(&store, &id).await.unwrap();
+
+    assert_eq!(server.count("/oauth/token"), 1);
+
+    let auth: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(codex_home.join("auth.json")).unwrap(),
+    ).unwrap();
+    assert_eq!(auth["tokens"]["refresh_token"], "rt-1");
+    assert!(auth.get("last_refresh").is_some());
+
+    let store = store.lock().unwrap();
+    assert_eq!(store.current.as_deref(), Some(id.as_str()));
+    assert_eq!(store.accounts[&id].refresh_token.as_deref(), Some("rt-1"));
+    assert!(switcher_home.join("accounts.json").exists());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_refreshes_share_one_rotation() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    let (codex_home, _) = temp_homes("single-flight");
+    server.allow_refresh("rt-race");
+
+    // access_token 已过期且不被用量端点接受: 切换、查询用量、补刷都会触发刷新
+    let (store, id) = store_with_account(&access_token("stale", -60), "rt-race");
+    let (a, b, c, d) = tokio::join!(
+        refresh_account_tokens(&store, &id),
+        switch_account_inner(&store, &id),
+        get_quota_inner(&store, &id),
+        refresh_account_tokens(&store, &id),
+    );
+    a.unwrap();
+    b.unwrap();
+    c.unwrap();
+    d.unwrap();
+
+    // 只发生一次轮换，所有调用者拿到同一组新令牌
+    assert_eq!(server.count("/oauth/token"), 1);
+    let s = store.lock().unwrap();
+    assert_eq!(s.accounts[&id].refresh_token.as_deref(), Some("rt-1"));
+    assert_eq!(s.accounts[&id].auth_json["tokens"]["refresh_token"], "rt-1");
+    let auth: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(codex_home.join("auth.json")).unwrap()).unwrap();
+    assert_eq!(auth["tokens"]["refresh_token"], "rt-1");
+    assert!(server.state.lock().unwrap().refresh_tokens.contains("rt-1"));
+}
+
+#[tokio::test]
+async fn background_refresh_of_current_account_updates_auth_json() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    let (codex_home, _) = temp_homes("refresh-current");
+    server.allow_refresh("rt-current");
+    server.allow_refresh("rt-other");
+
+    let (store, id) = store_with_account(&access_token("current", 60), "rt-current");
+    let other = {
+        let mut s = store.lock().unwrap();
+        let mut account = s.add_account(
+            "other".to_string(),
+            serde_json::json!({ "tokens": { "access_token": "at-other", "refresh_token": "rt-other", "account_id": "acct-other" } }),
+            None,
+        );
+        account.refresh_token = Some("rt-other".to_string());
+        s.accounts.insert(account.id.clone(), account.clone());
+        s.current = Some(id.clone());
+        AccountStore::write_codex_auth(&s.accounts[&id].auth_json).unwrap();
+        account.id
+    };
+    let read_auth = || -> serde_json::Value {
+        serde_json::from_str(&std::fs::read_to_string(codex_home.join("auth.json")).unwrap()).unwrap()
+    };
+
+    // 非当前账号的刷新不改动 auth.json
+    token_refresh::refresh_current(&store, &other).await.unwrap();
+    assert_eq!(read_auth()["tokens"]["refresh_token"], "rt-current");
+
+    // 当前账号轮换后 auth.json 随之更新，Codex CLI 不会拿到已作废的 refresh_token
+    let token_res = token_refresh::refresh_current(&store, &id).await.unwrap();
+    let auth = read_auth();
+    assert_eq!(auth["tokens"]["refresh_token"], token_res.refresh_token.unwrap().as_str());
+    assert_eq!(auth["tokens"]["account_id"], "acct-mock");
+}
+
+#[tokio::test]
+async fn store_service_shares_refreshes_and_broadcasts_changes() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    let (_, switcher_home) = temp_homes("store-service");
+    server.allow_refresh("rt-shared");
+
+    let (store, id) = store_with_account(&access_token("stale", -60), "rt-shared");
+    let app_store = StoreService::from_store(store.into_inner().unwrap());
+    let scheduler_store = app_store.clone();
+    let mut changes = store::subscribe();
+
+    // 调度器一侧刷新，应用一侧立即可见，且不会被应用随后的保存覆盖
+    refresh_account_tokens(&scheduler_store, &id).await.unwrap();
+    assert_eq!(app_store.snapshot().unwrap().accounts[&id].refresh_token.as_deref(), Some("rt-1"));
+    app_store.update(|s| {
+        s.accounts.get_mut(&id).unwrap().notes = Some("edited".to_string());
+        Ok(())
+    }).unwrap();
+
+    let first = changes.recv().await.unwrap();
+    let second = changes.recv().await.unwrap();
+    assert!(second.revision > first.revision);
+
+    let on_disk: AccountStore = serde_json::from_str(
+        &std::fs::read_to_string(switcher_home.join("accounts.json")).unwrap(),
+    ).unwrap();
+    assert_eq!(on_disk.accounts[&id].refresh_token.as_deref(), Some("rt-1"));
+    assert_eq!(on_disk.accounts[&id].notes.as_deref(), Some("edited"));
+}
+
+#[tokio::test]
+async fn switch_keeps_valid_token_without_refresh() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    temp_homes("switch-valid");
+
+    let (store, id) = store_with_account(&access_token("fresh", 3600), "rt-unused");
+    switch_account_inner(&store, &id).await.unwrap();
+
+    assert_eq!(server.count("/oauth/token"), 0);
+}
+
+#[tokio::test]
+async fn quota_refreshes_on_401_and_caches_result() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    temp_homes("quota");
+    server.allow_refresh("rt-old");
+
+    // access_token 未过期但已被服务端吊销
+    let (store, id) = store_with_account(&access_token("revoked", 3600), "rt-old");
+    let usage = get_quota_inner(&store, &id).await.unwrap();
+
+    assert_eq!(usage.five_hour_left, 75);
+    assert_eq!(usage.weekly_left, 40);
+    assert_eq!(usage.windows.len(), 2);
+    assert_eq!(server.count("/backend-api/wham/usage"), 2);
+
+    let store = store.lock().unwrap();
+    let account = &store.accounts[&id];
+    assert_eq!(account.refresh_token.as_deref(), Some("rt-1"));
+    assert_eq!(account.cached_quota.as_ref().unwrap().weekly_left, 40.0);
+}
+
+#[tokio::test]
+async fn quota_reports_invalid_when_refresh_is_rejected() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    temp_homes("quota-invalid");
+
+    let (store, id) = store_with_account(&access_token("revoked", 3600), "rt-revoked");
+    let err = get_quota_inner(&store, &id).await.unwrap_err();
+
+    assert!(err.starts_with("TOKEN_INVALID"), "{}", err);
+}
+
+#[tokio::test]
+async fn test_connection_goes_through_configured_proxy() {
+    let _guard = SERIAL.lock().await;
+    let proxy = MockServer::start().await;
+    codex_switcher_lib::endpoints::configure(codex_switcher_lib::endpoints::EndpointConfig {
+        token_url: "http://auth.unreachable.invalid/oauth/token".to_string(),
+        usage_url: "http://usage.unreachable.invalid/backend-api/wham/usage".to_string(),
+        ..Default::default()
+    });
+
+    let settings = NetworkSettings {
+        proxy_url: Some(proxy.base_url.clone()),
+        ..Default::default()
+    };
+    let checks = test_connection(Some(settings)).await.unwrap();
+
+    assert_eq!(checks.len(), 2);
+    assert!(checks.iter().all(|c| c.ok), "{:?}", checks);
+    assert_eq!(proxy.state.lock().unwrap().requests.len(), 2);
+}
+
+#[tokio::test]
+async fn invalid_network_settings_are_rejected() {
+    let settings = NetworkSettings {
+        ca_cert_paths: vec!["/nonexistent/ca.pem".to_string()],
+        ..Default::default()
+    };
+    assert!(build_client(&settings).is_err());
+}
+
+#[tokio::test]
+async fn refresh_retries_only_when_server_asks_to() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    server.allow_refresh("rt-flaky");
+    server.fail_next(503, Some("0"));
+    server.fail_next(429, Some("0"));
+
+    let res = oauth::refresh_access_token("rt-flaky").await.unwrap();
+    assert_eq!(res.refresh_token.as_deref(), Some("rt-1"));
+    assert_eq!(server.count("/oauth/token"), 3);
+
+    // 普通 5xx 时服务端可能已轮换令牌，不能重发
+    server.fail_next(500, None);
+    let err = oauth::refresh_access_token("rt-1").await.unwrap_err();
+    assert!(err.contains("HTTP 500"), "{}", err);
+    assert_eq!(server.count("/oauth/token"), 4);
+
+    let host = server.base_url.trim_start_matches("http://").to_string();
+    let status = breaker_statuses().into_iter().find(|b| b.host == host).unwrap();
+    assert_eq!(status.state, BreakerState::Closed);
+    assert_eq!(status.total_failures, 3);
+}
+
+#[tokio::test]
+async fn breaker_opens_after_repeated_failures() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    for _ in 0..5 {
+        server.fail_next(500, None);
+    }
+
+    // 刷新请求不重放，每次只请求一次，五次失败后达到熔断阈值
+    for _ in 0..5 {
+        assert!(oauth::refresh_access_token("rt-any").await.is_err());
+    }
+    let requests = server.count("/oauth/token");
+
+    let err = oauth::refresh_access_token("rt-any").await.unwrap_err();
+    assert!(err.contains("CIRCUIT_OPEN"), "{}", err);
+    assert_eq!(server.count("/oauth/token"), requests);
+}
+
+#[tokio::test]
+async fn offline_quota_serves_cache_and_switch_uses_valid_token() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    temp_homes("offline");
+    server.allow_refresh("rt-old");
+
+    // 先在线拉取一次，生成缓存
+    let access = access_token("valid", 3600);
+    server.allow_access(&access);
+    let (store, id) = store_with_account(&access, "rt-old");
+    get_quota_inner(&store, &id).await.unwrap();
+
+    // 指向一个已关闭的端口，模拟断网
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let dead = format!("http://{}", closed.local_addr().unwrap());
+    drop(closed);
+    codex_switcher_lib::endpoints::configure(codex_switcher_lib::endpoints::EndpointConfig {
+        token_url: format!("{}/oauth/token", dead),
+        usage_url: format!("{}/backend-api/wham/usage", dead),
+        ..Default::default()
+    });
+
+    let usage = get_quota_inner(&store, &id).await.unwrap();
+    assert!(usage.offline);
+    assert!(usage.cached_at.is_some());
+    assert_eq!(usage.five_hour_left, 75);
+    assert!(!connectivity::is_online());
+
+    // 离线时 Token 仍有效，可以切换；已过期则拒绝
+    switch_account_inner(&store, &id).await.unwrap();
+    {
+        let mut s = store.lock().unwrap();
+        let account = s.accounts.get_mut(&id).unwrap();
+        account.auth_json["tokens"]["access_token"] = serde_json::json!(access_token("expired", -60));
+    }
+    let err = switch_account_inner(&store, &id).await.unwrap_err();
+    assert!(err.starts_with("OFFLINE"), "{}", err);
+
+    // 没有缓存的账号离线时报错
+    {
+        let mut s = store.lock().unwrap();
+        s.accounts.get_mut(&id).unwrap().cached_quota = None;
+    }
+    assert!(get_quota_inner(&store, &id).await.unwrap_err().starts_with("OFFLINE"));
+
+    server.install();
+    assert!(connectivity::probe().await);
+}
+
+#[tokio::test]
+async fn device_code_polling_handles_pending_and_slow_down() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    {
+        let mut state = server.state.lock().unwrap();
+        state.device_script.push_back("pending".to_string());
+        state.device_script.push_back("slow_down".to_string());
+    }
+
+    let device = oauth::request_device_code().await.unwrap();
+    assert_eq!(device.user_code, "ABCD-EFGH");
+    assert_eq!(device.interval, 1);
+    assert_eq!(oauth::device_verification_url(), format!("{}/codex/device", server.base_url));
+
+    assert!(matches!(oauth::poll_device_token(&device).await.unwrap(), oauth::DevicePoll::Pending));
+    assert!(matches!(oauth::poll_device_token(&device).await.unwrap(), oauth::DevicePoll::SlowDown));
+    let (code, verifier) = match oauth::poll_device_token(&device).await.unwrap() {
+        oauth::DevicePoll::Authorized { authorization_code, code_verifier } => (authorization_code, code_verifier),
+        other => panic!("unexpected poll result: {:?}", other),
+    };
+    // 授权码只能配合服务端下发的 verifier 换取令牌
+    assert!(oauth::exchange_device_code(&code, "other-verifier").await.is_err());
+    let tokens = oauth::exchange_device_code(&code, &verifier).await.unwrap();
+    let info = oauth::parse_user_info(tokens.id_token.as_deref().unwrap()).unwrap();
+    assert_eq!(info.email, "mock@example.com");
+}
+
+#[tokio::test]
+async fn device_code_polling_stops_on_rejection() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    let device = oauth::request_device_code().await.unwrap();
+
+    for error in ["access_denied", "expired_token", "invalid_request"] {
+        server.state.lock().unwrap().device_script.push_back(error.to_string());
+        assert!(
+            matches!(oauth::poll_device_token(&device).await.unwrap(), oauth::DevicePoll::Rejected(_)),
+            "{} should stop polling",
+            error
+        );
+    }
+
+    // 服务端临时错误交给调用方继续轮询，单次轮询本身不会被自动重放
+    let before = server.count("/api/accounts/deviceauth/token");
+    server.fail_next(500, None);
+    assert!(oauth::poll_device_token(&device).await.is_err());
+    assert_eq!(server.count("/api/accounts/deviceauth/token"), before + 1);
+}
+
+#[tokio::test]
+async fn relogin_replaces_tokens_only_for_matching_identity() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    let (codex_home, _) = temp_homes("relogin");
+    let (store, id) = store_with_account("at-revoked", "rt-revoked");
+    {
+        let mut s = store.lock().unwrap();
+        let account = s.accounts.get_mut(&id).unwrap();
+        account.notes = Some("团队账号".to_string());
+        s.current = Some(id.clone());
+    }
+    let created_at = store.lock().unwrap().accounts[&id].created_at;
+
+    let tokens = |account_id: &str| oauth::TokenResponse {
+        access_token: access_token("fresh", 3600),
+        refresh_token: Some("rt-fresh".to_string()),
+        id_token: Some(signed_id_token(&server.base_url, "mock@example.com", account_id)),
+        expires_in: Some(3600),
+    };
+
+    // 其他身份的授权被拒绝，原账号不变
+    let err = relogin_account(&store, &id, tokens("acct-other")).await.unwrap_err();
+    assert!(err.contains("身份不匹配"), "{}", err);
+    assert_eq!(store.lock().unwrap().accounts[&id].refresh_token.as_deref(), Some("rt-revoked"));
+
+    let account = relogin_account(&store, &id, tokens("acct-mock")).await.unwrap();
+    assert_eq!(account.id, id);
+    assert_eq!(account.name, "mock");
+    assert_eq!(account.notes.as_deref(), Some("团队账号"));
+    assert_eq!(account.created_at, created_at);
+    assert_eq!(account.refresh_token.as_deref(), Some("rt-fresh"));
+    assert_eq!(account.auth_json["tokens"]["refresh_token"], "rt-fresh");
+    assert_eq!(store.lock().unwrap().accounts.len(), 1);
+
+    // 当前账号同步写入 auth.json
+    let auth: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(codex_home.join("auth.json")).unwrap()).unwrap();
+    assert_eq!(auth["tokens"]["refresh_token"], "rt-fresh");
+
+    // 没有可比对的身份信息时不替换令牌
+    let unknown = {
+        let mut s = store.lock().unwrap();
+        let account = s.add_account("unknown".to_string(), serde_json::json!({ "tokens": { "refresh_token": "rt-unknown" } }), None);
+        account.id
+    };
+    let err = relogin_account(&store, &unknown, tokens("acct-mock")).await.unwrap_err();
+    assert!(err.contains("作为新账号添加"), "{}", err);
+    assert_eq!(store.lock().unwrap().accounts[&unknown].auth_json["tokens"]["refresh_token"], "rt-unknown");
+}
+
+#[tokio::test]
+async fn delete_revokes_tokens_and_still_deletes_offline() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    temp_homes("revoke");
+    server.allow_refresh("rt-delete");
+    server.allow_access("at-delete");
+
+    let (store, id) = store_with_account("at-delete", "rt-delete");
+    let records = delete_accounts_inner(&store, std::slice::from_ref(&id), true).await.unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].refresh_token, RevokeOutcome::Revoked);
+    assert_eq!(records[0].access_token, RevokeOutcome::Revoked);
+    assert!(store.lock().unwrap().accounts.is_empty());
+    assert_eq!(store.lock().unwrap().revocations.len(), 1);
+    assert_eq!(server.state.lock().unwrap().revoked, vec!["rt-delete", "at-delete"]);
+    // 撤销后服务端不再接受该 refresh_token
+    assert!(oauth::refresh_access_token("rt-delete").await.is_err());
+
+    // 不存在的账号会让整批操作失败，什么都不删
+    let (store, id) = store_with_account("at-2", "rt-2");
+    assert!(delete_accounts_inner(&store, &[id.clone(), "missing".to_string()], true).await.is_err());
+    assert_eq!(store.lock().unwrap().accounts.len(), 1);
+
+    // 离线时记录结果并照常删除
+    connectivity::report_unreachable();
+    let records = delete_accounts_inner(&store, &[id], true).await.unwrap();
+    assert_eq!(records[0].refresh_token, RevokeOutcome::Offline);
+    assert!(store.lock().unwrap().accounts.is_empty());
+    assert_eq!(server.count("/oauth/revoke"), 2);
+    connectivity::report_reachable();
+}
+
+#[tokio::test]
+async fn deleting_the_current_account_keeps_codex_cli_logged_in() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    let (codex_home, _) = temp_homes("revoke-current");
+    server.allow_refresh("rt-current");
+    server.allow_access("at-current");
+
+    let (store, current) = store_with_account("at-current", "rt-current");
+    let next = {
+        let mut s = store.lock().unwrap();
+        let mut account = s.add_account(
+            "next".to_string(),
+            serde_json::json!({ "tokens": { "access_token": "at-next", "refresh_token": "rt-next", "account_id": "acct-next" } }),
+            None,
+        );
+        account.refresh_token = Some("rt-next".to_string());
+        s.accounts.insert(account.id.clone(), account.clone());
+        s.current = Some(current.clone());
+        AccountStore::write_codex_auth(&s.accounts[&current].auth_json).unwrap();
+        account.id
+    };
+
+    // auth.json 仍在使用的令牌不撤销
+    let records = delete_accounts_inner(&store, std::slice::from_ref(&current), true).await.unwrap();
+    assert_eq!(records[0].refresh_token, RevokeOutcome::Shared);
+    assert_eq!(records[0].access_token, RevokeOutcome::Shared);
+    assert!(server.state.lock().unwrap().revoked.is_empty());
+
+    // 当前账号转移后 auth.json 同步改写
+    assert_eq!(store.lock().unwrap().current.as_deref(), Some(next.as_str()));
+    let auth: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(codex_home.join("auth.json")).unwrap()).unwrap();
+    assert_eq!(auth["tokens"]["refresh_token"], "rt-next");
+    assert_eq!(auth["tokens"]["account_id"], "acct-next");
+}
+
+#[tokio::test]
+async fn login_rejects_forged_or_foreign_id_tokens() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    temp_homes("jwt");
+    let store = Mutex::new(AccountStore::default());
+    let tokens = |id_token: String| oauth::TokenResponse {
+        access_token: access_token("login", 3600),
+        refresh_token: Some("rt-login".to_string()),
+        id_token: Some(id_token),
+        expires_in: Some(3600),
+    };
+    let claims = |iss: &str, aud: &str, exp_in: i64| serde_json::json!({
+        "iss": iss,
+        "aud": aud,
+        "exp": chrono::Utc::now().timestamp() + exp_in,
+        "email": "mock@example.com",
+        "https://api.openai.com/auth": { "chatgpt_account_id": "acct-mock" }
+    });
+
+    // 未签名 / 篡改 / 受众或签发者不符 / 已过期 的令牌都被拒绝
+    let unsigned = make_jwt(claims(&server.base_url, "app_test", 3600));
+    let genuine = sign_jwt(claims(&server.base_url, "app_test", 3600));
+    let mut parts: Vec<String> = genuine.split('.').map(|s| s.to_string()).collect();
+    parts[1] = swap_email(&parts[1]);
+    let tampered = parts.join(".");
+    for bad in [
+        unsigned,
+        tampered,
+        sign_jwt(claims(&server.base_url, "someone_else", 3600)),
+        sign_jwt(claims("https://evil.example.com", "app_test", 3600)),
+        sign_jwt(claims(&server.base_url, "app_test", -3600)),
+    ] {
+        assert!(save_oauth_account(&store, tokens(bad)).await.is_err());
+    }
+    assert!(store.lock().unwrap().accounts.is_empty());
+
+    let account = save_oauth_account(&store, tokens(genuine)).await.unwrap();
+    assert_eq!(account.name, "mock@example.com");
+    // 公钥集合被缓存，只请求一次
+    assert_eq!(server.count("/.well-known/jwks.json"), 1);
+}
+
+#[tokio::test]
+async fn import_verifies_each_account_on_its_own() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    temp_homes("import");
+    let id_token = signed_id_token(&server.base_url, "mock@example.com", "acct-mock");
+    let auth = |id_token: Option<&str>, account_id: &str, access: String| serde_json::json!({
+        "tokens": {
+            "id_token": id_token,
+            "access_token": access,
+            "refresh_token": "rt-import",
+            "account_id": account_id,
+        }
+    });
+    let access = |email: &str, account_id: &str| make_jwt(serde_json::json!({
+        "exp": chrono::Utc::now().timestamp() + 3600,
+        "https://api.openai.com/profile": { "email": email },
+        "https://api.openai.com/auth": { "chatgpt_account_id": account_id }
+    }));
+    let good = auth(Some(&id_token), "acct-mock", access("mock@example.com", "acct-mock"));
+
+    match verify_auth_json(&good).await.unwrap() {
+        IdentityCheck::Verified(user) => {
+            assert_eq!((user.email.as_str(), user.account_id.as_deref()), ("mock@example.com", Some("acct-mock")));
+        }
+        other => panic!("{:?}", other),
+    }
+    // 账号 ID 或邮箱与 id_token 不一致被拒绝
+    for bad in [
+        auth(Some(&id_token), "acct-other", access("mock@example.com", "acct-mock")),
+        auth(Some(&id_token), "acct-mock", access("boss@example.com", "acct-mock")),
+        auth(Some(&id_token), "acct-mock", access("mock@example.com", "acct-other")),
+    ] {
+        assert!(verify_auth_json(&bad).await.is_err());
+    }
+    // 缺少 id_token 时标记为未校验
+    let legacy = auth(None, "acct-mock", access("mock@example.com", "acct-mock"));
+    assert!(matches!(verify_auth_json(&legacy).await.unwrap(), IdentityCheck::Unverified(_)));
+
+    // 逐个导入: 伪造的条目被跳过，不影响其他条目
+    let mut backup = AccountStore::default();
+    let good_id = backup.add_account("good".to_string(), good.clone(), None).id;
+    let forged_id = backup.add_account("forged".to_string(), auth(Some(&id_token), "acct-other", access("mock@example.com", "acct-mock")), None).id;
+    let legacy_id = backup.add_account("legacy".to_string(), legacy, None).id;
+    let store = Mutex::new(AccountStore::default());
+    let records = import_accounts_inner(&store, &backup.export().unwrap()).await.unwrap();
+    let status = |id: &str| status_of(&records, id);
+    assert_eq!(status(&good_id), ImportStatus::Verified);
+    assert!(matches!(status(&forged_id), ImportStatus::Rejected(_)));
+    assert!(matches!(status(&legacy_id), ImportStatus::Unverified(_)));
+    {
+        let s = store.lock().unwrap();
+        assert_eq!(s.accounts.len(), 2);
+        assert!(s.accounts[&good_id].unverified.is_none());
+        assert!(s.accounts[&legacy_id].unverified.is_some());
+    }
+
+    // 全部被拒绝时不覆盖现有数据
+    let mut forged_only = AccountStore::default();
+    forged_only.add_account("forged".to_string(), auth(Some(&id_token), "acct-other", access("mock@example.com", "acct-mock")), None);
+    assert!(import_accounts_inner(&store, &forged_only.export().unwrap()).await.is_err());
+    assert_eq!(store.lock().unwrap().accounts.len(), 2);
+
+    // 无法获取签名公钥时照常导入并标记为未校验
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let mut config = codex_switcher_lib::endpoints::current();
+    config.jwks_url = format!("http://{}/.well-known/jwks.json", closed);
+    codex_switcher_lib::endpoints::configure(config);
+    match verify_auth_json(&good).await.unwrap() {
+        IdentityCheck::Unverified(reason) => assert!(reason.contains("签名公钥"), "{}", reason),
+        other => panic!("{:?}", other),
+    }
+    let offline = Mutex::new(AccountStore::default());
+    let records = import_accounts_inner(&offline, &backup.export().unwrap()).await.unwrap();
+    assert!(matches!(status_of(&records, &good_id), ImportStatus::Unverified(_)));
+    assert_eq!(offline.lock().unwrap().accounts.len(), 3);
+    connectivity::report_reachable();
+}
+
+fn status_of(records: &[ImportRecord], id: &str) -> ImportStatus {
+    records.iter().find(|r| r.account_id == id).unwrap().status.clone()
+}
+
+/// 把 payload 中的邮箱换成另一个 (保持原签名不变)
+fn swap_email(payload: &str) -> String {
+    use base64::{engine::general_purpose, Engine as _};
+    let json = String::from_utf8(general_purpose::URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap();
+    general_purpose::URL_SAFE_NO_PAD.encode(json.replace("mock@example.com", "boss@example.com"))
+}
+
+#[tokio::test]
+async fn workspaces_are_saved_as_separate_accounts() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    let (codex_home, _) = temp_homes("workspace");
+    server.state.lock().unwrap().accounts_body = serde_json::json!({
+        "accounts": {
+            "acct-personal": { "account": { "account_id": "acct-personal", "name": null, "plan_type": "plus" } },
+            "acct-team": { "account": { "account_id": "acct-team", "name": "Team", "plan_type": "team", "account_user_role": "member" } },
+            "default": { "account": { "account_id": null } }
+        },
+        "account_ordering": ["acct-team", "acct-personal"]
+    });
+
+    let login = |tag: &str, account_id: &str| {
+        let access = access_token(tag, 3600);
+        server.allow_access(&access);
+        oauth::TokenResponse {
+            access_token: access,
+            refresh_token: Some(format!("rt-{}", tag)),
+            id_token: Some(signed_id_token(&server.base_url, "mock@example.com", account_id)),
+            expires_in: Some(3600),
+        }
+    };
+    let store = Mutex::new(AccountStore::default());
+    let personal = save_oauth_account(&store, login("personal", "acct-personal")).await.unwrap();
+
+    // 工作区来自 ChatGPT 账号列表，而不是 id_token 中的组织
+    let workspaces = list_workspaces(&store, &personal.id).await.unwrap();
+    let ids: Vec<_> = workspaces.iter().map(|w| w.id.as_str()).collect();
+    assert_eq!(ids, vec!["acct-team", "acct-personal"]);
+    assert_eq!(workspaces[1].saved_account_id.as_deref(), Some(personal.id.as_str()));
+    assert!(workspaces[1].is_default);
+    assert!(workspaces[0].saved_account_id.is_none());
+
+    // 为工作区单独授权: 令牌必须绑定所选工作区
+    let team_target = WorkspaceTarget { id: "acct-team".to_string(), title: Some("Team".to_string()) };
+    assert!(save_workspace_login(&store, &team_target, login("wrong", "acct-personal")).await.is_err());
+    let team = save_workspace_login(&store, &team_target, login("team", "acct-team")).await.unwrap();
+    assert_eq!(team.auth_json["tokens"]["account_id"], "acct-team");
+    assert_eq!(team.name, "mock@example.com (Team)");
+    assert_eq!(team.refresh_token.as_deref(), Some("rt-team"));
+    // 再次授权同一工作区时更新原条目
+    let again = save_workspace_login(&store, &team_target, login("team2", "acct-team")).await.unwrap();
+    assert_eq!(again.id, team.id);
+    assert_eq!(store.lock().unwrap().accounts.len(), 2);
+
+    // 用量请求头与令牌绑定的工作区一致
+    get_quota_inner(&store, &team.id).await.unwrap();
+    get_quota_inner(&store, &personal.id).await.unwrap();
+    assert_eq!(server.state.lock().unwrap().usage_account_ids, vec!["acct-team", "acct-personal"]);
+
+    // 导出后再导入，工作区条目通过身份校验
+    let exported = store.lock().unwrap().export().unwrap();
+    let restored = Mutex::new(AccountStore::default());
+    import_accounts_inner(&restored, &exported).await.unwrap();
+    assert_eq!(restored.lock().unwrap().accounts[&team.id].auth_json["tokens"]["account_id"], "acct-team");
+
+    // 切换后 auth.json 的 account_id 即所选工作区
+    switch_account_inner(&store, &team.id).await.unwrap();
+    let auth: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(codex_home.join("auth.json")).unwrap()).unwrap();
+    assert_eq!(auth["tokens"]["account_id"], "acct-team");
+}
+
+#[test]
+fn repair_restores_account_id_bound_to_the_token() {
+    let mut store = AccountStore::default();
+    let id_token = make_jwt(serde_json::json!({
+        "email": "mock@example.com",
+        "https://api.openai.com/auth": { "chatgpt_account_id": "acct-personal" }
+    }));
+    let account = store.add_account("legacy".to_string(), serde_json::json!({
+        "tokens": { "id_token": id_token, "access_token": "at", "account_id": "org-legacy" }
+    }), None);
+
+    assert_eq!(repair_account_ids(&mut store), 1);
+    assert_eq!(store.accounts[&account.id].auth_json["tokens"]["account_id"], "acct-personal");
+    assert_eq!(repair_account_ids(&mut store), 0);
+}
+
+#[tokio::test]
+async fn drift_check_only_overwrites_provably_older_side() {
+    let _guard = SERIAL.lock().await;
+    let (codex_home, _) = temp_homes("drift");
+    let (store, id) = store_with_account("at-app", "rt-app");
+    {
+        let mut s = store.lock().unwrap();
+        s.current = Some(id.clone());
+        let account = s.accounts.get_mut(&id).unwrap();
+        account.auth_json["last_refresh"] = serde_json::json!("2026-01-01T00:00:00Z");
+        AccountStore::write_codex_auth(&account.auth_json).unwrap();
+    }
+    assert_eq!(check_drift(&store).unwrap(), DriftOutcome::InSync);
+
+    // Codex CLI 自行刷新: auth.json 较新，令牌写回存储
+    let cli_auth = serde_json::json!({
+        "tokens": { "access_token": "at-cli", "refresh_token": "rt-cli", "account_id": "acct-mock" },
+        "last_refresh": "2026-01-02T00:00:00Z"
+    });
+    std::fs::write(codex_home.join("auth.json"), cli_auth.to_string()).unwrap();
+    assert_eq!(check_drift(&store).unwrap(), DriftOutcome::AdoptedFromAuthJson);
+    {
+        let s = store.lock().unwrap();
+        assert_eq!(s.accounts[&id].refresh_token.as_deref(), Some("rt-cli"));
+        assert_eq!(s.accounts[&id].auth_json["tokens"]["access_token"], "at-cli");
+    }
+
+    // 存储较新: 默认只报告，开启设置后才重写 auth.json
+    {
+        let mut s = store.lock().unwrap();
+        let account = s.accounts.get_mut(&id).unwrap();
+        account.auth_json["tokens"]["access_token"] = serde_json::json!("at-app-2");
+        account.auth_json["last_refresh"] = serde_json::json!("2026-01-03T00:00:00Z");
+    }
+    let read_auth = || -> serde_json::Value {
+        serde_json::from_str(&std::fs::read_to_string(codex_home.join("auth.json")).unwrap()).unwrap()
+    };
+    assert_eq!(check_drift(&store).unwrap(), DriftOutcome::StoreNewer);
+    assert_eq!(read_auth()["tokens"]["access_token"], "at-cli");
+    store.lock().unwrap().settings.rewrite_auth_json_on_drift = true;
+    assert_eq!(check_drift(&store).unwrap(), DriftOutcome::RewroteAuthJson);
+    assert_eq!(read_auth()["tokens"]["access_token"], "at-app-2");
+
+    // 任一方缺少 last_refresh: 无法判断新旧，两边都不动
+    let undated = serde_json::json!({
+        "tokens": { "access_token": "at-undated", "refresh_token": "rt-undated", "account_id": "acct-mock" }
+    });
+    std::fs::write(codex_home.join("auth.json"), undated.to_string()).unwrap();
+    assert_eq!(check_drift(&store).unwrap(), DriftOutcome::Diverged);
+    assert_eq!(read_auth()["tokens"]["access_token"], "at-undated");
+    assert_eq!(store.lock().unwrap().accounts[&id].auth_json["tokens"]["access_token"], "at-app-2");
+
+    // 在 Codex CLI 中登录了其他账号: 不做处理
+    let foreign = serde_json::json!({
+        "tokens": { "access_token": "at-other", "refresh_token": "rt-other", "account_id": "acct-other" },
+        "last_refresh": "2026-01-04T00:00:00Z"
+    });
+    std::fs::write(codex_home.join("auth.json"), foreign.to_string()).unwrap();
+    assert_eq!(check_drift(&store).unwrap(), DriftOutcome::ForeignAccount);
+    assert_eq!(store.lock().unwrap().accounts[&id].refresh_token.as_deref(), Some("rt-cli"));
+}
+
+#[tokio::test]
+async fn schedule_switches_once_per_window() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    temp_homes("schedule");
+
+    let (store, personal) = store_with_account(&access_token("personal", 3600), "rt-personal");
+    let team = {
+        let mut s = store.lock().unwrap();
+        let mut account = s.add_account(
+            "team".to_string(),
+            serde_json::json!({ "tokens": { "access_token": access_token("team", 3600), "account_id": "acct-team" } }),
+            None,
+        );
+        account.refresh_token = Some("rt-team".to_string());
+        s.accounts.insert(account.id.clone(), account.clone());
+        s.current = Some(personal.clone());
+        account.id
+    };
+    let rule = SwitchRule {
+        id: String::new(),
+        name: "工作时间".to_string(),
+        account_id: team.clone(),
+        weekdays: vec![1, 2, 3, 4, 5],
+        start: "09:00".to_string(),
+        end: "18:00".to_string(),
+        enabled: true,
+    };
+    let bad = SwitchRule { account_id: "missing".to_string(), ..rule.clone() };
+    assert!(save_rules_inner(&store, vec![bad]).is_err());
+    let saved = save_rules_inner(&store, vec![rule]).unwrap();
+    assert!(!saved[0].id.is_empty());
+
+    // 2026-03-02 为周一
+    let at = |value: &str| chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M").unwrap();
+    assert_eq!(apply_inner(&store, at("2026-03-02T08:59")).await.unwrap(), ScheduleOutcome::Idle);
+    assert!(matches!(apply_inner(&store, at("2026-03-02T09:00")).await.unwrap(), ScheduleOutcome::Switched { .. }));
+    assert_eq!(store.lock().unwrap().current.as_deref(), Some(team.as_str()));
+
+    // 时段内手动切换后不会被改回
+    switch_account_inner(&store, &personal).await.unwrap();
+    assert_eq!(apply_inner(&store, at("2026-03-02T12:00")).await.unwrap(), ScheduleOutcome::AlreadyApplied);
+    assert_eq!(store.lock().unwrap().current.as_deref(), Some(personal.as_str()));
+
+    // 次日进入新的时段
+    assert!(matches!(apply_inner(&store, at("2026-03-03T09:01")).await.unwrap(), ScheduleOutcome::Switched { .. }));
+    assert_eq!(store.lock().unwrap().current.as_deref(), Some(team.as_str()));
+
+    // 旧数据中指向已删除账号的规则: 跳过而不是报错
+    let stale = SwitchRule { id: "stale".to_string(), account_id: "deleted".to_string(), ..saved[0].clone() };
+    store.lock().unwrap().switch_schedules.insert(0, stale);
+    assert_eq!(
+        apply_inner(&store, at("2026-03-04T09:00")).await.unwrap(),
+        ScheduleOutcome::MissingAccount { account_id: "deleted".to_string(), rule_id: "stale".to_string() }
+    );
+    assert_eq!(store.lock().unwrap().prune_schedules(), 1);
+
+    // 删除账号时一并移除其规则，剩余规则仍可保存
+    delete_accounts_inner(&store, std::slice::from_ref(&team), false).await.unwrap();
+    assert!(store.lock().unwrap().switch_schedules.is_empty());
+    assert!(save_rules_inner(&store, vec![]).is_ok());
+}
+
+#[tokio::test]
+async fn repeated_rejections_flag_account_for_relogin() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    temp_homes("health");
+
+    let (store, id) = store_with_account(&access_token("stale", -60), "rt-revoked");
+    let health = |store: &Mutex<AccountStore>| store.lock().unwrap().accounts[&id].refresh_health.clone();
+
+    assert!(token_refresh::refresh_current(&store, &id).await.is_err());
+    let first = health(&store).unwrap();
+    assert_eq!((first.consecutive_failures, first.last_error_class, first.needs_relogin), (1, RefreshErrorClass::Revoked, false));
+
+    assert!(token_refresh::refresh_current(&store, &id).await.is_err());
+    let second = health(&store).unwrap();
+    assert!(second.needs_relogin);
+    assert_eq!(second.first_failed_at, first.first_failed_at);
+
+    // 定时切换跳过需要重新登录的账号
+    let rule = SwitchRule {
+        id: "always".to_string(),
+        name: "全天".to_string(),
+        account_id: id.clone(),
+        weekdays: vec![],
+        start: "00:00".to_string(),
+        end: "00:00".to_string(),
+        enabled: true,
+    };
+    save_rules_inner(&store, vec![rule]).unwrap();
+    let now = chrono::Local::now().naive_local();
+    assert_eq!(apply_inner(&store, now).await.unwrap(), ScheduleOutcome::NeedsRelogin { account_id: id.clone() });
+
+    // 刷新恢复后清除
+    server.allow_refresh("rt-revoked");
+    token_refresh::refresh_current(&store, &id).await.unwrap();
+    assert_eq!(health(&store), None);
+}
+
+#[tokio::test]
+async fn deleting_a_workspace_entry_keeps_shared_tokens() {
+    let _guard = SERIAL.lock().await;
+    let server = MockServer::start().await;
+    server.install();
+    temp_homes("revoke-shared");
+    server.allow_refresh("rt-shared");
+    server.allow_access("at-shared");
+
+    let (store, personal) = store_with_account("at-shared", "rt-shared");
+    let team = {
+        let mut s = store.lock().unwrap();
+        let mut sibling = s.accounts[&personal].clone();
+        sibling.id = "team-entry".to_string();
+        sibling.auth_json["tokens"]["account_id"] = serde_json::json!("acct-team");
+        s.accounts.insert(sibling.id.clone(), sibling.clone());
+        sibling.id
+    };
+
+    let records = delete_accounts_inner(&store, std::slice::from_ref(&team), true).await.unwrap();
+    assert_eq!(records[0].refresh_token, RevokeOutcome::Shared);
+    assert_eq!(records[0].access_token, RevokeOutcome::Shared);
+    assert!(server.state.lock().unwrap().revoked.is_empty());
+
+    // 保留的条目仍可刷新
+    refresh_account_tokens(&store, &personal).await.unwrap();
+    assert_ne!(store.lock().unwrap().accounts[&personal].refresh_token.as_deref(), Some("rt-shared"));
+}
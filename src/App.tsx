@@ -3,7 +3,7 @@ import { RefreshCw } from 'lucide-react';
 import { listen } from '@tauri-apps/api/event';
 import { save } from '@tauri-apps/plugin-dialog';
 import { writeTextFile } from '@tauri-apps/plugin-fs';
-import { useAccounts } from './hooks/useAccounts';
+import { useAccounts, type HealthChange, type ScheduleOutcome, type StoreChange } from './hooks/useAccounts';
 import { useUsage } from './hooks/useUsage';
 import { AddAccountModal } from './components/AddAccountModal';
 import { Dashboard } from './components/Dashboard';
@@ -24,6 +24,7 @@ function App() {
     importCurrent,
     switchTo,
     deleteAccount,
+    deleteAccounts,
     exportAccounts,
     importAccounts,
     reloadIdeWindows,
@@ -42,10 +43,10 @@ function App() {
 
   const currentAccount = accounts.find(a => a.id === currentId) || null;
 
-  // 监听后台调度器的账号更新事件
+  // 监听存储变更事件 (后台刷新、补刷、设备码登录等写入后触发)
   useEffect(() => {
-    const unlisten = listen('accounts-updated', () => {
-      console.log('[Frontend] 收到后台刷新通知，重新加载账号列表');
+    const unlisten = listen<StoreChange>('store-changed', (event) => {
+      console.log('[Frontend] 存储已变更 (revision', event.payload.revision, ')，重新加载账号列表');
       refresh();
     });
 
@@ -54,6 +55,36 @@ function App() {
     };
   }, [refresh]);
 
+  // 定时切换由后台完成，与手动切换一样按设置重载 IDE
+  useEffect(() => {
+    const unlisten = listen<ScheduleOutcome>('account-switched', () => {
+      if (settings.auto_reload_ide) {
+        reloadIdeWindows(false).catch(err => console.error('[Frontend] 定时切换后重载 IDE 失败:', err));
+      }
+      setTimeout(() => {
+        refreshUsage();
+      }, 500);
+    });
+
+    return () => {
+      unlisten.then(f => f());
+    };
+  }, [settings.auto_reload_ide, reloadIdeWindows, refreshUsage]);
+
+  // 账号需要重新登录时提醒 (列表由 store-changed 刷新)
+  useEffect(() => {
+    const unlisten = listen<HealthChange>('account-health-changed', (event) => {
+      const { account_name, health } = event.payload;
+      if (health?.needs_relogin) {
+        console.warn(`[Frontend] 账号 ${account_name} 需要重新登录: ${health.last_error}`);
+      }
+    });
+
+    return () => {
+      unlisten.then(f => f());
+    };
+  }, []);
+
   // Apply theme
   useEffect(() => {
     document.documentElement.setAttribute('data-theme', settings.theme || 'light');
@@ -65,7 +96,14 @@ function App() {
     if (settings.auto_reload_ide) {
       // 延迟一下等待文件写入完成
       setTimeout(async () => {
-        await reloadIdeWindows(false); // false = Restart Extension Host
+        const reports = await reloadIdeWindows(false); // false = Restart Extension Host
+        for (const report of reports) {
+          if (report.error) {
+            console.error(`[Frontend] 重载 ${report.ide} 失败: ${report.error}`);
+          } else {
+            console.log(`[Frontend] 重载 ${report.ide}: ${report.actions.join('；')}`);
+          }
+        }
       }, 300);
     }
     setTimeout(() => {
@@ -103,8 +141,12 @@ function App() {
 
       try {
         const text = await file.text();
-        await importAccounts(text);
-        alert('导入成功！');
+        const records = await importAccounts(text);
+        const lines = records
+          .filter(r => r.status.status !== 'verified')
+          .map(r => r.status.status === 'verified' ? '' : `${r.account_name}: ${r.status.status === 'rejected' ? '未导入' : '未校验'} (${r.status.reason})`);
+        const imported = records.filter(r => r.status.status !== 'rejected').length;
+        alert([`导入完成：${imported} / ${records.length} 个账号`, ...lines].join('\n'));
       } catch (err) {
         alert('导入失败: ' + String(err));
       }
@@ -202,11 +244,12 @@ function App() {
             settings={settings}
             onSwitch={handleSwitch}
             onDelete={deleteAccount}
+            onDeleteMany={deleteAccounts}
             onUpdateSettings={updateSettings}
             onRefreshComplete={refresh}
           />
         ) : (
-          <Settings settings={settings} onUpdateSettings={updateSettings} />
+          <Settings settings={settings} accounts={accounts} onUpdateSettings={updateSettings} />
         )}
       </main>
 
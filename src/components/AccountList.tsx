@@ -1,7 +1,9 @@
 import { useState, useEffect, useMemo } from 'react';
-import { Zap, RefreshCw, ArrowLeftRight, Trash2, Clock } from 'lucide-react';
-import { Account, AppSettings } from '../hooks/useAccounts';
+import { Zap, RefreshCw, ArrowLeftRight, Trash2, Clock, LogIn } from 'lucide-react';
+import { Account, AppSettings, OAuthCallback, OAuthLoginStart, OAuthProgress, RevocationRecord } from '../hooks/useAccounts';
 import { invoke } from '@tauri-apps/api/core';
+import { listen, UnlistenFn } from '@tauri-apps/api/event';
+import { ask } from '@tauri-apps/plugin-dialog';
 import './AccountList.css';
 
 interface UsageData {
@@ -70,6 +72,7 @@ interface AccountListProps {
     settings: AppSettings;
     onSwitch: (id: string) => void;
     onDelete: (id: string) => void;
+    onDeleteMany?: (ids: string[], revoke?: boolean) => Promise<RevocationRecord[]>;
     onUpdateSettings: (settings: AppSettings) => void;
     onRefreshComplete?: () => void;  // 刷新完成后的回调
 }
@@ -80,6 +83,7 @@ export function AccountList({
     settings,
     onSwitch,
     onDelete,
+    onDeleteMany,
     onUpdateSettings,
     onRefreshComplete,
 }: AccountListProps) {
@@ -90,6 +94,7 @@ export function AccountList({
     const [searchQuery, setSearchQuery] = useState('');
     const [filter, setFilter] = useState<FilterType>('all');
     const [invalidIds, setInvalidIds] = useState<Set<string>>(new Set()); // 无效Token的账号
+    const [reloggingIds, setReloggingIds] = useState<Set<string>>(new Set()); // 正在重新登录的账号
 
     const autoReload = settings.auto_reload_ide;
     const setAutoReload = (val: boolean) => onUpdateSettings({ ...settings, auto_reload_ide: val });
@@ -246,6 +251,70 @@ export function AccountList({
     };
 
 
+    // 重新登录: 为该账号走一遍浏览器授权，只替换令牌
+    const handleRelogin = async (id: string) => {
+        setReloggingIds(prev => new Set(prev).add(id));
+        const unlisteners: Promise<UnlistenFn>[] = [];
+        const cleanup = () => unlisteners.forEach(u => u.then(f => f()));
+        try {
+            const login = await invoke<OAuthLoginStart>('start_oauth_login', { accountId: id });
+            await new Promise<void>((resolve, reject) => {
+                unlisteners.push(listen<OAuthCallback>('oauth-callback-received', async (event) => {
+                    if (event.payload.state !== login.state) return;
+                    try {
+                        await invoke('finalize_oauth_login', { code: event.payload.code, oauthState: login.state });
+                        resolve();
+                    } catch (err) {
+                        reject(err);
+                    }
+                }));
+                unlisteners.push(listen<OAuthProgress>('oauth-login-progress', (event) => {
+                    const { state, stage, message } = event.payload;
+                    if (state !== login.state) return;
+                    if (stage === 'timed_out' || stage === 'cancelled' || stage === 'failed') {
+                        reject(message ?? stage);
+                    }
+                }));
+            });
+            setInvalidIds(prev => {
+                const next = new Set(prev);
+                next.delete(id);
+                return next;
+            });
+            onRefreshComplete?.();
+        } catch (err) {
+            console.error('重新登录失败:', err);
+        } finally {
+            cleanup();
+            setReloggingIds(prev => {
+                const next = new Set(prev);
+                next.delete(id);
+                return next;
+            });
+        }
+    };
+
+    // 撤销并删除选中账号
+    const handleRevokeAndDeleteSelected = async () => {
+        if (!onDeleteMany || selectedIds.size === 0) return;
+        const confirmed = await ask(
+            `将撤销选中的 ${selectedIds.size} 个账号的令牌并删除，离线时仅删除本地数据。确定继续吗？`,
+            { title: '撤销并删除', kind: 'warning' }
+        );
+        if (!confirmed) return;
+
+        try {
+            const records = await onDeleteMany(Array.from(selectedIds), true);
+            const failed = records.filter(r => r.refresh_token.status === 'failed' || r.refresh_token.status === 'offline');
+            if (failed.length > 0) {
+                console.warn('部分账号的令牌未能撤销:', failed);
+            }
+            setSelectedIds(new Set());
+        } catch (err) {
+            console.error('批量删除失败:', err);
+        }
+    };
+
     // 刷新选中账号
     const handleRefreshSelected = async () => {
         if (selectedIds.size === 0) {
@@ -429,6 +498,17 @@ export function AccountList({
                     <span style={{ fontSize: '12px' }}>自动重载</span>
                 </button>
 
+                {/* 撤销并删除选中 */}
+                {onDeleteMany && selectedIds.size > 0 && (
+                    <button
+                        className="btn-refresh"
+                        onClick={handleRevokeAndDeleteSelected}
+                        title={`撤销并删除选中 (${selectedIds.size})`}
+                    >
+                        <Trash2 className="icon" />
+                    </button>
+                )}
+
                 {/* 刷新按钮 */}
                 <button
                     className="btn-refresh"
@@ -466,7 +546,8 @@ export function AccountList({
                     const isRefreshing = refreshingIds.has(account.id);
                     const isCurrent = account.id === currentId;
 
-                    const isInvalid = invalidIds.has(account.id);
+                    const health = account.refresh_health;
+                    const isInvalid = invalidIds.has(account.id) || !!health?.needs_relogin;
 
                     return (
                         <div
@@ -487,7 +568,20 @@ export function AccountList({
                             <div className="col-email">
                                 <span className="email-text">{account.name}</span>
                                 {isCurrent && <span className="badge current">当前</span>}
-                                {isInvalid && <span className="badge invalid" title="授权已失效，请删除后重新登录">⚠️ 失效</span>}
+                                {isInvalid && <span className="badge invalid" title="授权已失效，请重新登录">⚠️ 失效</span>}
+                                {health && !isInvalid && (
+                                    <span
+                                        className="badge invalid"
+                                        title={`自 ${new Date(health.first_failed_at).toLocaleString()} 起连续失败: ${health.last_error}`}
+                                    >
+                                        刷新失败 ×{health.consecutive_failures}
+                                    </span>
+                                )}
+                                {account.unverified && (
+                                    <span className="badge invalid" title={`导入时未校验身份: ${account.unverified}，重新登录后即可校验`}>
+                                        未校验
+                                    </span>
+                                )}
                                 {usage?.plan_type && (
                                     <span className="badge plan">{usage.plan_type.toUpperCase()}</span>
                                 )}
@@ -532,6 +626,17 @@ export function AccountList({
                                     <RefreshCw className={`icon ${isRefreshing ? 'spinning' : ''}`} />
                                 </button>
 
+                                {isInvalid && (
+                                    <button
+                                        className="action-btn relogin"
+                                        onClick={() => handleRelogin(account.id)}
+                                        disabled={reloggingIds.has(account.id)}
+                                        title="重新登录 (保留名称、备注等信息)"
+                                    >
+                                        <LogIn className="icon" />
+                                    </button>
+                                )}
+
                                 {!isCurrent && (
                                     <button
                                         className="action-btn switch"
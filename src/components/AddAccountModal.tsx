@@ -1,46 +1,95 @@
-import { useState, useEffect } from 'react';
+import { useState, useEffect, useRef } from 'react';
 import { listen } from '@tauri-apps/api/event';
-import { useAccounts } from '../hooks/useAccounts';
+import { useAccounts, Account, DeviceLoginEvent, DeviceLoginInfo, OAuthCallback, OAuthProgress, PortCheck, Workspace } from '../hooks/useAccounts';
 import './AddAccountModal.css';
 
 interface AddAccountModalProps {
     isOpen: boolean;
     onClose: () => void;
-    onAdd: (name: string, notes?: string) => Promise<void>;
+    onAdd: (name: string, notes?: string) => Promise<Account>;
     onSuccess?: () => void;  // 添加成功后的回调，用于刷新父组件列表
 }
 
-type TabType = 'official' | 'openai';
+type TabType = 'official' | 'openai' | 'device';
 
 export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccountModalProps) {
-    const { startOAuthLogin, finalizeOAuthLogin } = useAccounts();
+    const { startOAuthLogin, checkOAuthPort, releaseOAuthPort, cancelOAuthLogin, finalizeOAuthLogin, submitOAuthRedirect, startDeviceLogin, cancelDeviceLogin, listAccountWorkspaces } = useAccounts();
     const [activeTab, setActiveTab] = useState<TabType>('openai');
     const [name, setName] = useState('');
     const [notes, setNotes] = useState('');
     const [loading, setLoading] = useState(false);
     const [error, setError] = useState<string | null>(null);
     const [oauthStatus, setOauthStatus] = useState<string>('');
+    const [deviceLogin, setDeviceLogin] = useState<DeviceLoginInfo | null>(null);
+    const [redirectInput, setRedirectInput] = useState('');
+    const [portConflict, setPortConflict] = useState<PortCheck | null>(null);
+    // 本窗口发起且尚未结束的浏览器登录 (可同时进行多个)
+    const pendingStates = useRef<Set<string>>(new Set());
+    const [pendingCount, setPendingCount] = useState(0);
+    // 登录账号属于多个工作区时，由用户选择为哪些工作区保存条目
+    const [workspacePick, setWorkspacePick] = useState<{ account: Account; workspaces: Workspace[] } | null>(null);
+    const [selectedWorkspaces, setSelectedWorkspaces] = useState<string[]>([]);
+
+    // 为工作区发起的登录完成后不再重复询问
+    const workspaceLogins = useRef(false);
+
+    // 账号属于多个工作区时进入选择步骤，返回 true 表示不要自动关闭窗口
+    const offerWorkspaces = async (account: Account) => {
+        if (workspaceLogins.current) return false;
+        try {
+            const workspaces = await listAccountWorkspaces(account.id);
+            if (workspaces.length > 1) {
+                setWorkspacePick({ account, workspaces });
+                setSelectedWorkspaces(workspaces.filter(w => w.saved_account_id).map(w => w.id));
+                setOauthStatus('');
+                setLoading(false);
+                return true;
+            }
+        } catch (err) {
+            console.error('读取工作区失败:', err);
+        }
+        return false;
+    };
+
+    const trackLogin = (state: string, active: boolean) => {
+        if (active) {
+            pendingStates.current.add(state);
+        } else {
+            pendingStates.current.delete(state);
+        }
+        setPendingCount(pendingStates.current.size);
+        return pendingStates.current.size;
+    };
 
     // 监听后端发来的授权码
     useEffect(() => {
         if (!isOpen) return;
 
-        const unlisten = listen<string>('oauth-callback-received', async (event) => {
-            const code = event.payload;
+        const unlisten = listen<OAuthCallback>('oauth-callback-received', async (event) => {
+            const { state, code } = event.payload;
+            if (!pendingStates.current.has(state)) return;
             setOauthStatus('已获取授权码，正在交换令牌...');
             try {
-                await finalizeOAuthLogin(code);
+                const account = await finalizeOAuthLogin(code, state);
+                const remaining = trackLogin(state, false);
+                onSuccess?.();  // 通知父组件刷新列表
+                if (remaining > 0) {
+                    setOauthStatus(`账号已添加，还有 ${remaining} 个登录等待授权...`);
+                    return;
+                }
+                if (await offerWorkspaces(account)) return;
                 setOauthStatus('授权成功！账号已添加。');
                 setLoading(false);
                 // 延迟关闭模态框，让用户看到成功提示
                 setTimeout(() => {
-                    onSuccess?.();  // 通知父组件刷新列表
                     onClose();
                 }, 1000);
             } catch (err) {
                 setError(String(err));
-                setOauthStatus('');
-                setLoading(false);
+                if (trackLogin(state, false) === 0) {
+                    setOauthStatus('');
+                    setLoading(false);
+                }
             }
         });
 
@@ -49,6 +98,66 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
         };
     }, [isOpen, finalizeOAuthLogin]);
 
+    // 监听浏览器登录进度 (超时、授权被拒绝等终止状态)
+    useEffect(() => {
+        if (!isOpen) return;
+
+        const unlisten = listen<OAuthProgress>('oauth-login-progress', (event) => {
+            const { state, stage, message } = event.payload;
+            if (state && !pendingStates.current.has(state)) return;
+            switch (stage) {
+                case 'listening':
+                case 'browser_opened':
+                    if (message) setOauthStatus(message);
+                    break;
+                case 'timed_out':
+                case 'failed':
+                    setError(message ?? '授权失败');
+                    if (!state || trackLogin(state, false) === 0) {
+                        setOauthStatus('');
+                        setLoading(false);
+                    }
+                    break;
+                case 'cancelled':
+                    if (state && trackLogin(state, false) === 0) {
+                        setOauthStatus('');
+                        setLoading(false);
+                    }
+                    break;
+            }
+        });
+
+        return () => {
+            unlisten.then(f => f());
+        };
+    }, [isOpen]);
+
+    // 监听设备码登录结果
+    useEffect(() => {
+        if (!isOpen) return;
+
+        const unlisten = listen<DeviceLoginEvent>('device-login-status', async (event) => {
+            const { status, account, error: loginError } = event.payload;
+            setDeviceLogin(null);
+            setLoading(false);
+            if (status === 'completed') {
+                onSuccess?.();
+                if (account && await offerWorkspaces(account)) return;
+                setOauthStatus('授权成功！账号已添加。');
+                setTimeout(() => {
+                    onClose();
+                }, 1000);
+            } else if (status === 'failed') {
+                setOauthStatus('');
+                setError(loginError ?? '设备码登录失败');
+            }
+        });
+
+        return () => {
+            unlisten.then(f => f());
+        };
+    }, [isOpen]);
+
     if (!isOpen) return null;
 
     // 处理官方导入
@@ -63,7 +172,8 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
         setError(null);
 
         try {
-            await onAdd(name.trim(), notes.trim() || undefined);
+            const account = await onAdd(name.trim(), notes.trim() || undefined);
+            if (await offerWorkspaces(account)) return;
             handleClose();
         } catch (err) {
             setError(String(err));
@@ -72,16 +182,107 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
         }
     };
 
-    // 处理 OpenAI 登录
+    // 处理 OpenAI 登录: 先检测回调端口，被占用时交由用户决定
     const handleOpenAILogin = async () => {
+        setLoading(true);
+        setError(null);
+        setOauthStatus('正在检测本地回调端口...');
+
+        try {
+            const check = await checkOAuthPort();
+            if (!check.available) {
+                setPortConflict(check);
+                setOauthStatus('');
+                setLoading(false);
+                return;
+            }
+        } catch (err) {
+            // 检测失败不影响登录，后端仍会自动回退端口
+            console.error(err);
+        }
+        await launchOAuthLogin();
+    };
+
+    const launchOAuthLogin = async () => {
+        setPortConflict(null);
         setLoading(true);
         setError(null);
         setOauthStatus('正在启动官方浏览器授权...');
 
         try {
             // 启动 OAuth 后端任务，后端会处理打开浏览器和启动监听
-            await startOAuthLogin();
+            const login = await startOAuthLogin();
+            const count = trackLogin(login.state, true);
+            setOauthStatus(count > 1
+                ? `请在浏览器中完成授权 (${count} 个登录进行中)...`
+                : '请在打开的浏览器窗口中完成 OpenAI 授权...');
+        } catch (err) {
+            setError(String(err));
+            if (pendingStates.current.size === 0) {
+                setOauthStatus('');
+                setLoading(false);
+            }
+        }
+    };
+
+    const handleCancelOAuthLogin = async () => {
+        try {
+            await cancelOAuthLogin();
+        } catch (err) {
+            console.error(err);
+        }
+        pendingStates.current.clear();
+        setPendingCount(0);
+        setOauthStatus('');
+        setRedirectInput('');
+        setLoading(false);
+    };
+
+    // 用户确认后结束占用端口的进程再登录
+    const handleReleasePort = async () => {
+        if (!portConflict?.owner) return;
+        setLoading(true);
+        setOauthStatus(`正在结束 ${portConflict.owner.name}...`);
+        try {
+            await releaseOAuthPort(portConflict.owner.pid);
+            await launchOAuthLogin();
+        } catch (err) {
+            setError(String(err));
+            setOauthStatus('');
+            setLoading(false);
+        }
+    };
+
+    // 手动粘贴回调地址
+    const handleSubmitRedirect = async () => {
+        if (!redirectInput.trim()) return;
+        setError(null);
+        setOauthStatus('正在校验回调地址并交换令牌...');
+        try {
+            const account = await submitOAuthRedirect(redirectInput.trim());
+            onSuccess?.();
+            if (await offerWorkspaces(account)) return;
+            setOauthStatus('授权成功！账号已添加。');
+            setLoading(false);
+            setTimeout(() => {
+                onClose();
+            }, 1000);
+        } catch (err) {
+            setError(String(err));
             setOauthStatus('请在打开的浏览器窗口中完成 OpenAI 授权...');
+        }
+    };
+
+    // 处理设备码登录
+    const handleDeviceLogin = async () => {
+        setLoading(true);
+        setError(null);
+        setOauthStatus('正在申请设备码...');
+
+        try {
+            const info = await startDeviceLogin();
+            setDeviceLogin(info);
+            setOauthStatus('等待在其他设备上完成授权...');
         } catch (err) {
             setError(String(err));
             setOauthStatus('');
@@ -89,15 +290,60 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
         }
     };
 
+    const handleCancelDeviceLogin = async () => {
+        if (deviceLogin) {
+            await cancelDeviceLogin(deviceLogin.login_id);
+        }
+        setDeviceLogin(null);
+        setOauthStatus('');
+        setLoading(false);
+    };
+
     const handleClose = () => {
         if (loading && !oauthStatus.includes('成功')) return;
         setName('');
         setNotes('');
         setError(null);
         setOauthStatus('');
+        setRedirectInput('');
+        setPortConflict(null);
+        setWorkspacePick(null);
+        workspaceLogins.current = false;
         onClose();
     };
 
+    const toggleWorkspace = (id: string) => {
+        setSelectedWorkspaces(prev => prev.includes(id) ? prev.filter(w => w !== id) : [...prev, id]);
+    };
+
+    // 为所选的未保存工作区分别发起授权，令牌绑定对应工作区后保存为账号条目
+    const handleSaveWorkspaces = async () => {
+        if (!workspacePick) return;
+        const targets = workspacePick.workspaces.filter(w => selectedWorkspaces.includes(w.id) && !w.saved_account_id);
+        if (targets.length === 0) {
+            handleClose();
+            return;
+        }
+        setLoading(true);
+        setError(null);
+        workspaceLogins.current = true;
+        setWorkspacePick(null);
+        setActiveTab('openai');
+        try {
+            for (const w of targets) {
+                const login = await startOAuthLogin({ id: w.id, title: w.title });
+                trackLogin(login.state, true);
+            }
+            setOauthStatus(`请在浏览器中依次授权所选的 ${targets.length} 个工作区...`);
+        } catch (err) {
+            setError(String(err));
+            if (pendingStates.current.size === 0) {
+                setOauthStatus('');
+                setLoading(false);
+            }
+        }
+    };
+
     return (
         <div className="modal-overlay" onClick={handleClose}>
             <div className="modal-content" onClick={e => e.stopPropagation()}>
@@ -121,11 +367,88 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
                         >
                             从官方导入
                         </button>
+                        <button
+                            className={`tab-item ${activeTab === 'device' ? 'active' : ''}`}
+                            onClick={() => !loading && setActiveTab('device')}
+                        >
+                            设备码登录
+                        </button>
                     </div>
                 </div>
 
                 <div className="modal-body">
-                    {activeTab === 'official' ? (
+                    {workspacePick ? (
+                        <div className="oauth-content">
+                            <h3 style={{ marginBottom: '8px', color: 'var(--text-primary)' }}>选择工作区</h3>
+                            <p className="oauth-desc">
+                                {workspacePick.account.name} 属于多个工作区。每个勾选的工作区需要在浏览器中单独授权一次，并保存为一个账号条目，切换到该条目时 Codex 将按对应工作区计费。
+                            </p>
+                            {workspacePick.workspaces.map(w => (
+                                <label key={w.id} className="form-group" style={{ display: 'flex', gap: '8px', alignItems: 'center', cursor: 'pointer' }}>
+                                    <input
+                                        type="checkbox"
+                                        checked={selectedWorkspaces.includes(w.id)}
+                                        disabled={!!w.saved_account_id || loading}
+                                        onChange={() => toggleWorkspace(w.id)}
+                                    />
+                                    <span>
+                                        {w.title ?? w.id}
+                                        {w.plan_type && ` · ${w.plan_type}`}
+                                        {w.role && ` · ${w.role}`}
+                                        {w.is_default && ' · 默认'}
+                                        {w.saved_account_id && ' (已保存)'}
+                                    </span>
+                                </label>
+                            ))}
+                            {error && <div className="error-message" style={{ marginTop: '16px' }}>{error}</div>}
+                            <div className="modal-footer" style={{ padding: '16px 0 0', border: 'none' }}>
+                                <button type="button" className="btn btn-ghost" onClick={handleClose} disabled={loading}>
+                                    跳过
+                                </button>
+                                <button type="button" className="btn btn-primary" onClick={handleSaveWorkspaces} disabled={loading}>
+                                    {loading ? '授权中...' : '授权所选工作区'}
+                                </button>
+                            </div>
+                        </div>
+                    ) : activeTab === 'device' ? (
+                        <div className="oauth-content">
+                            <div className="oauth-icon">🔑</div>
+                            <h3 style={{ marginBottom: '8px', color: 'var(--text-primary)' }}>设备码登录</h3>
+                            <p className="oauth-desc">
+                                适用于远程开发机或无法打开本地浏览器的环境。在任意设备上打开验证地址并输入下方的用户码即可 (需先在 ChatGPT 安全设置中允许设备码登录)。
+                            </p>
+
+                            {deviceLogin ? (
+                                <>
+                                    <div className="oauth-status" style={{ fontSize: '24px', letterSpacing: '4px' }}>
+                                        {deviceLogin.user_code}
+                                    </div>
+                                    <div className="oauth-status">
+                                        {deviceLogin.verification_uri}
+                                    </div>
+                                    <button
+                                        className="btn btn-ghost btn-full"
+                                        style={{ marginTop: '12px' }}
+                                        onClick={handleCancelDeviceLogin}
+                                    >
+                                        取消登录
+                                    </button>
+                                </>
+                            ) : (
+                                <button
+                                    className="btn btn-primary btn-full"
+                                    style={{ padding: '14px' }}
+                                    onClick={handleDeviceLogin}
+                                    disabled={loading}
+                                >
+                                    {loading ? '处理中...' : '获取设备码'}
+                                </button>
+                            )}
+
+                            {oauthStatus && <div className="oauth-status">{oauthStatus}</div>}
+                            {error && <div className="error-message" style={{ marginTop: '16px' }}>{error}</div>}
+                        </div>
+                    ) : activeTab === 'official' ? (
                         <form onSubmit={handleSubmitOfficial}>
                             <p className="modal-tip">
                                 将从本地官方 Codex 的登录状态 (`auth.json`) 中提取认证信息。
@@ -175,14 +498,44 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
                                 直接通过 OpenAI 官方渠道登录。支持令牌自动续期，多账号切换更稳定，无需再手动更新 `auth.json`。
                             </p>
 
-                            <button
-                                className="btn btn-primary btn-full"
-                                style={{ padding: '14px' }}
-                                onClick={handleOpenAILogin}
-                                disabled={loading}
-                            >
-                                {loading && oauthStatus ? '处理中...' : '立即登录 OpenAI'}
-                            </button>
+                            {portConflict ? (
+                                <div className="error-message">
+                                    <p style={{ margin: '0 0 8px' }}>
+                                        本地回调端口 {portConflict.port} 已被
+                                        {portConflict.owner
+                                            ? ` ${portConflict.owner.name} (PID ${portConflict.owner.pid})`
+                                            : '未知进程'} 占用。
+                                        {portConflict.owner?.is_codex && ' 这可能是正在进行的 codex login。'}
+                                    </p>
+                                    {portConflict.owner?.command && (
+                                        <p style={{ margin: '0 0 8px', fontSize: '12px', wordBreak: 'break-all' }}>
+                                            {portConflict.owner.command}
+                                        </p>
+                                    )}
+                                    {portConflict.owner && (
+                                        <button className="btn btn-ghost btn-full" onClick={handleReleasePort} disabled={loading}>
+                                            结束该进程并继续
+                                        </button>
+                                    )}
+                                    <button
+                                        className="btn btn-primary btn-full"
+                                        style={{ marginTop: '8px' }}
+                                        onClick={launchOAuthLogin}
+                                        disabled={loading}
+                                    >
+                                        不占用端口，稍后手动粘贴回调地址
+                                    </button>
+                                </div>
+                            ) : (
+                                <button
+                                    className="btn btn-primary btn-full"
+                                    style={{ padding: '14px' }}
+                                    onClick={handleOpenAILogin}
+                                    disabled={loading}
+                                >
+                                    {loading && oauthStatus ? '处理中...' : '立即登录 OpenAI'}
+                                </button>
+                            )}
 
                             {!loading && (
                                 <button
@@ -194,6 +547,48 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
                                 </button>
                             )}
 
+                            {loading && (
+                                <div className="form-group" style={{ marginTop: '16px' }}>
+                                    <label htmlFor="redirect">浏览器没有自动跳回？</label>
+                                    <textarea
+                                        id="redirect"
+                                        value={redirectInput}
+                                        onChange={e => setRedirectInput(e.target.value)}
+                                        placeholder={pendingCount > 1
+                                            ? '粘贴完整的 http://localhost:1455/auth/callback?code=...&state=... 地址'
+                                            : '粘贴完整的 http://localhost:1455/auth/callback?code=...&state=... 地址，或仅粘贴 code'}
+                                        rows={2}
+                                    />
+                                    <p className="oauth-desc" style={{ margin: '8px 0 0' }}>
+                                        回调地址须包含 state；仅粘贴 code 只在只有一个进行中的登录时可用。
+                                    </p>
+                                    <button
+                                        className="btn btn-ghost btn-full"
+                                        style={{ marginTop: '8px' }}
+                                        onClick={handleSubmitRedirect}
+                                        disabled={!redirectInput.trim()}
+                                    >
+                                        使用回调地址完成登录
+                                    </button>
+                                    {pendingCount > 0 && (
+                                        <button
+                                            className="btn btn-ghost btn-full"
+                                            style={{ marginTop: '8px' }}
+                                            onClick={launchOAuthLogin}
+                                        >
+                                            再登录一个账号
+                                        </button>
+                                    )}
+                                    <button
+                                        className="btn btn-ghost btn-full"
+                                        style={{ marginTop: '8px' }}
+                                        onClick={handleCancelOAuthLogin}
+                                    >
+                                        取消登录
+                                    </button>
+                                </div>
+                            )}
+
                             {oauthStatus && <div className="oauth-status">{oauthStatus}</div>}
                             {error && <div className="error-message" style={{ marginTop: '16px' }}>{error}</div>}
 
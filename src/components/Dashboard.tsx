@@ -57,7 +57,7 @@ export function Dashboard({
                     <div className="card-header">
                         <span className="card-icon">✓</span>
                         <h3>当前账号</h3>
-                        {isCurrentInvalid && <span className="invalid-badge" title="授权已失效，请删除后重新登录">⚠️ 失效</span>}
+                        {isCurrentInvalid && <span className="invalid-badge" title="授权已失效，请在账号列表中重新登录">⚠️ 失效</span>}
                     </div>
                     {currentAccount ? (
                         <div className="current-account-content">
@@ -1,9 +1,12 @@
 import { useState, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import './Settings.css';
-import { AppSettings } from '../hooks/useAccounts';
+import { Account, ActiveWindow, AppSettings, BreakerStatus, ConnectionCheck, JobSettings, JobStatus, NetworkSettings, ProcessScan, SchedulerStatus, SwitchRule } from '../hooks/useAccounts';
 
 interface SettingsProps {
     settings: AppSettings;
+    accounts: Account[];
     onUpdateSettings: (settings: AppSettings) => Promise<void>;
 }
 
@@ -14,12 +17,24 @@ const IDE_OPTIONS = [
     { value: 'VSCode', label: 'VS Code' },
 ];
 
+const DEFAULT_NETWORK: NetworkSettings = {
+    proxy_url: null,
+    proxy_username: null,
+    proxy_password: null,
+    no_proxy: [],
+    ca_cert_paths: [],
+    connect_timeout_secs: 10,
+    request_timeout_secs: 30,
+};
+
+const WEEKDAYS = ['一', '二', '三', '四', '五', '六', '日'];
+
 const THEME_OPTIONS = [
     { value: 'light', label: '浅色 (White)' },
     { value: 'dark', label: '深色 (Dark)' },
 ];
 
-export function Settings({ settings, onUpdateSettings }: SettingsProps) {
+export function Settings({ settings, accounts, onUpdateSettings }: SettingsProps) {
     const [localSettings, setLocalSettings] = useState<AppSettings>(settings);
     const [saving, setSaving] = useState(false);
     const [message, setMessage] = useState<string | null>(null);
@@ -47,6 +62,135 @@ export function Settings({ settings, onUpdateSettings }: SettingsProps) {
         setLocalSettings(prev => ({ ...prev, [key]: value }));
     };
 
+    const network = localSettings.network ?? DEFAULT_NETWORK;
+    const [testing, setTesting] = useState(false);
+    const [checks, setChecks] = useState<ConnectionCheck[] | null>(null);
+    const [breakers, setBreakers] = useState<BreakerStatus[]>([]);
+
+    const updateNetwork = <K extends keyof NetworkSettings>(key: K, value: NetworkSettings[K]) => {
+        setLocalSettings(prev => ({ ...prev, network: { ...(prev.network ?? DEFAULT_NETWORK), [key]: value } }));
+    };
+
+    const splitList = (value: string) => value.split(/[,\n]/).map(s => s.trim()).filter(Boolean);
+
+    const testConnection = async () => {
+        setTesting(true);
+        setChecks(null);
+        try {
+            setChecks(await invoke<ConnectionCheck[]>('test_connection', { settings: network }));
+            setBreakers(await invoke<BreakerStatus[]>('get_network_diagnostics'));
+        } catch (e) {
+            setMessage(`❌ 测试失败: ${e}`);
+        } finally {
+            setTesting(false);
+        }
+    };
+
+    const [scheduler, setScheduler] = useState<SchedulerStatus | null>(null);
+    const [runningJob, setRunningJob] = useState<string | null>(null);
+
+    useEffect(() => {
+        invoke<SchedulerStatus>('get_scheduler_status').then(setScheduler).catch(() => {});
+        const unlisten = listen<SchedulerStatus>('scheduler-status', (event) => setScheduler(event.payload));
+        return () => {
+            unlisten.then(f => f());
+        };
+    }, [settings]);
+
+    const runJobNow = async (job: string) => {
+        setRunningJob(job);
+        try {
+            setScheduler(await invoke<SchedulerStatus>('run_scheduler_now', { job }));
+        } catch (e) {
+            setMessage(`❌ 执行失败: ${e}`);
+        } finally {
+            setRunningJob(null);
+        }
+    };
+
+    // Token 刷新沿用 background_refresh / refresh_interval_minutes，其余任务写入 settings.jobs
+    const updateJob = (job: JobStatus, patch: Partial<JobSettings>) => {
+        const current: JobSettings = localSettings.jobs?.[job.name] ?? {
+            enabled: job.enabled,
+            interval_minutes: job.interval_minutes,
+            jitter_secs: job.jitter_secs,
+        };
+        setLocalSettings(prev => ({ ...prev, jobs: { ...(prev.jobs ?? {}), [job.name]: { ...current, ...patch } } }));
+    };
+
+    const jobSettings = (job: JobStatus): JobSettings => localSettings.jobs?.[job.name] ?? job;
+
+    const formatTime = (value: string | null) => value ? new Date(value).toLocaleString() : '—';
+
+    // 定时切换规则单独保存
+    const [rules, setRules] = useState<SwitchRule[]>([]);
+    const [previewAt, setPreviewAt] = useState('');
+    const [preview, setPreview] = useState<ActiveWindow | null | undefined>(undefined);
+
+    useEffect(() => {
+        invoke<SwitchRule[]>('get_switch_schedules').then(setRules).catch(() => {});
+    }, []);
+
+    // 删除账号时后端会一并移除其规则，本地列表同步去掉
+    useEffect(() => {
+        setRules(prev => prev.filter(rule => !rule.id || accounts.some(a => a.id === rule.account_id)));
+    }, [accounts]);
+
+    useEffect(() => {
+        invoke<ActiveWindow | null>('preview_switch_schedule', { at: previewAt || undefined, rules })
+            .then(setPreview)
+            .catch(() => setPreview(undefined));
+    }, [previewAt, rules]);
+
+    const updateRule = (index: number, patch: Partial<SwitchRule>) => {
+        setRules(prev => prev.map((rule, i) => i === index ? { ...rule, ...patch } : rule));
+    };
+
+    const toggleWeekday = (index: number, day: number) => {
+        const weekdays = rules[index].weekdays;
+        updateRule(index, {
+            weekdays: weekdays.includes(day) ? weekdays.filter(d => d !== day) : [...weekdays, day].sort(),
+        });
+    };
+
+    const addRule = () => {
+        setRules(prev => [...prev, {
+            id: '',
+            name: `规则 ${prev.length + 1}`,
+            account_id: accounts[0]?.id ?? '',
+            weekdays: [1, 2, 3, 4, 5],
+            start: '09:00',
+            end: '18:00',
+            enabled: true,
+        }]);
+    };
+
+    const saveRules = async () => {
+        try {
+            setRules(await invoke<SwitchRule[]>('save_switch_schedules', { rules }));
+            setMessage('✅ 定时切换规则已保存');
+            setTimeout(() => setMessage(null), 3000);
+        } catch (e) {
+            setMessage(`❌ 保存失败: ${e}`);
+        }
+    };
+
+    const [processes, setProcesses] = useState<ProcessScan | null>(null);
+
+    const scanProcesses = async () => {
+        try {
+            setProcesses(await invoke<ProcessScan>('get_ide_processes'));
+        } catch (e) {
+            setMessage(`❌ 检测失败: ${e}`);
+        }
+    };
+
+    useEffect(() => {
+        scanProcesses();
+    }, []);
+
+    const accountName = (id: string) => accounts.find(a => a.id === id)?.name ?? id;
+
     return (
         <div className="settings-page">
             <div className="settings-header">
@@ -87,12 +231,15 @@ export function Settings({ settings, onUpdateSettings }: SettingsProps) {
                 <div className="setting-item">
                     <div className="setting-info">
                         <span className="setting-label">后台自动刷新</span>
-                        <span className="setting-desc">后台自动刷新所有账号的配额信息，这是 Token 保活的基础</span>
+                        <span className="setting-desc">后台定期检查并刷新即将过期的 Token，这是 Token 保活的基础</span>
                     </div>
-                    <label className="toggle always-on">
-                        <input type="checkbox" checked={true} disabled />
+                    <label className="toggle">
+                        <input
+                            type="checkbox"
+                            checked={localSettings.background_refresh}
+                            onChange={e => updateField('background_refresh', e.target.checked)}
+                        />
                         <span className="toggle-slider"></span>
-                        <span className="toggle-text">始终开启</span>
                     </label>
                 </div>
 
@@ -109,6 +256,262 @@ export function Settings({ settings, onUpdateSettings }: SettingsProps) {
                         onChange={e => updateField('refresh_interval_minutes', parseInt(e.target.value) || 30)}
                     />
                 </div>
+
+                {scheduler?.jobs.map(job => (
+                    <div key={job.name} className="setting-item sub-item">
+                        <div className="setting-info">
+                            <span className="setting-label">{job.label}</span>
+                            <span className="setting-desc">
+                                {job.running ? '正在执行...' : `上次: ${formatTime(job.last_run?.last_finished_at ?? null)}${job.last_run?.last_manual ? ' (手动)' : ''}，下次: ${job.enabled ? formatTime(job.next_run_at) : '已关闭'}`}
+                            </span>
+                            {job.last_run?.last_error
+                                ? <span className="setting-desc">❌ {job.last_run.last_error}</span>
+                                : job.last_run?.last_summary && <span className="setting-desc">{job.last_run.last_summary}</span>}
+                        </div>
+                        {job.name !== 'token_refresh' && (
+                            <>
+                                <input
+                                    type="number"
+                                    className="number-input"
+                                    min={1}
+                                    title="间隔（分钟）"
+                                    value={jobSettings(job).interval_minutes}
+                                    onChange={e => updateJob(job, { interval_minutes: parseInt(e.target.value) || job.interval_minutes })}
+                                />
+                                <label className="toggle">
+                                    <input
+                                        type="checkbox"
+                                        checked={jobSettings(job).enabled}
+                                        onChange={e => updateJob(job, { enabled: e.target.checked })}
+                                    />
+                                    <span className="toggle-slider"></span>
+                                </label>
+                            </>
+                        )}
+                        <button className="save-button" onClick={() => runJobNow(job.name)} disabled={runningJob !== null || job.running}>
+                            {runningJob === job.name ? '执行中...' : '立即执行'}
+                        </button>
+                    </div>
+                ))}
+
+                <div className="setting-item sub-item">
+                    <div className="setting-info">
+                        <span className="setting-label">漂移时重写 auth.json</span>
+                        <span className="setting-desc">漂移检查确认存储中的令牌更新时，用其覆盖 ~/.codex/auth.json；关闭时只报告</span>
+                    </div>
+                    <label className="toggle">
+                        <input
+                            type="checkbox"
+                            checked={localSettings.rewrite_auth_json_on_drift ?? false}
+                            onChange={e => updateField('rewrite_auth_json_on_drift', e.target.checked)}
+                        />
+                        <span className="toggle-slider"></span>
+                    </label>
+                </div>
+            </div>
+
+            <div className="settings-section">
+                <h3>定时切换</h3>
+
+                {rules.map((rule, index) => (
+                    <div key={rule.id || index} className="setting-item sub-item">
+                        <div className="setting-info">
+                            <input
+                                type="text"
+                                className="text-input"
+                                value={rule.name}
+                                onChange={e => updateRule(index, { name: e.target.value })}
+                            />
+                            <span className="setting-desc">
+                                {WEEKDAYS.map((label, i) => (
+                                    <label key={label}>
+                                        <input
+                                            type="checkbox"
+                                            checked={rule.weekdays.includes(i + 1)}
+                                            onChange={() => toggleWeekday(index, i + 1)}
+                                        />
+                                        {label}
+                                    </label>
+                                ))}
+                                {rule.weekdays.length === 0 && '（每天）'}
+                            </span>
+                        </div>
+                        <input type="time" className="text-input" value={rule.start} onChange={e => updateRule(index, { start: e.target.value })} />
+                        <input type="time" className="text-input" value={rule.end} onChange={e => updateRule(index, { end: e.target.value })} />
+                        <select
+                            className="select-input"
+                            value={rule.account_id}
+                            onChange={e => updateRule(index, { account_id: e.target.value })}
+                        >
+                            {accounts.map(account => (
+                                <option key={account.id} value={account.id}>{account.name}</option>
+                            ))}
+                        </select>
+                        <label className="toggle">
+                            <input
+                                type="checkbox"
+                                checked={rule.enabled}
+                                onChange={e => updateRule(index, { enabled: e.target.checked })}
+                            />
+                            <span className="toggle-slider"></span>
+                        </label>
+                        <button className="save-button" onClick={() => setRules(prev => prev.filter((_, i) => i !== index))}>删除</button>
+                    </div>
+                ))}
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">预览</span>
+                        <span className="setting-desc">
+                            {preview === undefined
+                                ? '—'
+                                : preview
+                                    ? `${previewAt ? '该时刻' : '当前'}使用「${accountName(preview.account_id)}」（规则「${preview.rule_name}」）`
+                                    : `${previewAt ? '该时刻' : '当前'}不在任何时段内`}
+                        </span>
+                    </div>
+                    <input
+                        type="datetime-local"
+                        className="text-input"
+                        value={previewAt}
+                        onChange={e => setPreviewAt(e.target.value)}
+                    />
+                    <button className="save-button" onClick={addRule} disabled={accounts.length === 0}>添加规则</button>
+                    <button className="save-button" onClick={saveRules}>保存规则</button>
+                </div>
+            </div>
+
+            <div className="settings-section">
+                <h3>网络</h3>
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">代理地址</span>
+                        <span className="setting-desc">如 http://proxy.corp:8080，留空则使用系统环境变量</span>
+                    </div>
+                    <input
+                        type="text"
+                        className="text-input"
+                        value={network.proxy_url ?? ''}
+                        onChange={e => updateNetwork('proxy_url', e.target.value || null)}
+                    />
+                </div>
+
+                <div className="setting-item sub-item">
+                    <div className="setting-info">
+                        <span className="setting-label">代理用户名 / 密码</span>
+                    </div>
+                    <input
+                        type="text"
+                        className="text-input"
+                        value={network.proxy_username ?? ''}
+                        onChange={e => updateNetwork('proxy_username', e.target.value || null)}
+                    />
+                    <input
+                        type="password"
+                        className="text-input"
+                        value={network.proxy_password ?? ''}
+                        onChange={e => updateNetwork('proxy_password', e.target.value || null)}
+                    />
+                </div>
+
+                <div className="setting-item sub-item">
+                    <div className="setting-info">
+                        <span className="setting-label">不走代理的主机</span>
+                        <span className="setting-desc">逗号分隔，如 localhost,.corp.example</span>
+                    </div>
+                    <input
+                        type="text"
+                        className="text-input"
+                        value={network.no_proxy.join(',')}
+                        onChange={e => updateNetwork('no_proxy', splitList(e.target.value))}
+                    />
+                </div>
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">自定义根证书</span>
+                        <span className="setting-desc">PEM 文件路径，多个用逗号分隔</span>
+                    </div>
+                    <input
+                        type="text"
+                        className="text-input"
+                        value={network.ca_cert_paths.join(',')}
+                        onChange={e => updateNetwork('ca_cert_paths', splitList(e.target.value))}
+                    />
+                </div>
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">连接 / 请求超时（秒）</span>
+                    </div>
+                    <input
+                        type="number"
+                        className="number-input"
+                        min={1}
+                        value={network.connect_timeout_secs}
+                        onChange={e => updateNetwork('connect_timeout_secs', parseInt(e.target.value) || 10)}
+                    />
+                    <input
+                        type="number"
+                        className="number-input"
+                        min={1}
+                        value={network.request_timeout_secs}
+                        onChange={e => updateNetwork('request_timeout_secs', parseInt(e.target.value) || 30)}
+                    />
+                </div>
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">测试连接</span>
+                        {checks && checks.map(c => (
+                            <span key={c.target} className="setting-desc">
+                                {c.ok ? '✅' : '❌'} {c.target}: {c.ok ? `HTTP ${c.status} (${c.elapsed_ms}ms)` : c.error}
+                            </span>
+                        ))}
+                        {breakers.filter(b => b.state !== 'closed').map(b => (
+                            <span key={b.host} className="setting-desc">
+                                ⛔ {b.host} 已熔断 ({b.consecutive_failures} 次连续失败{b.retry_in_secs !== null ? `，${b.retry_in_secs}s 后重试` : ''}): {b.last_error}
+                            </span>
+                        ))}
+                    </div>
+                    <button className="save-button" onClick={testConnection} disabled={testing}>
+                        {testing ? '测试中...' : '测试'}
+                    </button>
+                </div>
+            </div>
+
+            <div className="settings-section">
+                <h3>OAuth 登录</h3>
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">删除时撤销令牌</span>
+                        <span className="setting-desc">删除账号前在服务端撤销其 Token，离线时仅删除本地数据</span>
+                    </div>
+                    <label className="toggle">
+                        <input
+                            type="checkbox"
+                            checked={localSettings.revoke_on_delete ?? false}
+                            onChange={e => updateField('revoke_on_delete', e.target.checked)}
+                        />
+                        <span className="toggle-slider"></span>
+                    </label>
+                </div>
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">等待授权超时（秒）</span>
+                        <span className="setting-desc">超时后结束本地回调监听，需要重新登录</span>
+                    </div>
+                    <input
+                        type="number"
+                        className="number-input"
+                        min={30}
+                        value={localSettings.oauth_timeout_secs ?? 300}
+                        onChange={e => setLocalSettings(prev => ({ ...prev, oauth_timeout_secs: parseInt(e.target.value) || 300 }))}
+                    />
+                </div>
             </div>
 
             <div className="settings-section">
@@ -129,6 +532,26 @@ export function Settings({ settings, onUpdateSettings }: SettingsProps) {
                     </label>
                 </div>
 
+                {processes?.supported && (
+                    <div className="setting-item sub-item">
+                        <div className="setting-info">
+                            <span className="setting-label">运行中的 IDE</span>
+                            {processes.ides.length === 0 && <span className="setting-desc">未检测到</span>}
+                            {processes.ides.map(ide => (
+                                <span key={ide.pid} className="setting-desc" title={ide.exe}>
+                                    {ide.ide} (PID {ide.pid}){ide.has_codex_child ? ` · Codex 服务 ${ide.codex_pids.join(', ')}` : ' · 无 Codex 服务'}
+                                </span>
+                            ))}
+                            {processes.codex.filter(c => c.ide_pid === null).length > 0 && (
+                                <span className="setting-desc">
+                                    独立运行的 Codex: {processes.codex.filter(c => c.ide_pid === null).map(c => c.pid).join(', ')}
+                                </span>
+                            )}
+                        </div>
+                        <button className="save-button" onClick={scanProcesses}>重新检测</button>
+                    </div>
+                )}
+
                 {localSettings.auto_reload_ide && (
                     <>
                         <div className="setting-item sub-item">
@@ -35,6 +35,15 @@ export function UsageCard({ usage, loading, error, onRefresh }: UsageCardProps)
 
     return (
         <div className="usage-meters">
+            {usage.offline && (
+                <div className="usage-row">
+                    <span className="usage-label">📴 离线缓存</span>
+                    <span className="usage-reset">
+                        {usage.cached_at ? `更新于 ${new Date(usage.cached_at).toLocaleString()}` : ''}
+                    </span>
+                </div>
+            )}
+
             {/* FREE accounts only have one quota (shown in five_hour_* fields) */}
             {usage.plan_type?.toLowerCase() === 'free' ? (
                 <>
@@ -81,6 +90,23 @@ export function UsageCard({ usage, loading, error, onRefresh }: UsageCardProps)
                 </>
             )}
 
+            {/* 后端返回的额外限额 */}
+            {usage.windows?.filter(w => w.bucket !== 'codex').map(w => (
+                <div key={`${w.bucket}-${w.name}`}>
+                    <div className="usage-row">
+                        <span className="usage-label">{w.bucket} · {w.name}</span>
+                        <span className="usage-reset">{w.reset}</span>
+                        <span className="usage-percent">{w.left_percent}%</span>
+                    </div>
+                    <div className="meter-bar">
+                        <div
+                            className={`meter-fill ${getColorClass(w.left_percent)}`}
+                            style={{ width: `${w.left_percent}%` }}
+                        />
+                    </div>
+                </div>
+            ))}
+
             {/* 额度 */}
             {usage.has_credits && usage.credits_balance !== null && (
                 <div className="usage-credits">
@@ -1,5 +1,6 @@
 import { useState, useEffect, useCallback } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import type { RateWindow } from './useUsage';
 
 export interface CachedQuota {
     five_hour_left: number;
@@ -10,16 +11,61 @@ export interface CachedQuota {
     weekly_reset_at?: number;
     plan_type: string;
     is_valid_for_cli?: boolean;
+    windows?: RateWindow[];
     updated_at: string;
 }
 
+export interface EndpointConfig {
+    client_id: string;
+    auth_url: string;
+    token_url: string;
+    usage_url: string;
+    accounts_url: string;
+    device_auth_url: string;
+}
+
+export interface NetworkSettings {
+    proxy_url?: string | null;
+    proxy_username?: string | null;
+    proxy_password?: string | null;
+    no_proxy: string[];
+    ca_cert_paths: string[];
+    connect_timeout_secs: number;
+    request_timeout_secs: number;
+}
+
+export interface ConnectionCheck {
+    target: string;
+    url: string;
+    ok: boolean;
+    status: number | null;
+    elapsed_ms: number;
+    error: string | null;
+}
+
+export interface BreakerStatus {
+    host: string;
+    state: 'closed' | 'open' | 'half_open';
+    consecutive_failures: number;
+    total_failures: number;
+    retry_in_secs: number | null;
+    last_error: string | null;
+    last_failure_at: string | null;
+}
+
 export interface AppSettings {
     auto_reload_ide: boolean;
     primary_ide: string;
     use_pkill_restart: boolean;
     background_refresh: boolean;
     refresh_interval_minutes: number;
+    jobs?: Record<string, JobSettings>;
     theme: string;
+    endpoints?: EndpointConfig;
+    network?: NetworkSettings;
+    revoke_on_delete?: boolean;
+    oauth_timeout_secs?: number;
+    rewrite_auth_json_on_drift?: boolean;
 }
 
 export interface Account {
@@ -28,8 +74,237 @@ export interface Account {
     auth_json: unknown;
     created_at: string;
     last_used: string | null;
+    refresh_token_updated_at?: string | null;
     notes: string | null;
     cached_quota: CachedQuota | null;
+    /** 刷新健康状态，刷新正常时为空 */
+    refresh_health?: RefreshHealth | null;
+    /** 导入时未能校验身份的原因 */
+    unverified?: string | null;
+}
+
+export type ImportStatus =
+    | { status: 'verified' }
+    | { status: 'unverified' | 'rejected'; reason: string };
+
+export interface ImportRecord {
+    account_id: string;
+    account_name: string;
+    status: ImportStatus;
+}
+
+export interface RefreshHealth {
+    consecutive_failures: number;
+    revoked_failures: number;
+    last_error_class: 'revoked' | 'network' | 'server';
+    last_error: string;
+    first_failed_at: string;
+    last_failed_at: string;
+    needs_relogin: boolean;
+}
+
+/** 刷新健康状态变化事件 (account-health-changed)，health 为空表示已恢复 */
+export interface HealthChange {
+    account_id: string;
+    account_name: string;
+    health: RefreshHealth | null;
+}
+
+/** 存储变更事件 (store-changed) */
+export interface StoreChange {
+    revision: number;
+    changed_at: string;
+}
+
+export interface JobSettings {
+    enabled: boolean;
+    interval_minutes: number;
+    jitter_secs: number;
+}
+
+export interface JobRecord {
+    last_started_at: string | null;
+    last_finished_at: string | null;
+    last_success_at: string | null;
+    last_error: string | null;
+    last_summary: string | null;
+    last_duration_ms: number;
+    last_manual: boolean;
+    run_count: number;
+}
+
+/** 后台任务状态 */
+export interface JobStatus extends JobSettings {
+    name: string;
+    label: string;
+    running: boolean;
+    next_run_at: string | null;
+    last_run: JobRecord | null;
+    details: unknown;
+}
+
+export interface SchedulerStatus {
+    jobs: JobStatus[];
+}
+
+export interface SwitchRule {
+    id: string;
+    name: string;
+    account_id: string;
+    /** 1 = 周一 … 7 = 周日，为空表示每天 */
+    weekdays: number[];
+    /** 本地时间 "HH:MM"，结束早于开始表示跨越午夜 */
+    start: string;
+    end: string;
+    enabled: boolean;
+}
+
+export interface ActiveWindow {
+    rule_id: string;
+    rule_name: string;
+    account_id: string;
+    started_at: string;
+}
+
+export type ScheduleOutcome =
+    | { status: 'idle' | 'already_applied' }
+    | { status: 'already_current' | 'needs_relogin'; account_id: string }
+    | { status: 'switched' | 'missing_account'; account_id: string; rule_id: string };
+
+export interface CodexProcess {
+    pid: number;
+    ppid: number;
+    exe: string;
+    kind: 'app_server' | 'cli';
+    /** 所属 IDE 主进程 */
+    ide_pid: number | null;
+}
+
+export interface IdeProcess {
+    pid: number;
+    exe: string;
+    ide: string;
+    has_codex_child: boolean;
+    codex_pids: number[];
+}
+
+/** 进程扫描结果 (目前仅 Linux 支持) */
+export interface ProcessScan {
+    supported: boolean;
+    ides: IdeProcess[];
+    codex: CodexProcess[];
+}
+
+/** 单个 IDE 的重载结果 */
+export interface IdeReloadReport {
+    ide: string;
+    pids: number[];
+    actions: string[];
+    /** 收到 SIGTERM 后退出的 Codex 进程 */
+    terminated: number[];
+    /** 超时后被强制结束的 Codex 进程 */
+    killed: number[];
+    error: string | null;
+}
+
+export type RevokeOutcome =
+    | { status: 'revoked' | 'no_token' | 'shared' | 'offline' }
+    | { status: 'failed'; error: string };
+
+export interface RevocationRecord {
+    account_id: string;
+    account_name: string;
+    refresh_token: RevokeOutcome;
+    access_token: RevokeOutcome;
+    deleted_at: string;
+}
+
+export interface Organization {
+    id: string;
+    title: string | null;
+    role: string | null;
+    is_default: boolean;
+}
+
+export interface TokenTimes {
+    issued_at: string | null;
+    expires_at: string | null;
+    expired: boolean;
+}
+
+export interface AccountClaims {
+    id: string;
+    email: string | null;
+    user_id: string | null;
+    chatgpt_account_id: string | null;
+    plan_type: string | null;
+    subscription_active_start: string | null;
+    subscription_active_until: string | null;
+    subscription_last_checked: string | null;
+    organizations: Organization[];
+    id_token: TokenTimes | null;
+    access_token: TokenTimes | null;
+    refresh_token_updated_at: string | null;
+    refresh_token_age_secs: number | null;
+}
+
+export interface Workspace {
+    id: string;
+    title: string | null;
+    role: string | null;
+    plan_type: string | null;
+    is_default: boolean;
+    saved_account_id: string | null;
+}
+
+export interface WorkspaceTarget {
+    id: string;
+    title: string | null;
+}
+
+export interface PortOwner {
+    pid: number;
+    name: string;
+    command: string | null;
+    is_codex: boolean;
+}
+
+export interface PortCheck {
+    port: number;
+    available: boolean;
+    owner: PortOwner | null;
+}
+
+export interface OAuthLoginStart {
+    state: string;
+    auth_url: string;
+    port: number;
+}
+
+export interface OAuthCallback {
+    state: string;
+    code: string;
+}
+
+export interface OAuthProgress {
+    state: string | null;
+    stage: 'listening' | 'browser_opened' | 'callback_received' | 'exchanging' | 'completed' | 'failed' | 'timed_out' | 'cancelled';
+    message: string | null;
+}
+
+export interface DeviceLoginInfo {
+    login_id: string;
+    user_code: string;
+    verification_uri: string;
+    expires_in: number;
+    interval: number;
+}
+
+export interface DeviceLoginEvent {
+    login_id: string;
+    status: 'completed' | 'failed' | 'cancelled';
+    account: Account | null;
+    error: string | null;
 }
 
 export function useAccounts() {
@@ -91,8 +366,9 @@ export function useAccounts() {
     const importCurrent = useCallback(async (name: string, notes?: string) => {
         try {
             setError(null);
-            await invoke('import_current_account', { name, notes });
+            const account = await invoke<Account>('import_current_account', { name, notes });
             await loadData();
+            return account;
         } catch (err) {
             setError(String(err));
             throw err;
@@ -113,11 +389,25 @@ export function useAccounts() {
     }, [loadData]);
 
     // 删除账号
-    const deleteAccount = useCallback(async (id: string) => {
+    const deleteAccount = useCallback(async (id: string, revoke?: boolean) => {
         try {
             setError(null);
-            await invoke('delete_account', { id });
+            const record = await invoke<RevocationRecord | null>('delete_account', { id, revoke });
             await loadData();
+            return record;
+        } catch (err) {
+            setError(String(err));
+            throw err;
+        }
+    }, [loadData]);
+
+    // 批量 (撤销并) 删除账号
+    const deleteAccounts = useCallback(async (ids: string[], revoke?: boolean) => {
+        try {
+            setError(null);
+            const records = await invoke<RevocationRecord[]>('delete_accounts', { ids, revoke });
+            await loadData();
+            return records;
         } catch (err) {
             setError(String(err));
             throw err;
@@ -150,8 +440,9 @@ export function useAccounts() {
     const importAccounts = useCallback(async (json: string) => {
         try {
             setError(null);
-            await invoke('import_accounts', { json });
+            const records = await invoke<ImportRecord[]>('import_accounts', { json });
             await loadData();
+            return records;
         } catch (err) {
             setError(String(err));
             throw err;
@@ -168,21 +459,36 @@ export function useAccounts() {
     }, []);
 
     // 开始 OAuth 登录
-    const startOAuthLogin = useCallback(async () => {
+    const startOAuthLogin = useCallback(async (workspace?: WorkspaceTarget) => {
         try {
             setError(null);
-            return await invoke<string>('start_oauth_login');
+            return await invoke<OAuthLoginStart>('start_oauth_login', { workspace: workspace ?? null });
         } catch (err) {
             setError(String(err));
             throw err;
         }
     }, []);
 
+    // 检测 OAuth 回调端口占用情况
+    const checkOAuthPort = useCallback(async () => {
+        return await invoke<PortCheck>('check_oauth_port');
+    }, []);
+
+    // 结束占用回调端口的进程 (用户确认后)
+    const releaseOAuthPort = useCallback(async (pid: number) => {
+        await invoke('release_oauth_port', { pid });
+    }, []);
+
+    // 取消浏览器登录 (不传 state 时取消全部)
+    const cancelOAuthLogin = useCallback(async (state?: string) => {
+        await invoke('cancel_oauth_login', { state });
+    }, []);
+
     // 完成 OAuth 登录
-    const finalizeOAuthLogin = useCallback(async (code: string) => {
+    const finalizeOAuthLogin = useCallback(async (code: string, oauthState?: string) => {
         try {
             setError(null);
-            const account = await invoke<Account>('finalize_oauth_login', { code });
+            const account = await invoke<Account>('finalize_oauth_login', { code, oauthState });
             await loadData();
             return account;
         } catch (err) {
@@ -191,11 +497,58 @@ export function useAccounts() {
         }
     }, [loadData]);
 
+    // 手动粘贴回调地址完成登录
+    const submitOAuthRedirect = useCallback(async (input: string) => {
+        try {
+            setError(null);
+            const account = await invoke<Account>('submit_oauth_redirect', { input });
+            await loadData();
+            return account;
+        } catch (err) {
+            setError(String(err));
+            throw err;
+        }
+    }, [loadData]);
+
+    // 开始设备码登录
+    const startDeviceLogin = useCallback(async () => {
+        try {
+            setError(null);
+            return await invoke<DeviceLoginInfo>('start_device_login');
+        } catch (err) {
+            setError(String(err));
+            throw err;
+        }
+    }, []);
+
+    // 取消设备码登录
+    const cancelDeviceLogin = useCallback(async (loginId: string) => {
+        try {
+            await invoke('cancel_device_login', { loginId });
+        } catch {
+            // 流程可能已经结束
+        }
+    }, []);
+
+    // 查看账号令牌声明
+    const getAccountClaims = useCallback(async (id: string) => {
+        return await invoke<AccountClaims>('get_account_claims', { id });
+    }, []);
+
+    const listAccountClaims = useCallback(async () => {
+        return await invoke<AccountClaims[]>('list_account_claims');
+    }, []);
+
+    // 工作区选择
+    const listAccountWorkspaces = useCallback(async (id: string) => {
+        return await invoke<Workspace[]>('list_account_workspaces', { id });
+    }, []);
+
     // 重载 IDE 窗口
     const reloadIdeWindows = useCallback(async (useWindowReload: boolean = false) => {
         try {
             setError(null);
-            return await invoke<string[]>('reload_ide_windows', { useWindowReload });
+            return await invoke<IdeReloadReport[]>('reload_ide_windows', { useWindowReload });
         } catch (err) {
             setError(String(err));
             throw err;
@@ -212,13 +565,23 @@ export function useAccounts() {
         importCurrent,
         switchTo,
         deleteAccount,
+        deleteAccounts,
         updateAccount,
         exportAccounts,
         importAccounts,
         checkCodexLogin,
         startOAuthLogin,
+        checkOAuthPort,
+        releaseOAuthPort,
+        cancelOAuthLogin,
         finalizeOAuthLogin,
+        submitOAuthRedirect,
+        startDeviceLogin,
+        cancelDeviceLogin,
         reloadIdeWindows,
+        getAccountClaims,
+        listAccountClaims,
+        listAccountWorkspaces,
         updateSettings,
     };
 }
@@ -1,5 +1,16 @@
 import { useState, useEffect, useCallback } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+
+export interface RateWindow {
+    bucket: string;
+    name: string;
+    window_seconds: number | null;
+    used_percent: number;
+    left_percent: number;
+    reset: string;
+    reset_at?: number;
+}
 
 export interface UsageDisplay {
     plan_type: string;
@@ -13,6 +24,31 @@ export interface UsageDisplay {
     weekly_reset_at?: number;
     credits_balance: number | null;
     has_credits: boolean;
+    windows?: RateWindow[];
+    raw?: unknown;
+    offline?: boolean;
+    cached_at?: string | null;
+}
+
+export interface WindowChange {
+    bucket: string;
+    name: string;
+    left_before: number | null;
+    left_after: number | null;
+    reset_at_before: number | null;
+    reset_at_after: number | null;
+}
+
+/** 后台轮询发现的配额变化 (quota-updated 事件) */
+export interface QuotaDiff {
+    account_id: string;
+    account_name: string;
+    first_poll: boolean;
+    windows: WindowChange[];
+    plan_type_before: string | null;
+    plan_type_after: string;
+    valid_for_cli_before: boolean | null;
+    valid_for_cli_after: boolean;
 }
 
 export function useUsage() {
@@ -45,6 +81,19 @@ export function useUsage() {
         fetchUsage();
     }, [fetchUsage]);
 
+    // 后台轮询发现当前账号配额变化时更新
+    useEffect(() => {
+        const unlisten = listen<QuotaDiff[]>('quota-updated', async (event) => {
+            const currentId = await invoke<string | null>('get_current_account_id');
+            if (event.payload.some(diff => diff.account_id === currentId)) {
+                fetchUsage();
+            }
+        });
+        return () => {
+            unlisten.then(f => f());
+        };
+    }, [fetchUsage]);
+
     return {
         usage,
         loading,